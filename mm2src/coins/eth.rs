@@ -58,7 +58,7 @@ use std::time::Duration;
 use web3::{ self, Web3 };
 use web3::types::{Action as TraceAction, BlockId, BlockNumber, Bytes, CallRequest, FilterBuilder, Log, Transaction as Web3Transaction, TransactionId, H256, Trace, TraceFilterBuilder};
 
-use super::{CoinsContext, FoundSwapTxSpend, HistorySyncState, MarketCoinOps, MmCoin, SwapOps, TradeInfo,
+use super::{publish_balance_change, CoinsContext, FoundSwapTxSpend, HistorySyncState, MarketCoinOps, MmCoin, SwapOps, TradeInfo,
             TransactionFut, TransactionEnum, Transaction, TransactionDetails, WithdrawFee, WithdrawRequest};
 
 pub use ethcore_transaction::SignedTransaction as SignedEthTx;
@@ -1258,6 +1258,7 @@ impl EthCoin {
     fn process_erc20_history(&self, token_addr: H160, ctx: &MmArc) {
         let delta = U256::from(10000);
 
+        let mut last_balance = None;
         loop {
             if ctx.is_stopping() { break };
             {
@@ -1269,6 +1270,10 @@ impl EthCoin {
                 };
             }
 
+            if let Ok(balance) = MarketCoinOps::my_balance(self).wait() {
+                publish_balance_change(ctx, &self.ticker, balance, &mut last_balance);
+            }
+
             let current_block = match self.web3.eth().block_number().wait() {
                 Ok(block) => block,
                 Err(e) => {
@@ -1495,6 +1500,7 @@ impl EthCoin {
         // Also the Parity RPC server seem to get stuck while request in running (other requests performance is also lowered).
         let delta = U256::from(1000);
 
+        let mut last_balance = None;
         loop {
             if ctx.is_stopping() { break };
             {
@@ -1506,6 +1512,10 @@ impl EthCoin {
                 };
             }
 
+            if let Ok(balance) = MarketCoinOps::my_balance(self).wait() {
+                publish_balance_change(ctx, &self.ticker, balance, &mut last_balance);
+            }
+
             let current_block = match self.web3.eth().block_number().wait() {
                 Ok(block) => block,
                 Err(e) => {
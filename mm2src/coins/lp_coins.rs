@@ -33,6 +33,8 @@
 
 use bigdecimal::BigDecimal;
 use common::{HyRes, rpc_response, rpc_err_response};
+use common::event_stream::EventTopic;
+use common::rpc_error::{rpc_typed_err_response, RpcError, RpcErrorCode};
 use common::mm_ctx::{from_ctx, MmArc};
 use common::mm_number::MmNumber;
 use futures01::Future;
@@ -434,6 +436,14 @@ impl CoinsContext {
     }
 }
 
+/// The configuration of the `ticker` coin from the "coins" of the node `conf`, `Json::Null` if the coin isn't configured.
+pub fn coin_conf (conf: &Json, ticker: &str) -> Json {
+    match conf["coins"].as_array() {
+        Some (coins) => coins.iter().find (|coin| coin["coin"].as_str() == Some (ticker)) .cloned() .unwrap_or (Json::Null),
+        None => Json::Null
+    }
+}
+
 /// Adds a new currency into the list of currencies configured.
 ///
 /// Returns an error if the currency already exists. Initializing the same currency twice is a bad habit
@@ -445,9 +455,7 @@ pub async fn lp_coininit (ctx: &MmArc, ticker: &str, req: &Json) -> Result<MmCoi
     let cctx = try_s! (CoinsContext::from_ctx (ctx));
     if cctx.coins.lock().await.get (ticker) .is_some() {return ERR! ("Coin {} already initialized", ticker)}
 
-    let coins_en = if let Some (coins) = ctx.conf["coins"].as_array() {
-        coins.iter().find (|coin| coin["coin"].as_str() == Some (ticker)) .unwrap_or (&Json::Null)
-    } else {&Json::Null};
+    let coins_en = &coin_conf (&ctx.conf, ticker);
 
     if coins_en.is_null() {
         ctx.log.log ("😅", &[&("coin" as &str), &ticker, &("no-conf" as &str)],
@@ -495,6 +503,18 @@ pub async fn lp_coininit (ctx: &MmArc, ticker: &str, req: &Json) -> Result<MmCoi
     Ok (coin)
 }
 
+/// Invoked from the coin history loops, pushes the `BalanceChanged` event to the `event_stream` subscribers
+/// when the `balance` differs from the `last_balance` the loop has seen on the previous iteration.
+pub fn publish_balance_change(ctx: &MmArc, ticker: &str, balance: BigDecimal, last_balance: &mut Option<BigDecimal>) {
+    if last_balance.as_ref() == Some(&balance) {return}
+    ctx.event_stream.publish(EventTopic::Coin, ticker, "BalanceChanged", json!({
+        "coin": ticker,
+        "balance": balance,
+        "previous_balance": last_balance,
+    }));
+    *last_balance = Some(balance);
+}
+
 /// NB: Returns only the enabled (aka active) coins.
 pub async fn lp_coinfind (ctx: &MmArc, ticker: &str) -> Result<Option<MmCoinEnum>, String> {
     let cctx = try_s! (CoinsContext::from_ctx (ctx));
@@ -503,13 +523,13 @@ pub async fn lp_coinfind (ctx: &MmArc, ticker: &str) -> Result<Option<MmCoinEnum
 }
 
 pub fn withdraw (ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h! (req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th! (InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let coin = match block_on (lp_coinfind (&ctx, &ticker)) {
         Ok (Some (t)) => t,
-        Ok (None) => return rpc_err_response (500, &fomat! ("No such coin: " (ticker))),
+        Ok (None) => return rpc_typed_err_response (RpcError::new (RpcErrorCode::CoinNotEnabled, fomat! ("No such coin: " (ticker)))),
         Err (err) => return rpc_err_response (500, &fomat! ("!lp_coinfind(" (ticker) "): " (err)))
     };
-    let withdraw_req: WithdrawRequest = try_h!(json::from_value(req));
+    let withdraw_req: WithdrawRequest = try_th!(InvalidRequest, json::from_value(req));
     Box::new(coin.withdraw(withdraw_req).and_then(|res| {
         let body = try_h!(json::to_string(&res));
         rpc_response(200, body)
@@ -517,17 +537,18 @@ pub fn withdraw (ctx: MmArc, req: Json) -> HyRes {
 }
 
 pub fn send_raw_transaction (ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h! (req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th! (InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let coin = match block_on (lp_coinfind (&ctx, &ticker)) {
         Ok (Some (t)) => t,
-        Ok (None) => return rpc_err_response (500, &fomat! ("No such coin: " (ticker))),
+        Ok (None) => return rpc_typed_err_response (RpcError::new (RpcErrorCode::CoinNotEnabled, fomat! ("No such coin: " (ticker)))),
         Err (err) => return rpc_err_response (500, &fomat! ("!lp_coinfind(" (ticker) "): " (err)))
     };
-    let bytes_string = try_h! (req["tx_hex"].as_str().ok_or ("No 'tx_hex' field"));
-    Box::new(coin.send_raw_tx(&bytes_string).and_then(|res| {
-        rpc_response(200, json!({
+    let bytes_string = try_th! (InvalidRequest, req["tx_hex"].as_str().ok_or ("No 'tx_hex' field"));
+    Box::new(coin.send_raw_tx(&bytes_string).then(|res| match res {
+        Ok(res) => rpc_response(200, json!({
             "tx_hash": res
-        }).to_string())
+        }).to_string()),
+        Err(err) => rpc_typed_err_response(RpcError::new(RpcErrorCode::TransportError, err))
     }))
 }
 
@@ -541,18 +562,30 @@ pub enum HistorySyncState {
     Finished,
 }
 
+impl HistorySyncState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistorySyncState::NotEnabled => "NotEnabled",
+            HistorySyncState::NotStarted => "NotStarted",
+            HistorySyncState::InProgress(_) => "InProgress",
+            HistorySyncState::Error(_) => "Error",
+            HistorySyncState::Finished => "Finished",
+        }
+    }
+}
+
 /// Returns the transaction history of selected coin. Returns no more than `limit` records (default: 10).
 /// Skips the first `skip` records (default: 0).
 /// Transactions are sorted by number of confirmations in ascending order.
 pub fn my_tx_history(ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h!(req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th!(InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let coin = match block_on(lp_coinfind(&ctx, &ticker)) {
         Ok(Some(t)) => t,
-        Ok(None) => return rpc_err_response(500, &fomat!("No such coin: " (ticker))),
+        Ok(None) => return rpc_typed_err_response(RpcError::new(RpcErrorCode::CoinNotEnabled, fomat!("No such coin: " (ticker)))),
         Err(err) => return rpc_err_response(500, &fomat!("!lp_coinfind(" (ticker) "): " (err)))
     };
     let limit = req["limit"].as_u64().unwrap_or(10);
-    let from_id: Option<BytesJson> = try_h!(json::from_value(req["from_id"].clone()));
+    let from_id: Option<BytesJson> = try_th!(InvalidRequest, json::from_value(req["from_id"].clone()));
     let file_path = coin.tx_history_path(&ctx);
     let content = slurp(&file_path);
     let history: Vec<TransactionDetails> = match json::from_slice(&content) {
@@ -565,10 +598,11 @@ pub fn my_tx_history(ctx: MmArc, req: Json) -> HyRes {
         }
     };
     let total_records = history.len();
-    Box::new(coin.current_block().and_then(move |block_number| {
+    Box::new(coin.current_block().then(move |block_number| {
+        let block_number = try_th!(TransportError, block_number);
         let skip = match &from_id {
             Some(id) => {
-                try_h!(history.iter().position(|item| item.internal_id == *id).ok_or(format!("from_id {:02x} is not found", id))) + 1
+                try_th!(InvalidRequest, history.iter().position(|item| item.internal_id == *id).ok_or(format!("from_id {:02x} is not found", id))) + 1
             },
             None => 0,
         };
@@ -602,10 +636,10 @@ pub fn my_tx_history(ctx: MmArc, req: Json) -> HyRes {
 }
 
 pub fn get_trade_fee(ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h!(req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th!(InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let coin = match block_on(lp_coinfind(&ctx, &ticker)) {
         Ok(Some(t)) => t,
-        Ok(None) => return rpc_err_response(500, &fomat!("No such coin: " (ticker))),
+        Ok(None) => return rpc_typed_err_response(RpcError::new(RpcErrorCode::CoinNotEnabled, fomat!("No such coin: " (ticker)))),
         Err(err) => return rpc_err_response(500, &fomat!("!lp_coinfind(" (ticker) "): " (err)))
     };
     coin.get_trade_fee()
@@ -629,6 +663,32 @@ pub fn get_enabled_coins(ctx: MmArc) -> HyRes {
     }).to_string())
 }
 
+/// The state of an enabled coin sampled by the "/metrics" endpoint.
+pub struct CoinMetrics {
+    pub ticker: String,
+    pub history_sync: &'static str,
+    /// The Electrum server addresses and whether we're connected to them.
+    pub electrum_connections: Vec<(String, bool)>,
+}
+
+pub async fn coins_metrics(ctx: &MmArc) -> Result<Vec<CoinMetrics>, String> {
+    let coins_ctx = try_s!(CoinsContext::from_ctx(&ctx));
+    let coins: Vec<MmCoinEnum> = coins_ctx.coins.lock().await.values().cloned().collect();
+    let mut metrics = Vec::with_capacity(coins.len());
+    for coin in coins {
+        let electrum_connections = match coin {
+            MmCoinEnum::UtxoCoin(ref utxo) => utxo.electrum_connections_state().await,
+            _ => Vec::new(),
+        };
+        metrics.push(CoinMetrics {
+            ticker: coin.ticker().to_owned(),
+            history_sync: coin.history_sync_status().name(),
+            electrum_connections,
+        });
+    }
+    Ok(metrics)
+}
+
 pub fn disable_coin(ctx: &MmArc, ticker: &str) -> Result<(), String> {
     let coins_ctx = try_s!(CoinsContext::from_ctx(&ctx));
     let mut coins = block_on(coins_ctx.coins.lock());
@@ -645,10 +705,10 @@ pub struct ConfirmationsReq {
 }
 
 pub async fn set_required_confirmations(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
-    let req: ConfirmationsReq = try_s!(json::from_value(req));
+    let req: ConfirmationsReq = try_tr!(InvalidRequest, json::from_value(req));
     let coin = match block_on(lp_coinfind(&ctx, &req.coin)) {
         Ok(Some(t)) => t,
-        Ok(None) => return ERR!("No such coin {}", req.coin),
+        Ok(None) => return RpcError::new(RpcErrorCode::CoinNotEnabled, ERRL!("No such coin {}", req.coin)).into_response(),
         Err(err) => return ERR!("!lp_coinfind ({}): {}", req.coin, err),
     };
     coin.set_required_confirmations(req.confirmations);
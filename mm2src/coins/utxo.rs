@@ -66,7 +66,7 @@ use std::time::Duration;
 pub use chain::Transaction as UtxoTx;
 
 use self::rpc_clients::{electrum_script_hash, ElectrumClient, ElectrumClientImpl, EstimateFeeMethod, NativeClient, UtxoRpcClientEnum, UnspentInfo };
-use super::{CoinsContext, FoundSwapTxSpend, HistorySyncState, MarketCoinOps, MmCoin, SwapOps, TradeInfo,
+use super::{publish_balance_change, CoinsContext, FoundSwapTxSpend, HistorySyncState, MarketCoinOps, MmCoin, SwapOps, TradeInfo,
             Transaction, TransactionEnum, TransactionFut, TransactionDetails, WithdrawFee, WithdrawRequest};
 use crate::utxo::rpc_clients::{NativeClientImpl, UtxoRpcClientOps, ElectrumRpcRequest};
 
@@ -152,6 +152,9 @@ enum FeePolicy {
     DeductFromOutput(usize),
 }
 
+/// The minimal output value in satoshis when the coin configuration has no "dust".
+const DEFAULT_DUST: u64 = 1000;
+
 #[derive(Debug)]
 pub struct UtxoCoinImpl {  // pImpl idiom.
     ticker: String,
@@ -211,6 +214,9 @@ pub struct UtxoCoinImpl {  // pImpl idiom.
     signature_version: SignatureVersion,
     history_sync_state: Mutex<HistorySyncState>,
     required_confirmations: AtomicU64,
+    /// The minimal output value in satoshis, the smaller change is added to the fee.
+    /// The "dust" of the coin configuration.
+    dust_amount: u64,
 }
 
 impl UtxoCoinImpl {
@@ -553,6 +559,14 @@ async fn send_outputs_from_my_address_impl(coin: UtxoCoin, outputs: Vec<Transact
 }
 
 impl UtxoCoin {
+    /// The Electrum servers of the coin and whether we're connected to them. Empty for the native coin daemon.
+    pub async fn electrum_connections_state(&self) -> Vec<(String, bool)> {
+        match self.rpc_client {
+            UtxoRpcClientEnum::Electrum(ref electrum) => electrum.connections_state().await,
+            UtxoRpcClientEnum::Native(_) => Vec::new(),
+        }
+    }
+
     fn send_outputs_from_my_address(&self, outputs: Vec<TransactionOutput>) -> TransactionFut {
         let fut = send_outputs_from_my_address_impl(self.clone(), outputs);
         Box::new(fut.boxed().compat().map(|tx| tx.into()))
@@ -619,7 +633,6 @@ impl UtxoCoin {
         fee_policy: FeePolicy,
         fee: Option<ActualTxFee>,
     ) -> Box<dyn Future<Item=(TransactionInputSigner, AdditionalTxData), Error=String> + Send> {
-        const DUST: u64 = 1000;
         let lock_time = (now_ms() / 1000) as u32;
         let change_script_pubkey = Builder::build_p2pkh(&self.my_address.hash).to_bytes();
         let arc = self.clone();
@@ -631,10 +644,11 @@ impl UtxoCoin {
             true_or_err!(!utxos.is_empty(), "Couldn't generate tx from empty utxos set");
             true_or_err!(!outputs.is_empty(), "Couldn't generate tx from empty outputs set");
 
+            let dust = arc.dust_amount;
             let mut sum_outputs_value = 0;
             let mut received_by_me = 0;
             for output in outputs.iter() {
-                true_or_err!(output.value >= DUST, "Output value {} is less than dust amount {}", output.value, DUST);
+                true_or_err!(output.value >= dust, "Output value {} is less than dust amount {}", output.value, dust);
                 sum_outputs_value += output.value;
                 if output.script_pubkey == change_script_pubkey {
                     received_by_me += output.value;
@@ -680,7 +694,7 @@ impl UtxoCoin {
                     FeePolicy::SendExact => {
                         let mut target_value = sum_outputs_value + tx_fee;
                         if value_to_spend >= target_value {
-                            if value_to_spend - target_value > DUST {
+                            if value_to_spend - target_value > dust {
                                 if let ActualTxFee::Dynamic(ref f) = coin_tx_fee {
                                     tx_fee += (f * 34) / 1024;
                                     target_value += (f * 34) / 1024;
@@ -694,7 +708,7 @@ impl UtxoCoin {
                     },
                     FeePolicy::DeductFromOutput(_) => {
                         if value_to_spend >= sum_outputs_value {
-                            if value_to_spend - sum_outputs_value > DUST {
+                            if value_to_spend - sum_outputs_value > dust {
                                 if let ActualTxFee::Dynamic(ref f) = coin_tx_fee {
                                     tx_fee += (f * 34) / 1024;
                                 }
@@ -707,7 +721,7 @@ impl UtxoCoin {
             match fee_policy {
                 FeePolicy::SendExact => sum_outputs_value += tx_fee,
                 FeePolicy::DeductFromOutput(i) => {
-                    let min_output = tx_fee + DUST;
+                    let min_output = tx_fee + dust;
                     let val = tx.outputs[i].value;
                     true_or_err!(val >= min_output, "Output {} value {} is too small, required no less than {}", i, val, min_output);
                     tx.outputs[i].value -= tx_fee;
@@ -719,7 +733,7 @@ impl UtxoCoin {
             true_or_err!(value_to_spend >= sum_outputs_value, "Not sufficient balance. Couldn't collect enough value from utxos {:?} to create tx with outputs {:?}", utxos, tx.outputs);
 
             let change = value_to_spend - sum_outputs_value;
-            if change >= DUST {
+            if change >= dust {
                 tx.outputs.push({
                     TransactionOutput {
                         value: change,
@@ -1415,6 +1429,7 @@ impl MmCoin for UtxoCoin {
         });
         let history = self.load_history_from_file(&ctx);
         let mut history_map: HashMap<H256Json, TransactionDetails> = history.into_iter().map(|tx| (H256Json::from(tx.tx_hash.as_slice()), tx)).collect();
+        let mut last_balance = None;
         loop {
             if ctx.is_stopping() { break };
             {
@@ -1426,6 +1441,10 @@ impl MmCoin for UtxoCoin {
                 };
             }
 
+            if let Ok(balance) = self.my_balance().wait() {
+                publish_balance_change(&ctx, &self.ticker, balance, &mut last_balance);
+            }
+
             let tx_ids: Vec<(H256Json, u64)> = match &self.rpc_client {
                 UtxoRpcClientEnum::Native(client) => {
                     let mut from = 0;
@@ -1936,6 +1955,7 @@ pub async fn utxo_coin_from_conf_and_request(
         fork_id,
         history_sync_state: Mutex::new(initial_history_state),
         required_confirmations: conf["required_confirmations"].as_u64().unwrap_or(1).into(),
+        dust_amount: conf["dust"].as_u64().unwrap_or(DEFAULT_DUST),
     };
     Ok(UtxoCoin(Arc::new(coin)))
 }
@@ -829,6 +829,15 @@ impl ElectrumClientImpl {
         }
         false
     }
+
+    /// The server addresses and whether we're connected to them.
+    pub async fn connections_state(&self) -> Vec<(String, bool)> {
+        let mut state = Vec::with_capacity(self.connections.len());
+        for connection in self.connections.iter() {
+            state.push((connection.addr.to_string(), connection.is_connected().await));
+        }
+        state
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -76,6 +76,7 @@ fn utxo_coin_for_test(rpc_client: UtxoRpcClientEnum, force_seed: Option<&str>) -
         signature_version: SignatureVersion::Base,
         history_sync_state: Mutex::new(HistorySyncState::NotEnabled),
         required_confirmations: 1.into(),
+        dust_amount: DEFAULT_DUST,
     };
 
     UtxoCoin(Arc::new(coin))
@@ -64,8 +64,11 @@ pub mod log;
 #[cfg(feature = "native")]
 pub mod for_c;
 pub mod custom_futures;
+pub mod event_stream;
 pub mod iguana_utils;
+pub mod metrics;
 pub mod privkey;
+pub mod rpc_error;
 pub mod mm_ctx;
 pub mod mm_number;
 pub mod seri;
@@ -857,6 +860,31 @@ macro_rules! try_h {
     }
 }
 
+/// Returns a typed JSON error HyRes on a failure, cf. `rpc_error`.
+///
+///     let coin = try_th! (CoinNotEnabled, coin.ok_or ("Coin is not enabled"));
+#[macro_export]
+macro_rules! try_th {
+    ($code: ident, $e: expr) => {
+        match $e {
+            Ok (ok) => ok,
+            Err (err) => {return $crate::rpc_error::rpc_typed_err_response (
+                $crate::rpc_error::RpcError::new ($crate::rpc_error::RpcErrorCode::$code, ERRL! ("{}", err)))}
+        }
+    }
+}
+
+/// Returns a typed JSON error response from an `async fn` RPC handler on a failure, cf. `rpc_error`.
+#[macro_export]
+macro_rules! try_tr {
+    ($code: ident, $e: expr) => {
+        match $e {
+            Ok (ok) => ok,
+            Err (err) => {return $crate::rpc_error::RpcError::new ($crate::rpc_error::RpcErrorCode::$code, ERRL! ("{}", err)) .into_response()}
+        }
+    }
+}
+
 /// Executes a GET request, returning the response status, headers and body.
 pub fn slurp_url (url: &str) -> SlurpFut {
     wio::slurp_req (try_fus! (Request::builder().uri (url) .body (Vec::new())))
@@ -913,17 +941,10 @@ pub fn rpc_response<T> (status: u16, body: T) -> HyRes where Vec<u8>: From<T> {
     Box::new (rf)
 }
 
-/// Converts the given `err` message into the `{error: $err}` JSON string.
+/// Converts the given `err` message into the `{error: $err}` JSON string.  
+/// The error is reported with the `InternalError` code, cf. `rpc_error` for the typed errors.
 pub fn err_to_rpc_json_string(err: &str) -> String {
-    #[derive(Serialize)]
-    struct ErrResponse {
-        error: String,
-    }
-
-    let err = ErrResponse {
-        error: err.to_owned(),
-    };
-    json::to_string(&err).unwrap()
+    rpc_error::RpcError::internal(err.to_owned()).to_json().to_string()
 }
 
 /// Returns the `{error: $msg}` JSON response with the given HTTP `status`.  
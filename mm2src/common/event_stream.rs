@@ -0,0 +1,143 @@
+//! Push notifications about the swaps, orders and balances.
+//!
+//! The producers (swap loops, ordermatch, coin history loops) publish the `Event`s into the `MmCtx::event_stream`
+//! and the consumers (the WebSocket sessions in particular) receive the events matching their subscriptions.
+
+use futures01::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde_json::{Value as Json};
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::now_ms;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTopic {
+    /// Swap events, identified by the swap uuid.
+    Swap,
+    /// Maker and taker order events, identified by the order uuid.
+    Order,
+    /// Coin balance changes, identified by the coin ticker.
+    Coin,
+}
+
+impl fmt::Display for EventTopic {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventTopic::Swap => write! (f, "swap"),
+            EventTopic::Order => write! (f, "order"),
+            EventTopic::Coin => write! (f, "coin"),
+}   }   }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    pub topic: EventTopic,
+    /// Swap uuid, order uuid or coin ticker, depending on the `topic`.
+    pub id: String,
+    /// The kind of the event, like "MakerPaymentSent", "Created", "Cancelled" or "BalanceChanged".
+    pub event: String,
+    pub data: Json,
+    pub timestamp: u64,
+}
+
+/// A subscription filter, parsed from strings like "swap:<uuid>", "coin:KMD", "order:*" or "*".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subscription {
+    /// `None` matches any topic.
+    pub topic: Option<EventTopic>,
+    /// `None` matches any identifier.
+    pub id: Option<String>,
+}
+
+impl Subscription {
+    pub fn parse (s: &str) -> Result<Subscription, String> {
+        if s == "*" {return Ok (Subscription {topic: None, id: None})}
+        let mut it = s.splitn (2, ':');
+        let topic = match it.next() {
+            Some ("swap") => EventTopic::Swap,
+            Some ("order") => EventTopic::Order,
+            Some ("coin") => EventTopic::Coin,
+            _ => return ERR! ("Unknown topic in subscription {}", s)
+        };
+        let id = match it.next() {
+            None | Some ("") | Some ("*") => None,
+            Some (id) => Some (id.into())
+        };
+        Ok (Subscription {topic: Some (topic), id})
+    }
+
+    pub fn matches (&self, event: &Event) -> bool {
+        if let Some (topic) = self.topic {if topic != event.topic {return false}}
+        match self.id {
+            // Coin tickers are case insensitive in the RPC.
+            Some (ref id) => id.eq_ignore_ascii_case (&event.id),
+            None => true
+}   }   }
+
+/// Fans the published events out to the subscribers.
+#[derive(Default)]
+pub struct EventStream {
+    subscribers: Mutex<Vec<UnboundedSender<Event>>>,
+}
+
+impl EventStream {
+    /// Returns a stream of all the events published after the call.
+    /// The subscription is dropped when the receiver is dropped.
+    pub fn subscribe (&self) -> UnboundedReceiver<Event> {
+        let (tx, rx) = unbounded();
+        unwrap! (self.subscribers.lock()) .push (tx);
+        rx
+    }
+
+    pub fn publish (&self, topic: EventTopic, id: &str, event: &str, data: Json) {
+        let mut subscribers = unwrap! (self.subscribers.lock());
+        if subscribers.is_empty() {return}
+        let event = Event {
+            topic,
+            id: id.into(),
+            event: event.into(),
+            data,
+            timestamp: now_ms(),
+        };
+        subscribers.retain (|tx| tx.unbounded_send (event.clone()) .is_ok())
+    }
+
+    pub fn subscribers_count (&self) -> usize {
+        unwrap! (self.subscribers.lock()) .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures01::{Future, Stream};
+    use super::*;
+
+    #[test]
+    fn test_subscription_parse_and_match() {
+        let event = Event {
+            topic: EventTopic::Coin,
+            id: "KMD".into(),
+            event: "BalanceChanged".into(),
+            data: json! ({}),
+            timestamp: 0,
+        };
+        assert! (unwrap! (Subscription::parse ("*")) .matches (&event));
+        assert! (unwrap! (Subscription::parse ("coin")) .matches (&event));
+        assert! (unwrap! (Subscription::parse ("coin:kmd")) .matches (&event));
+        assert! (!unwrap! (Subscription::parse ("coin:BTC")) .matches (&event));
+        assert! (!unwrap! (Subscription::parse ("swap:*")) .matches (&event));
+        assert! (Subscription::parse ("foo:bar") .is_err());
+    }
+
+    #[test]
+    fn test_publish_drops_closed_subscribers() {
+        let stream = EventStream::default();
+        let rx = stream.subscribe();
+        let dropped = stream.subscribe();
+        drop (dropped);
+        stream.publish (EventTopic::Order, "uuid", "Created", json! ({}));
+        assert_eq! (stream.subscribers_count(), 1);
+        let (event, _) = unwrap! (rx.into_future().wait().map_err (|_| "!recv"));
+        assert_eq! (unwrap! (event) .event, "Created");
+    }
+}
@@ -0,0 +1,123 @@
+//! Counters and the Prometheus text exposition format.
+//!
+//! The counters are incremented in place (RPC calls, P2P messages, finished swaps) and kept in the `MmCtx::metrics`,
+//! whereas the gauges (active swaps, orders, Electrum connections) are sampled from the respective contexts
+//! when the "/metrics" endpoint is scraped, cf. `rpc/prometheus.rs`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Label names and values, like `[("method", "my_balance")]`.
+pub type Labels = Vec<(&'static str, String)>;
+
+/// A metric sample, like `mm2_rpc_requests_total{method="my_balance"} 3`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Labels,
+    pub value: f64,
+}
+
+impl Sample {
+    pub fn new (name: &str, labels: Labels, value: f64) -> Sample {
+        Sample {name: name.into(), labels, value}
+    }
+}
+
+fn to_labels (labels: &[(&'static str, &str)]) -> Labels {
+    labels.iter().map (|&(k, v)| (k, v.to_owned())) .collect()
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Sample name and labels to the counter value.
+    counters: Mutex<BTreeMap<(String, Labels), f64>>,
+}
+
+impl Metrics {
+    pub fn inc_counter (&self, name: &str, labels: &[(&'static str, &str)]) {
+        self.add_counter (name, labels, 1.)
+    }
+
+    pub fn add_counter (&self, name: &str, labels: &[(&'static str, &str)], value: f64) {
+        if let Ok (mut counters) = self.counters.lock() {
+            *counters.entry ((name.to_owned(), to_labels (labels))) .or_insert (0.) += value
+        }
+    }
+
+    /// Records a duration (or any other value) into the summary made of the `{name}_sum` and `{name}_count` counters.
+    pub fn observe (&self, name: &str, labels: &[(&'static str, &str)], value: f64) {
+        if let Ok (mut counters) = self.counters.lock() {
+            *counters.entry ((fomat! ((name) "_sum"), to_labels (labels))) .or_insert (0.) += value;
+            *counters.entry ((fomat! ((name) "_count"), to_labels (labels))) .or_insert (0.) += 1.
+        }
+    }
+
+    pub fn counter_value (&self, name: &str, labels: &[(&'static str, &str)]) -> f64 {
+        match self.counters.lock() {
+            Ok (counters) => counters.get (&(name.to_owned(), to_labels (labels))) .cloned().unwrap_or (0.),
+            Err (_) => 0.
+        }
+    }
+
+    /// A snapshot of the counters belonging to the metric family `name` (including the "_sum" and "_count" samples).
+    pub fn samples (&self, name: &str) -> Vec<Sample> {
+        let (sum, count) = (fomat! ((name) "_sum"), fomat! ((name) "_count"));
+        let counters = match self.counters.lock() {Ok (c) => c, Err (_) => return Vec::new()};
+        counters.iter()
+            .filter (|((sample, _), _)| sample == name || *sample == sum || *sample == count)
+            .map (|((sample, labels), value)| Sample {name: sample.clone(), labels: labels.clone(), value: *value})
+            .collect()
+    }
+}
+
+fn escape_label_value (value: &str) -> String {
+    value.replace ('\\', "\\\\") .replace ('"', "\\\"") .replace ('\n', "\\n")
+}
+
+/// Renders a metric family in the Prometheus text format (version 0.0.4).
+///
+/// * `kind` - "counter", "gauge" or "summary".
+pub fn write_family (out: &mut String, name: &str, kind: &str, help: &str, samples: &[Sample]) {
+    let _ = writeln! (out, "# HELP {} {}", name, help);
+    let _ = writeln! (out, "# TYPE {} {}", name, kind);
+    for sample in samples {
+        out.push_str (&sample.name);
+        if !sample.labels.is_empty() {
+            out.push ('{');
+            for (idx, (key, value)) in sample.labels.iter().enumerate() {
+                if idx != 0 {out.push (',')}
+                let _ = write! (out, "{}=\"{}\"", key, escape_label_value (value));
+            }
+            out.push ('}');
+        }
+        let _ = writeln! (out, " {}", sample.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_samples() {
+        let metrics = Metrics::default();
+        metrics.inc_counter ("mm2_p2p_messages_sent_total", &[]);
+        metrics.inc_counter ("mm2_p2p_messages_sent_total", &[]);
+        metrics.observe ("mm2_rpc_request_duration_seconds", &[("method", "my_balance")], 0.5);
+        metrics.observe ("mm2_rpc_request_duration_seconds", &[("method", "my_balance")], 0.25);
+        assert_eq! (metrics.counter_value ("mm2_p2p_messages_sent_total", &[]), 2.);
+
+        assert_eq! (metrics.samples ("mm2_p2p_messages_sent_total"), vec! [Sample::new ("mm2_p2p_messages_sent_total", vec![], 2.)]);
+        assert_eq! (metrics.samples ("mm2_rpc_request_duration_seconds"), vec! [
+            Sample::new ("mm2_rpc_request_duration_seconds_count", vec![("method", "my_balance".into())], 2.),
+            Sample::new ("mm2_rpc_request_duration_seconds_sum", vec![("method", "my_balance".into())], 0.75)
+        ]);
+
+        let mut out = String::new();
+        write_family (&mut out, "mm2_orders", "gauge", "The number of my orders.",
+            &[Sample::new ("mm2_orders", vec![("type", "maker".into()), ("pair", "KMD/\"BTC\"".into())], 3.)]);
+        assert_eq! (out, "# HELP mm2_orders The number of my orders.\n# TYPE mm2_orders gauge\nmm2_orders{type=\"maker\",pair=\"KMD/\\\"BTC\\\"\"} 3\n");
+    }
+}
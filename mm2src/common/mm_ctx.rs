@@ -22,6 +22,8 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, Weak};
 
 use crate::{bits256, small_rng};
+use crate::event_stream::EventStream;
+use crate::metrics::Metrics;
 use crate::log::{self, LogState};
 
 /// MarketMaker state, shared between the various MarketMaker threads.
@@ -92,6 +94,12 @@ pub struct MmCtx {
     pub coins_needed_for_kick_start: Mutex<HashSet<String>>,
     /// The context belonging to the `lp_swap` mod: `SwapsContext`.
     pub swaps_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
+    /// The context belonging to the `rpc` mod: `RpcContext`.
+    pub rpc_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
+    /// Swap, order and balance events pushed to the WebSocket subscribers.
+    pub event_stream: EventStream,
+    /// The counters exported by the "/metrics" endpoint.
+    pub metrics: Metrics,
 }
 impl MmCtx {
     pub fn with_log_state (log: LogState) -> MmCtx {
@@ -116,6 +124,9 @@ impl MmCtx {
             secp256k1_key_pair: Constructible::default(),
             coins_needed_for_kick_start: Mutex::new (HashSet::new()),
             swaps_ctx: Mutex::new (None),
+            rpc_ctx: Mutex::new (None),
+            event_stream: EventStream::default(),
+            metrics: Metrics::default(),
         }
     }
 
@@ -197,6 +208,7 @@ impl MmCtx {
 
     /// Sends the P2P message to a processing thread
     pub fn broadcast_p2p_msg(&self, msg: &str) {
+        self.metrics.inc_counter("mm2_p2p_messages_sent_total", &[("transport", "broadcast")]);
         let i_am_seed = self.conf["i_am_seed"].as_bool().unwrap_or(false);
         if i_am_seed {
             unwrap!(self.seednode_p2p_channel.0.send(msg.to_owned().into_bytes()));
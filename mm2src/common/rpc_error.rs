@@ -0,0 +1,169 @@
+//! Structured RPC errors.
+//!
+//! The error response keeps the human-readable "error" string for the backward compatibility
+//! and adds the machine-readable fields the clients can rely upon:
+//!
+//!     {
+//!       "error": "lp_ordermatch:694] The KMD amount 10 is larger than available 1.5, ...",
+//!       "error_code": "NotSufficientBalance",
+//!       "error_category": "balance",
+//!       "error_details": {"coin": "KMD", "required": "10", "available": "1.5", "locked_by_swaps": "0"}
+//!     }
+//!
+//! The errors which weren't classified yet are reported with the `InternalError` code.
+
+use futures01::future;
+use http::Response;
+use http::header::{HeaderValue, CONTENT_TYPE};
+use serde_json::{self as json, Value as Json};
+use std::fmt;
+
+use crate::HyRes;
+
+/// A group of error codes which can be handled the same way by a client.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpcErrorCategory {
+    /// The request is malformed or can't be satisfied as given. Fix the request before retrying.
+    Request,
+    /// The client is not allowed to invoke the method.
+    Auth,
+    /// Problems with the coin configuration or activation.
+    Coin,
+    /// Not enough funds to perform the operation.
+    Balance,
+    Order,
+    Swap,
+    /// A remote node (Electrum, ETH node, coin daemon) is unavailable or failed. Might make sense to retry later.
+    Transport,
+    Internal,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum RpcErrorCode {
+    /// A required field is missing or has a wrong value.
+    InvalidRequest,
+    NoSuchMethod,
+    Unauthorized,
+    CoinNotEnabled,
+    CoinAlreadyEnabled,
+    CoinConfigError,
+    /// The coin can't be disabled as it is used by the active swaps or orders.
+    CoinIsInUse,
+    NotSufficientBalance,
+    OrderNotFound,
+    /// The order is being matched now and can't be modified.
+    OrderIsMatching,
+    SwapNotFound,
+    SwapIsNotRecoverable,
+    TransportError,
+    InternalError,
+}
+
+impl RpcErrorCode {
+    pub fn category (&self) -> RpcErrorCategory {
+        match self {
+            RpcErrorCode::InvalidRequest | RpcErrorCode::NoSuchMethod => RpcErrorCategory::Request,
+            RpcErrorCode::Unauthorized => RpcErrorCategory::Auth,
+            RpcErrorCode::CoinNotEnabled | RpcErrorCode::CoinAlreadyEnabled | RpcErrorCode::CoinConfigError |
+            RpcErrorCode::CoinIsInUse => RpcErrorCategory::Coin,
+            RpcErrorCode::NotSufficientBalance => RpcErrorCategory::Balance,
+            RpcErrorCode::OrderNotFound | RpcErrorCode::OrderIsMatching => RpcErrorCategory::Order,
+            RpcErrorCode::SwapNotFound | RpcErrorCode::SwapIsNotRecoverable => RpcErrorCategory::Swap,
+            RpcErrorCode::TransportError => RpcErrorCategory::Transport,
+            RpcErrorCode::InternalError => RpcErrorCategory::Internal,
+        }
+    }
+
+    /// NB: Most of the errors are reported with the HTTP 500 status, as they always were,
+    /// the clients should use the `error_code` in order to tell them apart.
+    pub fn http_status (&self) -> u16 {
+        match self {
+            RpcErrorCode::OrderNotFound | RpcErrorCode::SwapNotFound => 404,
+            _ => 500
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RpcError {
+    pub code: RpcErrorCode,
+    pub message: String,
+    pub details: Option<Json>,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write! (f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl RpcError {
+    pub fn new (code: RpcErrorCode, message: String) -> RpcError {
+        RpcError {code, message, details: None}
+    }
+
+    pub fn with_details (mut self, details: Json) -> RpcError {
+        self.details = Some (details);
+        self
+    }
+
+    /// Wraps an error which wasn't classified yet.
+    pub fn internal (message: String) -> RpcError {
+        RpcError::new (RpcErrorCode::InternalError, message)
+    }
+
+    pub fn to_json (&self) -> Json {
+        let mut err = json! ({
+            "error": self.message,
+            "error_code": self.code,
+            "error_category": self.code.category(),
+        });
+        if let Some (ref details) = self.details {err["error_details"] = details.clone()}
+        err
+    }
+
+    /// The response to return from the `async fn` RPC handlers.
+    pub fn into_response (self) -> Result<Response<Vec<u8>>, String> {
+        log! ("RPC error response: " (self));
+        let body = try_s! (json::to_vec (&self.to_json()));
+        Ok (try_s! (Response::builder()
+            .status (self.code.http_status())
+            .header (CONTENT_TYPE, HeaderValue::from_static ("application/json"))
+            .body (body)))
+    }
+}
+
+impl From<String> for RpcError {
+    fn from (message: String) -> RpcError {RpcError::internal (message)}
+}
+
+/// The `HyRes` version of the `RpcError::into_response`.
+pub fn rpc_typed_err_response (err: RpcError) -> HyRes {
+    Box::new (future::result (err.into_response()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_to_json() {
+        let err = RpcError::new (RpcErrorCode::NotSufficientBalance, "Not enough KMD".into())
+            .with_details (json! ({"coin": "KMD"}));
+        assert_eq! (err.to_json(), json! ({
+            "error": "Not enough KMD",
+            "error_code": "NotSufficientBalance",
+            "error_category": "balance",
+            "error_details": {"coin": "KMD"},
+        }));
+
+        let err = RpcError::from (String::from ("Something went wrong"));
+        assert_eq! (err.to_json(), json! ({
+            "error": "Something went wrong",
+            "error_code": "InternalError",
+            "error_category": "internal",
+        }));
+        assert_eq! (RpcErrorCode::OrderNotFound.http_status(), 404);
+    }
+}
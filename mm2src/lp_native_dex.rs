@@ -47,8 +47,8 @@ use crate::common::{slurp_url, MM_VERSION};
 use crate::common::mm_ctx::{MmCtx, MmArc};
 use crate::common::privkey::key_pair_from_seed;
 use crate::mm2::lp_network::{lp_command_q_loop, seednode_loop, start_client_p2p_loop};
-use crate::mm2::lp_ordermatch::{lp_ordermatch_loop, lp_trade_command, migrate_saved_orders, orders_kick_start};
-use crate::mm2::lp_swap::swap_kick_starts;
+use crate::mm2::lp_ordermatch::{init_ordermatch_context, lp_ordermatch_loop, lp_trade_command, migrate_saved_orders, orders_kick_start};
+use crate::mm2::lp_swap::{swap_kick_starts, swap_recovery_loop};
 use crate::mm2::rpc::{spawn_rpc};
 
 /// Process a previously queued command that wasn't handled by the RPC `dispatcher`.  
@@ -1038,6 +1038,8 @@ fn fix_directories(ctx: &MmCtx) -> Result<(), String> {
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY")) {return ERR!("ORDERS/MY db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("MAKER")) {return ERR!("ORDERS/MY/MAKER db dir is not writable")}
     if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("TAKER")) {return ERR!("ORDERS/MY/TAKER db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("MY").join ("CONDITIONAL")) {return ERR!("ORDERS/MY/CONDITIONAL db dir is not writable")}
+    if !ensure_dir_is_writable(&dbdir.join ("ORDERS").join ("HISTORY")) {return ERR!("ORDERS/HISTORY db dir is not writable")}
     try_s!(ensure_file_is_writable(&dbdir.join ("GTC").join ("orders")));
     Ok(())
 }
@@ -1058,6 +1060,8 @@ fn fix_directories(ctx: &MmCtx) -> Result<(), String> {
     writeable_dir! (dbdir.join ("SWAPS"));
     writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("MAKER"));
     writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("TAKER"));
+    writeable_dir! (dbdir.join ("ORDERS") .join ("MY") .join ("CONDITIONAL"));
+    writeable_dir! (dbdir.join ("ORDERS") .join ("HISTORY"));
     Ok(())
 }
 
@@ -1198,6 +1202,7 @@ pub async fn lp_init (mypubport: u16, ctx: MmArc) -> Result<(), String> {
 
     try_s! (fix_directories (&ctx));
     #[cfg(feature = "native")] {try_s! (migrate_db (&ctx));}
+    try_s! (init_ordermatch_context (&ctx));
 
     fn simple_ip_extractor (ip: &str) -> Result<IpAddr, String> {
         let ip = ip.trim();
@@ -1340,6 +1345,13 @@ pub async fn lp_init (mypubport: u16, ctx: MmArc) -> Result<(), String> {
         move || lp_ordermatch_loop (ctx)
     }));
 
+    if ctx.conf["auto_recover_swaps"].as_bool().unwrap_or (true) {
+        try_s! (thread::Builder::new().name ("swap_recovery".into()) .spawn ({
+            let ctx = ctx.clone();
+            move || swap_recovery_loop (ctx)
+        }));
+    }
+
     let command_queue = try_s! (thread::Builder::new().name ("command_queue".into()) .spawn ({
         let ctx = ctx.clone();
         move || unsafe { lp_command_q_loop (ctx) }
@@ -42,7 +42,7 @@ use std::thread;
 use std::time::Duration;
 
 use crate::mm2::lp_native_dex::lp_command_process;
-use crate::mm2::lp_ordermatch::lp_post_price_recv;
+use crate::mm2::lp_ordermatch::{lp_post_price_recv, lp_private_price_notify_recv};
 use crate::mm2::lp_swap::save_stats_swap_status;
 use crate::mm2::rpc::lp_signatures::lp_notify_recv;
 
@@ -70,6 +70,7 @@ fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
     DispatcherRes::Match (match &method[..] {  // Sorted alphanumerically (on the first latter) for readability.
         "notify" => lp_notify_recv (ctx, req),  // Invoked usually from the `lp_command_q_loop`
         "postprice" => lp_post_price_recv (&ctx, req),
+        "private_price_ping_notify" => lp_private_price_notify_recv (&ctx, req),
         _ => return DispatcherRes::NoMatch (req)
     })
 }
@@ -128,6 +129,7 @@ pub unsafe fn lp_command_q_loop(ctx: MmArc) {
             Err(channel::RecvTimeoutError::Timeout) => continue,  // And check `is_stopping`.
             Err(channel::RecvTimeoutError::Disconnected) => break
         };
+        ctx.metrics.inc_counter("mm2_p2p_messages_received_total", &[("transport", "broadcast")]);
 
         let now = now_ms();
         // clean up messages older than 60 seconds
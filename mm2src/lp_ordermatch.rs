@@ -22,27 +22,32 @@
 #![cfg_attr(not(feature = "native"), allow(dead_code))]
 
 use bigdecimal::BigDecimal;
-use bitcrypto::sha256;
+use bitcrypto::{dhash160, sha256};
 use coins::{lp_coinfind, MmCoinEnum, TradeInfo};
 use coins::utxo::{compressed_pub_key_from_priv_raw, ChecksumType};
 use common::{bits256, json_dir_entries, now_ms, new_uuid, rpc_response, rpc_err_response, write, HyRes};
-use common::executor::spawn;
+use common::event_stream::EventTopic;
+use common::rpc_error::{rpc_typed_err_response, RpcError, RpcErrorCode};
+use common::executor::{spawn, Timer};
 use common::mm_ctx::{from_ctx, MmArc, MmWeak};
 use common::mm_number::{from_dec_to_ratio, from_ratio_to_dec, MmNumber};
-use futures01::future::{Either, Future};
+use futures01::Future;
 use futures::compat::Future01CompatExt;
 use futures::executor::block_on;
+use futures::future::{join_all, select, Either};
 use gstuff::slurp;
 use http::Response;
-use keys::{Public, Signature};
+use keys::{KeyPair, Public, Signature};
 #[cfg(test)]
 use mocktopus::macros::*;
 use num_rational::BigRational;
+use peers::FixedValidator;
 use num_traits::cast::ToPrimitive;
 use num_traits::identities::Zero;
 use primitives::hash::H256;
 use rpc::v1::types::{H256 as H256Json};
 use serde_json::{self as json, Value as Json};
+use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::collections::hash_map::{Entry, HashMap};
 use std::fs::{self, DirEntry};
@@ -58,6 +63,32 @@ use crate::mm2::lp_swap::{dex_fee_amount, get_locked_amount, MakerSwap, run_make
 #[path = "ordermatch_tests.rs"]
 mod ordermatch_tests;
 
+#[path = "lp_ordermatch/best_orders.rs"]
+mod best_orders;
+
+#[path = "lp_ordermatch/conditional_orders.rs"]
+mod conditional_orders;
+
+#[path = "lp_ordermatch/external_prices.rs"]
+mod external_prices;
+
+#[path = "lp_ordermatch/orderbook_limits.rs"]
+mod orderbook_limits;
+
+#[path = "lp_ordermatch/orders_history.rs"]
+mod orders_history;
+
+use self::orderbook_limits::{check_min_order_volume, check_new_order_limits, check_price_bounds, load_banned_pubkeys, min_order_volume,
+                             normalize_pubkey, OrderbookLimits};
+pub use self::best_orders::best_orders;
+use self::conditional_orders::{external_prices_loop, load_my_conditional_orders, process_conditional_orders, ConditionalOrder};
+use self::external_prices::ExternalPrices;
+pub use self::conditional_orders::{cancel_conditional_order, conditional_order, my_conditional_orders};
+pub use self::orderbook_limits::{ban_pubkey, orderbook_limits, unban_pubkey};
+
+use self::orders_history::{save_maker_order_history, save_taker_order_history};
+pub use self::orders_history::orders_history;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 enum TakerAction {
     Buy,
@@ -77,6 +108,10 @@ struct TakerRequest {
     method: String,
     sender_pubkey: H256Json,
     dest_pub_key: H256Json,
+    /// The taker accepts the "reserved" replies for a part of the base amount, the rest being filled by other makers.
+    /// Missing in the requests of the nodes not supporting the partial fills.
+    #[serde(default)]
+    partial_fill: bool,
 }
 
 impl TakerRequest {
@@ -95,11 +130,86 @@ impl TakerRequest {
     }
 }
 
+/// How long my order stays active.
+///
+/// Kept with my order only (persisted with the order file), the P2P messages aren't affected.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum TimeInForce {
+    /// The order is active until cancelled.
+    /// The taker order which isn't matched within the `ordermatch_timeout` is converted to a maker order.
+    GoodTillCancelled,
+    /// The order is cancelled when the `expires_at` UNIX timestamp (in seconds) passes.
+    /// The taker order which isn't matched within the `ordermatch_timeout` is converted to a maker order
+    /// carrying the same expiry.
+    GoodTillTime { expires_at: u64 },
+    /// Taker only. The order is cancelled (never converted to a maker order) if not matched within the `ordermatch_timeout`.
+    ImmediateOrCancel,
+    /// Taker only. Same as the `ImmediateOrCancel` but the order is never partially filled,
+    /// only matched with a single maker order for the full volume.
+    FillOrKill,
+}
+
+impl Default for TimeInForce {
+    fn default() -> TimeInForce { TimeInForce::GoodTillCancelled }
+}
+
+impl TimeInForce {
+    /// True if the `GoodTillTime` order has expired by `now` (UNIX timestamp in seconds).
+    fn is_expired(&self, now: u64) -> bool {
+        match self {
+            TimeInForce::GoodTillTime { expires_at } => *expires_at <= now,
+            _ => false,
+        }
+    }
+
+    /// True if the unmatched taker order should be cancelled rather than converted to a maker order.
+    fn is_immediate(&self) -> bool {
+        match self {
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill => true,
+            _ => false,
+        }
+    }
+
+    fn check(&self, is_taker: bool) -> Result<(), RpcError> {
+        if self.is_expired(now_ms() / 1000) {
+            return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The expires_at timestamp is in the past")));
+        }
+        if !is_taker && self.is_immediate() {
+            return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("{:?} is only available to the taker orders", self)));
+        }
+        Ok(())
+    }
+}
+
+/// Why my order was removed before being filled.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+enum OrderCancellationReason {
+    /// Cancelled with the "cancel_order" or "cancel_all_orders" RPC.
+    Cancelled,
+    /// Replaced by a new "setprice" order of the same pair.
+    Replaced,
+    /// The `GoodTillTime` order has expired.
+    Expired,
+    /// The `ImmediateOrCancel` or `FillOrKill` taker order wasn't matched in time.
+    NotMatched,
+    /// The maker order volume available for trading dropped below the minimal amount.
+    InsufficientVolume,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct TakerOrder {
     created_at: u64,
     request: TakerRequest,
-    matches: HashMap<Uuid, TakerMatch>
+    matches: HashMap<Uuid, TakerMatch>,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    /// The uuids of the swaps started by the order, one per matched maker order.
+    #[serde(default)]
+    started_swaps: Vec<Uuid>,
+    /// The reasons the makers have rejected the request for, by the maker order uuid.
+    #[serde(default)]
+    rejections: HashMap<Uuid, String>,
 }
 
 /// Result of match_reserved function
@@ -112,25 +222,76 @@ enum MatchReservedResult {
 }
 
 impl TakerOrder {
+    /// The order can't be cancelled while waiting for the maker to confirm a match,
+    /// cancelling a partially filled order only cancels the unfilled amount.
     fn is_cancellable(&self) -> bool {
-        self.matches.is_empty()
+        self.matches.values().all(|m| m.connected.is_some())
+    }
+
+    /// The amount of the `request.base` coin the order is matched for by the `reserved` reply.
+    fn reserved_base_amount(&self, reserved: &MakerReserved) -> MmNumber {
+        match self.request.action {
+            TakerAction::Buy => reserved.get_base_amount(),
+            TakerAction::Sell => reserved.get_rel_amount(),
+        }
+    }
+
+    /// The match for the whole amount of the order (the only match the order has then),
+    /// the swap of such match has the `taker_order_uuid`.
+    fn full_match(&self) -> Option<&TakerMatch> {
+        self.matches.values().find(|m| m.connect.swap_uuid.is_none())
+    }
+
+    /// The amount of the `request.base` coin the swaps were started for.
+    fn filled_base_amount(&self) -> MmNumber {
+        if let Some(full_match) = self.full_match() {
+            return if full_match.connected.is_some() {self.request.get_base_amount()} else {MmNumber::from(0)}
+        }
+        self.matches.values().filter(|m| m.connected.is_some()).fold(
+            MmNumber::from(0),
+            |filled, m| filled + self.reserved_base_amount(&m.reserved)
+        )
+    }
+
+    /// The amount of the `request.base` coin which isn't matched yet.
+    fn unmatched_base_amount(&self) -> MmNumber {
+        if self.full_match().is_some() {return MmNumber::from(0)}
+        self.matches.values().fold(
+            self.request.get_base_amount(),
+            |unmatched, m| unmatched - self.reserved_base_amount(&m.reserved)
+        )
+    }
+
+    /// True when the swaps were started for the whole `request.base_amount`.
+    fn is_filled(&self) -> bool {
+        self.filled_base_amount() >= self.request.get_base_amount()
     }
 
     fn match_reserved(&self, reserved: &MakerReserved) -> MatchReservedResult {
+        if self.matches.contains_key(&reserved.maker_order_uuid) {return MatchReservedResult::NotMatched}
         let my_base_amount: MmNumber = self.request.get_base_amount();
         let my_rel_amount: MmNumber = self.request.get_rel_amount();
         let other_base_amount: MmNumber = reserved.get_base_amount();
         let other_rel_amount: MmNumber = reserved.get_rel_amount();
+        let unmatched = self.unmatched_base_amount();
+        // the reply can be for a part of the unmatched amount if the partial fills are allowed
+        let fits = |amount: &MmNumber| if self.request.partial_fill {
+            *amount > MmNumber::from(0) && *amount <= unmatched
+        } else {
+            *amount == my_base_amount && self.matches.is_empty()
+        };
 
         match self.request.action {
+            // the maker price can't be higher than ours: other_rel / other_base <= my_rel / my_base
             TakerAction::Buy => if self.request.base == reserved.base && self.request.rel == reserved.rel
-                && my_base_amount == other_base_amount && other_rel_amount <= my_rel_amount {
+                && fits(&other_base_amount) && &other_rel_amount * &my_base_amount <= &my_rel_amount * &other_base_amount {
                 MatchReservedResult::Matched
             } else {
                 MatchReservedResult::NotMatched
             },
+            // we should get at least our price: other_base / other_rel >= my_rel / my_base
             TakerAction::Sell => if self.request.base == reserved.rel && self.request.rel == reserved.base
-                && my_base_amount == other_rel_amount && my_rel_amount <= other_base_amount {
+                && fits(&other_rel_amount) && &my_rel_amount * &other_rel_amount <= &other_base_amount * &my_base_amount {
                 MatchReservedResult::Matched
             } else {
                 MatchReservedResult::NotMatched
@@ -160,11 +321,21 @@ pub struct MakerOrder {
     matches: HashMap<Uuid, MakerMatch>,
     started_swaps: Vec<Uuid>,
     uuid: Uuid,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    /// The pubkeys of the only nodes the order is sent to and matched with, the order is public if empty.
+    #[serde(default)]
+    allowed_pubkeys: Vec<String>,
 }
 
 fn zero_rat() -> BigRational { BigRational::zero() }
 
 impl MakerOrder {
+    /// Whether the node having the `pubkey` can match the order.
+    fn is_allowed(&self, pubkey: &H256Json) -> bool {
+        self.allowed_pubkeys.is_empty() || self.allowed_pubkeys.contains(&hex::encode(&pubkey.0))
+    }
+
     fn available_amount(&self) -> MmNumber {
         let reserved: MmNumber = self.matches.iter().fold(
             MmNumber::from(BigRational::from_integer(0.into())),
@@ -188,14 +359,17 @@ impl MakerOrder {
     }
 }
 
+/// Converts the unmatched amount of the taker order to a maker order.
 impl Into<MakerOrder> for TakerOrder {
     fn into(self) -> MakerOrder {
+        let base_amount = self.unmatched_base_amount();
+        let rel_amount = &base_amount * &(self.request.get_rel_amount() / self.request.get_base_amount());
         let order = match self.request.action {
             TakerAction::Sell => MakerOrder {
                 price: &self.request.rel_amount / &self.request.base_amount,
                 price_rat: (self.request.get_rel_amount() / self.request.get_base_amount()).into(),
-                max_base_vol_rat: self.request.get_base_amount().into(),
-                max_base_vol: self.request.base_amount,
+                max_base_vol_rat: base_amount.clone().into(),
+                max_base_vol: base_amount.into(),
                 min_base_vol_rat: BigRational::from_integer(0.into()),
                 min_base_vol: 0.into(),
                 created_at: now_ms(),
@@ -204,13 +378,15 @@ impl Into<MakerOrder> for TakerOrder {
                 matches: HashMap::new(),
                 started_swaps: Vec::new(),
                 uuid: self.request.uuid,
+                time_in_force: self.time_in_force,
+                allowed_pubkeys: Vec::new(),
             },
             // The "buy" taker order is recreated with reversed pair as Maker order is always considered as "sell"
             TakerAction::Buy => MakerOrder {
                 price: &self.request.base_amount / &self.request.rel_amount,
                 price_rat: (self.request.get_base_amount() / self.request.get_rel_amount()).into(),
-                max_base_vol_rat: self.request.get_rel_amount().into(),
-                max_base_vol: self.request.rel_amount,
+                max_base_vol_rat: rel_amount.clone().into(),
+                max_base_vol: rel_amount.into(),
                 min_base_vol: 0.into(),
                 min_base_vol_rat: BigRational::from_integer(0.into()),
                 created_at: now_ms(),
@@ -219,6 +395,8 @@ impl Into<MakerOrder> for TakerOrder {
                 matches: HashMap::new(),
                 started_swaps: Vec::new(),
                 uuid: self.request.uuid,
+                time_in_force: self.time_in_force,
+                allowed_pubkeys: Vec::new(),
             },
         };
         order
@@ -232,6 +410,16 @@ struct TakerConnect {
     method: String,
     sender_pubkey: H256Json,
     dest_pub_key: H256Json,
+    /// The uuid of the swap when the taker order is partially filled by the maker order.
+    /// The swap uuid is the `taker_order_uuid` otherwise, as it is for the nodes not supporting the partial fills.
+    #[serde(default)]
+    swap_uuid: Option<Uuid>,
+}
+
+impl TakerConnect {
+    fn swap_uuid(&self) -> Uuid {
+        self.swap_uuid.unwrap_or(self.taker_order_uuid)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -274,22 +462,56 @@ struct MakerConnected {
     dest_pub_key: H256Json,
 }
 
+/// The reply of the maker to the taker request it doesn't match, cf. `lp_trade_command`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct MakerRejected {
+    taker_order_uuid: Uuid,
+    maker_order_uuid: Uuid,
+    method: String,
+    sender_pubkey: H256Json,
+    dest_pub_key: H256Json,
+    reason: String,
+}
+
 struct OrdermatchContext {
     pub my_maker_orders: Mutex<HashMap<Uuid, MakerOrder>>,
     pub my_taker_orders: Mutex<HashMap<Uuid, TakerOrder>>,
     pub my_cancelled_orders: Mutex<HashMap<Uuid, MakerOrder>>,
+    /// My stop-loss and take-profit orders, including the triggered and the cancelled ones.
+    pub my_conditional_orders: Mutex<HashMap<Uuid, ConditionalOrder>>,
+    /// The external prices the conditional orders are triggered by, cf. `external_prices_loop`.
+    pub external_prices: Mutex<ExternalPrices>,
     pub orderbook: Mutex<HashMap<(String, String), HashMap<Uuid, PricePingRequest>>>,
+    /// The "reserved" replies to my taker orders collected within the `reserved_window`, in the order of arrival.
+    pub pending_reserved: Mutex<HashMap<Uuid, Vec<MakerReserved>>>,
+    /// The `pubsecp` of the nodes which have sent us the version 2 signed price pings, cf. `PricePingRequest::verify`.
+    pub price_ping_v2_signers: Mutex<HashSet<Vec<u8>>>,
+    /// The limits on the orders of the other nodes in the `orderbook`, cf. the "orderbook_limits" configuration.
+    pub orderbook_limits: OrderbookLimits,
+    /// The pubkeys whose price pings are dropped and the reasons they are banned for.
+    pub banned_pubkeys: Mutex<HashMap<String, String>>,
+    /// The private orders sent to us, keyed by the maker pubkey and the order uuid,
+    /// and the time their makers have notified us at, cf. `PrivatePriceNotify`.
+    pub private_price_orders: Mutex<HashMap<(String, Uuid), u64>>,
 }
 
 impl OrdermatchContext {
     /// Obtains a reference to this crate context, creating it if necessary.
+    /// Fails on the invalid "orderbook_limits" configuration or BANNED_PUBKEYS.json, cf. `init_ordermatch_context`.
     fn from_ctx (ctx: &MmArc) -> Result<Arc<OrdermatchContext>, String> {
         Ok (try_s! (from_ctx (&ctx.ordermatch_ctx, move || {
             Ok (OrdermatchContext {
                 my_taker_orders: Mutex::new (HashMap::default()),
                 my_maker_orders: Mutex::new (HashMap::default()),
                 my_cancelled_orders: Mutex::new (HashMap::default()),
+                my_conditional_orders: Mutex::new (HashMap::default()),
+                external_prices: Mutex::new (ExternalPrices::default()),
                 orderbook: Mutex::new (HashMap::default()),
+                pending_reserved: Mutex::new (HashMap::default()),
+                price_ping_v2_signers: Mutex::new (HashSet::default()),
+                orderbook_limits: try_s! (OrderbookLimits::from_conf (&ctx.conf)),
+                banned_pubkeys: Mutex::new (try_s! (load_banned_pubkeys (ctx))),
+                private_price_orders: Mutex::new (HashMap::default()),
             })
         })))
     }
@@ -302,6 +524,13 @@ impl OrdermatchContext {
     }
 }
 
+/// Creates the ordermatch context on the startup, refusing to start with the invalid "orderbook_limits" configuration
+/// or BANNED_PUBKEYS.json, so that `OrdermatchContext::from_ctx` doesn't fail later on.
+pub fn init_ordermatch_context(ctx: &MmArc) -> Result<(), String> {
+    try_s!(OrdermatchContext::from_ctx(ctx));
+    Ok(())
+}
+
 fn lp_connect_start_bob(ctx: &MmArc, maker_match: &MakerMatch) -> i32 {
     let mut retval = -1;
     let loop_thread = thread::Builder::new().name("maker_loop".into()).spawn({
@@ -335,7 +564,10 @@ fn lp_connect_start_bob(ctx: &MmArc, maker_match: &MakerMatch) -> i32 {
         let taker_amount = maker_match.reserved.get_rel_amount().into();
         let privkey = &ctx.secp256k1_key_pair().private().secret;
         let my_persistent_pub = unwrap!(compressed_pub_key_from_priv_raw(&privkey[..], ChecksumType::DSHA256));
-        let uuid = maker_match.request.uuid.to_string();
+        let uuid = match &maker_match.connect {
+            Some(connect) => connect.swap_uuid(),
+            None => maker_match.request.uuid,
+        }.to_string();
         move || {
             log!("Entering the maker_swap_loop " (maker_coin.ticker()) "/" (taker_coin.ticker()));
             let maker_swap = MakerSwap::new(
@@ -395,7 +627,7 @@ fn lp_connected_alice(ctx: &MmArc, taker_match: &TakerMatch) { // alice
         let my_persistent_pub = unwrap!(compressed_pub_key_from_priv_raw(&privkey[..], ChecksumType::DSHA256));
         let maker_amount = taker_match.reserved.get_base_amount().into();
         let taker_amount = taker_match.reserved.get_rel_amount().into();
-        let uuid = taker_match.reserved.taker_order_uuid.to_string();
+        let uuid = taker_match.connect.swap_uuid().to_string();
         move || {
             log!("Entering the taker_swap_loop " (maker_coin.ticker()) "/" (taker_coin.ticker()));
             let taker_swap = TakerSwap::new(
@@ -419,9 +651,23 @@ fn lp_connected_alice(ctx: &MmArc, taker_match: &TakerMatch) { // alice
     }
 }
 
+/// The default time, in milliseconds, the taker order is matched for before it's converted to a maker order (or cancelled),
+/// and the maker waits for the taker to connect to the match.
+const DEFAULT_ORDERMATCH_TIMEOUT_MS: u64 = 30000;
+/// Leaves the taker the time to collect the "reserved" replies and to connect.
+const MIN_ORDERMATCH_TIMEOUT_MS: u64 = 10000;
+
+/// The "ordermatch_timeout_ms" configuration value, at least twice the `reserved_window`.
+fn ordermatch_timeout(ctx: &MmArc) -> u64 {
+    let timeout = ctx.conf["ordermatch_timeout_ms"].as_u64().unwrap_or(DEFAULT_ORDERMATCH_TIMEOUT_MS);
+    timeout.max(MIN_ORDERMATCH_TIMEOUT_MS).max(reserved_window(ctx) * 2)
+}
+
 pub fn lp_ordermatch_loop(ctx: MmArc) {
-    const ORDERMATCH_TIMEOUT: u64 = 30000;
+    let ordermatch_timeout = ordermatch_timeout(&ctx);
     let mut last_price_broadcast = 0;
+    spawn(private_price_ping_loop(ctx.clone()));
+    spawn(external_prices_loop(ctx.clone()));
 
     loop {
         if ctx.is_stopping() { break }
@@ -429,13 +675,23 @@ pub fn lp_ordermatch_loop(ctx: MmArc) {
         let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
         let mut my_maker_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
         let mut my_cancelled_orders = unwrap!(ordermatch_ctx.my_cancelled_orders.lock());
-        // move the timed out and unmatched taker orders to maker
-        *my_taker_orders = my_taker_orders.drain().filter_map(|(uuid, order)| if order.created_at + ORDERMATCH_TIMEOUT < now_ms() {
+        // cancel the expired orders, move the timed out and unmatched taker orders to maker
+        *my_taker_orders = my_taker_orders.drain().filter_map(|(uuid, order)| if order.matches.is_empty() && order.time_in_force.is_expired(now_ms() / 1000) {
+            delete_my_taker_order(&ctx, &order);
+            publish_taker_order_cancelled(&ctx, &order, OrderCancellationReason::Expired);
+            None
+        } else if order.created_at + ordermatch_timeout < now_ms() {
             delete_my_taker_order(&ctx, &order);
-            if order.matches.is_empty() {
-                let maker_order = order.into();
-                save_my_maker_order(&ctx, &maker_order);
-                my_maker_orders.insert(uuid, maker_order);
+            // the matches still not confirmed by the makers are dropped
+            if order.unmatched_base_amount() > MmNumber::from(0) {
+                if order.time_in_force.is_immediate() {
+                    publish_taker_order_cancelled(&ctx, &order, OrderCancellationReason::NotMatched);
+                } else {
+                    let maker_order = order.into();
+                    save_my_maker_order(&ctx, &maker_order);
+                    publish_maker_order_event(&ctx, &maker_order, "Created");
+                    my_maker_orders.insert(uuid, maker_order);
+                }
             }
             None
         } else {
@@ -444,15 +700,24 @@ pub fn lp_ordermatch_loop(ctx: MmArc) {
         // remove timed out unfinished matches to unlock the reserved amount
         my_maker_orders.iter_mut().for_each(|(_, order)| {
             order.matches = order.matches.drain().filter(
-                |(_, order_match)| order_match.last_updated + ORDERMATCH_TIMEOUT > now_ms() || order_match.connected.is_some()
+                |(_, order_match)| order_match.last_updated + ordermatch_timeout > now_ms() || order_match.connected.is_some()
             ).collect();
             save_my_maker_order(&ctx, order);
         });
         *my_maker_orders = my_maker_orders.drain().filter_map(|(uuid, order)| {
-            let min_amount: BigDecimal = "0.00777".parse().unwrap();
-            let min_amount: MmNumber = min_amount.into();
-            if order.available_amount() <= min_amount && !order.has_ongoing_matches() {
+            let min_amount = min_order_volume(&ctx.conf, &order.base);
+            let reason = if order.has_ongoing_matches() {
+                None
+            } else if order.available_amount() <= min_amount || order.available_amount() < MmNumber::from(order.min_base_vol_rat.clone()) {
+                Some(OrderCancellationReason::InsufficientVolume)
+            } else if order.time_in_force.is_expired(now_ms() / 1000) {
+                Some(OrderCancellationReason::Expired)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
                 delete_my_maker_order(&ctx, &order);
+                publish_maker_order_cancelled(&ctx, &order, reason);
                 my_cancelled_orders.insert(uuid, order);
                 None
             } else {
@@ -473,7 +738,7 @@ pub fn lp_ordermatch_loop(ctx: MmArc) {
 
         let mut orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
         *orderbook = orderbook.drain().filter_map(|((base, rel), mut pair_orderbook)| {
-            pair_orderbook = pair_orderbook.drain().filter_map(|(pubkey, order)| if now_ms() / 1000 > order.timestamp + 30 {
+            pair_orderbook = pair_orderbook.drain().filter_map(|(pubkey, order)| if now_ms() / 1000 > order.timestamp + PRICE_PING_TTL {
                 None
             } else {
                 Some((pubkey, order))
@@ -486,10 +751,100 @@ pub fn lp_ordermatch_loop(ctx: MmArc) {
         }).collect();
         drop(orderbook);
 
+        if let Err(e) = process_conditional_orders(&ctx) {
+            ctx.log.log("", &[&"process_conditional_orders"], &format!("error {}", e));
+        }
+
         thread::sleep(Duration::from_secs(1));
     }
 }
 
+/// The default time, in milliseconds, the taker collects the "reserved" replies for before connecting to the best one.
+const DEFAULT_RESERVED_WINDOW_MS: u64 = 1000;
+/// Keeps the window well below the `ordermatch_timeout` of the taker order.
+const MAX_RESERVED_WINDOW_MS: u64 = 10000;
+
+/// The "reserved_window_ms" configuration value.
+/// 0 disables the collection, the taker then connects to the first matching reply.
+fn reserved_window(ctx: &MmArc) -> u64 {
+    let window = ctx.conf["reserved_window_ms"].as_u64().unwrap_or(DEFAULT_RESERVED_WINDOW_MS);
+    window.min(MAX_RESERVED_WINDOW_MS)
+}
+
+/// Sorts the replies by the price, the best for the taker first.
+/// The price is always `rel_amount / base_amount` of the reply: the buying taker pays the `rel_amount` for the `base_amount`,
+/// the selling taker gets the `base_amount` for the `rel_amount`, so the lower the better.
+/// On a tie the order seen in the `orderbook` first goes first, cf. `PricePingRequest::first_seen`.
+fn reserved_by_price(replies: Vec<MakerReserved>, orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>)
+                     -> Vec<MakerReserved> {
+    let mut priced: Vec<(MmNumber, u64, MakerReserved)> = replies.into_iter().filter_map(|reply| {
+        let base_amount = reply.get_base_amount();
+        // zero base amount can't be priced
+        if base_amount <= MmNumber::from(0) {return None}
+        // the orders missing in the orderbook are considered the newest
+        let first_seen = orderbook.get(&(reply.base.clone(), reply.rel.clone()))
+            .and_then(|pair_orders| pair_orders.get(&reply.maker_order_uuid))
+            .map(|ping| ping.first_seen)
+            .unwrap_or(u64::max_value());
+        Some((reply.get_rel_amount() / base_amount, first_seen, reply))
+    }).collect();
+    // the older orders go first among the equal prices, the sort is stable, keeping the order of arrival otherwise
+    priced.sort_by(|(a, a_seen, _), (b, b_seen, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal).then(a_seen.cmp(b_seen)));
+    priced.into_iter().map(|(_, _, reply)| reply).collect()
+}
+
+/// Sends the "connect" message to the maker of the `reserved` reply, matching my taker order with it.
+fn taker_connect(ctx: &MmArc, my_order: &mut TakerOrder, reserved: MakerReserved) {
+    let our_public_id = unwrap!(ctx.public_id());
+    // a new swap uuid is only needed if the order is filled by several makers
+    let swap_uuid = if my_order.reserved_base_amount(&reserved) == my_order.request.get_base_amount() {
+        None
+    } else {
+        Some(new_uuid())
+    };
+    let connect = TakerConnect {
+        sender_pubkey: H256Json::from(our_public_id.bytes),
+        dest_pub_key: reserved.sender_pubkey.clone(),
+        method: "connect".into(),
+        taker_order_uuid: reserved.taker_order_uuid,
+        maker_order_uuid: reserved.maker_order_uuid,
+        swap_uuid,
+    };
+    ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&connect)));
+    let taker_match = TakerMatch {
+        reserved,
+        connect,
+        connected: None,
+        last_updated: now_ms(),
+    };
+    my_order.matches.insert(taker_match.reserved.maker_order_uuid, taker_match);
+    save_my_taker_order(ctx, my_order);
+    publish_taker_order_event(ctx, my_order, "Matched");
+}
+
+/// Connects my taker order to the best priced "reserved" replies collected within the `reserved_window`,
+/// as many as needed to fill the order.
+fn connect_to_best_reserved(ctx: &MmArc, taker_order_uuid: Uuid) {
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(ctx));
+    let replies = match unwrap!(ordermatch_ctx.pending_reserved.lock()).remove(&taker_order_uuid) {
+        Some(replies) => replies,
+        None => return,
+    };
+    let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
+    let my_order = match my_taker_orders.get_mut(&taker_order_uuid) {
+        Some(order) => order,
+        // the order was cancelled in the meantime
+        None => return,
+    };
+    let replies_count = replies.len();
+    let replies = reserved_by_price(replies, &unwrap!(ordermatch_ctx.orderbook.lock()));
+    for reply in replies {
+        if my_order.match_reserved(&reply) != MatchReservedResult::Matched {continue}
+        log!("Taker order " (taker_order_uuid) " got " (replies_count) " reserved replies, connecting to the maker order " (reply.maker_order_uuid));
+        taker_connect(ctx, my_order, reply);
+    }
+}
+
 pub fn lp_trade_command(
     ctx: MmArc,
     json: Json,
@@ -521,28 +876,55 @@ pub fn lp_trade_command(
             return 1;
         }
 
-        // send "connect" message if reserved message targets our pubkey AND
-        // reserved amounts match our order AND order is NOT reserved by someone else (empty matches)
-        if my_order.match_reserved(&reserved_msg) == MatchReservedResult::Matched && my_order.matches.is_empty() {
-            let connect = TakerConnect {
-                sender_pubkey: H256Json::from(our_public_id.bytes),
-                dest_pub_key: reserved_msg.sender_pubkey.clone(),
-                method: "connect".into(),
-                taker_order_uuid: reserved_msg.taker_order_uuid,
-                maker_order_uuid: reserved_msg.maker_order_uuid,
-            };
-            ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&connect)));
-            let taker_match = TakerMatch {
-                reserved: reserved_msg,
-                connect,
-                connected: None,
-                last_updated: now_ms(),
-            };
-            my_order.matches.insert(taker_match.reserved.maker_order_uuid, taker_match);
-            save_my_taker_order(&ctx, &my_order);
+        // collect the "reserved" replies if the reserved amounts match the unmatched amount of our order
+        if my_order.match_reserved(&reserved_msg) == MatchReservedResult::Matched {
+            let window = reserved_window(&ctx);
+            if window == 0 {
+                taker_connect(&ctx, my_order, reserved_msg);
+                return 1;
+            }
+            let mut pending_reserved = unwrap!(ordermatch_ctx.pending_reserved.lock());
+            let replies = pending_reserved.entry(reserved_msg.taker_order_uuid).or_insert_with(Vec::new);
+            if replies.is_empty() {
+                let ctx = ctx.clone();
+                let uuid = reserved_msg.taker_order_uuid;
+                spawn(async move {
+                    Timer::sleep(window as f64 / 1000.).await;
+                    connect_to_best_reserved(&ctx, uuid)
+                });
+            }
+            if replies.iter().all(|r| r.maker_order_uuid != reserved_msg.maker_order_uuid) {
+                replies.push(reserved_msg);
+            }
         }
         return 1;
     }
+    if method == Some("rejected") {
+        let rejected: MakerRejected = match json::from_value(json.clone()) {
+            Ok(r) => r,
+            Err(_) => return 1,
+        };
+        if H256Json::from(our_public_id.bytes) != rejected.dest_pub_key {
+            // ignore the messages that do not target our node
+            return 1;
+        }
+
+        let mut my_taker_orders = unwrap!(ordermatch_ctx.my_taker_orders.lock());
+        let my_order = match my_taker_orders.get_mut(&rejected.taker_order_uuid) {
+            Some(order) => order,
+            // the order might be already filled by the other makers
+            None => return 1,
+        };
+        if my_order.request.dest_pub_key != H256Json::default() && my_order.request.dest_pub_key != rejected.sender_pubkey {
+            log!("got rejected response from different node " (hex::encode(&rejected.sender_pubkey.0)));
+            return 1;
+        }
+        log!("Taker order " (rejected.taker_order_uuid) " is rejected by the maker order " (rejected.maker_order_uuid) ": " (rejected.reason));
+        my_order.rejections.insert(rejected.maker_order_uuid, rejected.reason);
+        save_my_taker_order(&ctx, my_order);
+        publish_taker_order_event(&ctx, my_order, "Rejected");
+        return 1;
+    }
     if method == Some("connected") {
         let connected: MakerConnected = match json::from_value(json.clone()) {
             Ok(c) => c,
@@ -557,21 +939,33 @@ pub fn lp_trade_command(
                     return 1;
                 },
             };
-            let order_match = match my_order_entry.get().matches.get(&connected.maker_order_uuid) {
+            let my_order = my_order_entry.get_mut();
+            let order_match = match my_order.matches.get_mut(&connected.maker_order_uuid) {
                 Some(o) => o,
                 None => {
                     log!("Our node doesn't have the match with uuid "(connected.maker_order_uuid));
                     return 1;
                 }
             };
+            if order_match.connected.is_some() {
+                log!("The swap with the maker order " (connected.maker_order_uuid) " is already started");
+                return 1;
+            }
             // alice
             lp_connected_alice(
                 &ctx,
                 order_match,
             );
-            // remove the matched order immediately
-            delete_my_taker_order(&ctx, &my_order_entry.get());
-            my_order_entry.remove();
+            my_order.started_swaps.push(order_match.connect.swap_uuid());
+            order_match.connected = Some(connected);
+            publish_taker_order_event(&ctx, my_order, "SwapStarted");
+            if my_order.is_filled() {
+                // remove the filled order immediately
+                delete_my_taker_order(&ctx, my_order);
+                my_order_entry.remove();
+            } else {
+                save_my_taker_order(&ctx, my_order);
+            }
             // AG: Bob's p2p ID (`LP_mypub25519`) is in `json["srchash"]`.
             log!("CONNECTED.(" (json) ")");
         }
@@ -591,6 +985,26 @@ pub fn lp_trade_command(
         let mut my_orders = unwrap!(ordermatch_ctx.my_maker_orders.lock());
 
         for (uuid, order) in my_orders.iter_mut() {
+            // the expired order is waiting to be cancelled by the `lp_ordermatch_loop`
+            if order.time_in_force.is_expired(now_ms() / 1000) {continue}
+            if !order.is_allowed(&taker_request.sender_pubkey) {
+                log!("Request " (taker_request.uuid) " is from a pubkey not allowed by the private order " (uuid));
+                continue;
+            }
+            if let Some(min_volume) = below_min_volume(order, &taker_request) {
+                let reason = format!("The request volume is below the min_volume {} of the order", min_volume);
+                log!("Request " (taker_request.uuid) " is rejected by the order " (uuid) ": " (reason));
+                let rejected = MakerRejected {
+                    taker_order_uuid: taker_request.uuid,
+                    maker_order_uuid: *uuid,
+                    method: "rejected".into(),
+                    sender_pubkey: our_public_id.bytes.into(),
+                    dest_pub_key: taker_request.sender_pubkey.clone(),
+                    reason,
+                };
+                ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&rejected)));
+                continue;
+            }
             if let OrderMatchResult::Matched((base_amount, rel_amount)) = match_order_and_request(order, &taker_request) {
                 let reserved = MakerReserved {
                     dest_pub_key: taker_request.sender_pubkey.clone(),
@@ -615,6 +1029,7 @@ pub fn lp_trade_command(
                 };
                 order.matches.insert(maker_match.request.uuid, maker_match);
                 save_my_maker_order(&ctx, &order);
+                publish_maker_order_event(&ctx, &order, "Matched");
                 return 1;
             }
         }
@@ -651,31 +1066,67 @@ pub fn lp_trade_command(
                 method: "connected".into(),
             };
             ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&connected)));
+            let connect_msg_swap_uuid = connect_msg.swap_uuid();
             order_match.connect = Some(connect_msg);
             order_match.connected = Some(connected);
-            my_order.started_swaps.push(order_match.request.uuid);
+            my_order.started_swaps.push(connect_msg_swap_uuid);
             lp_connect_start_bob(&ctx, order_match);
             save_my_maker_order(&ctx, &my_order);
+            publish_maker_order_event(&ctx, &my_order, "SwapStarted");
         }
         return 1;
     }
     -1
 }
 
-fn check_locked_coins(ctx: &MmArc, amount: &MmNumber, balance: &BigDecimal, ticker: &str) -> impl Future<Item=(), Error=String> {
+fn check_locked_coins(ctx: &MmArc, amount: &MmNumber, balance: &BigDecimal, ticker: &str) -> Result<(), RpcError> {
     let locked = get_locked_amount(ctx, ticker);
     let available = balance - &locked;
     if amount > &available {
-        futures01::future::err(ERRL!("The {} amount {} is larger than available {:.8}, balance: {}, locked by swaps: {:.8}", ticker, amount, available, balance, locked))
+        let err = RpcError::new(
+            RpcErrorCode::NotSufficientBalance,
+            ERRL!("The {} amount {} is larger than available {:.8}, balance: {}, locked by swaps: {:.8}", ticker, amount, available, balance, locked)
+        );
+        Err(err.with_details(json!({
+            "coin": ticker,
+            "required": BigDecimal::from(amount.clone()),
+            "available": available,
+            "balance": balance,
+            "locked_by_swaps": locked,
+        })))
     } else {
-        futures01::future::ok(())
+        Ok(())
+    }
+}
+
+fn check_min_price(price: &MmNumber) -> Result<(), RpcError> {
+    if *price < MmNumber::from(BigRational::new(1.into(), 100000000.into())) {
+        return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Price is too low, minimum is 0.00000001")));
     }
+    Ok(())
+}
+
+/// How the price of the taker order is set.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum OrderType {
+    /// The price is given in the request.
+    Limit,
+    /// The price is computed from the orderbook, cf. `market_price`.
+    /// `max_slippage` is the percentage the price can be worse than the best price in the orderbook.
+    Market { max_slippage: MmNumber },
+}
+
+impl Default for OrderType {
+    fn default() -> OrderType { OrderType::Limit }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct AutoBuyInput {
     base: String,
     rel: String,
+    /// Computed from the orderbook for the market orders.
+    #[serde(default)]
     price: MmNumber,
     volume: MmNumber,
     timeout: Option<u32>,
@@ -686,51 +1137,176 @@ pub struct AutoBuyInput {
     gui: Option<String>,
     #[serde(rename="destpubkey")]
     #[serde(default)]
-    dest_pub_key: H256Json
+    dest_pub_key: H256Json,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    #[serde(default)]
+    order_type: OrderType,
+    /// Cancel the unmatched order instead of converting it to a maker order, defaults to the "taker_only" configuration.
+    taker_only: Option<bool>,
+}
+
+/// The limit price of the market order, in the `rel` coin per the `base` coin.
+#[derive(Debug, PartialEq)]
+struct MarketPrice {
+    /// The best price in the orderbook.
+    best: MmNumber,
+    /// The volume-weighted average price filling the `volume`.
+    average: MmNumber,
+    /// The best price worsened by the `max_slippage`, the makers with the better prices can fill the order.
+    limit: MmNumber,
 }
 
-pub fn buy(ctx: MmArc, json: Json) -> HyRes {
-    let input: AutoBuyInput = try_h!(json::from_value(json.clone()));
-    if input.base == input.rel {
-        return rpc_err_response(500, "Base and rel must be different coins");
+/// Computes the price of the market order buying (or selling) the `volume` of the `base` coin
+/// from the maker `orders` selling the `base` for the `rel` (or the `rel` for the `base` when selling).
+/// Fails if the orders within the `max_slippage` (percents) from the best price don't have enough volume.
+fn market_price(orders: Option<&HashMap<Uuid, PricePingRequest>>, my_pubkey: &str, is_buy: bool, volume: &MmNumber, max_slippage: &MmNumber)
+                -> Result<MarketPrice, RpcError> {
+    if *max_slippage < MmNumber::from(0) || *max_slippage >= MmNumber::from(100) {
+        return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The max_slippage must be at least 0 and less than 100")));
+    }
+    // the prices in the `rel` coin per the `base` coin and the volumes in the `base` coin
+    let mut levels: Vec<(MmNumber, MmNumber)> = orders.map(|orders| orders.values()
+        .filter(|order| order.pubkey != my_pubkey && order.balance > 0.into())
+        .map(|order| {
+            let price = MmNumber::from(order.get_price_rat());
+            let balance = MmNumber::from(order.balance_rat.clone().unwrap_or_else(|| from_dec_to_ratio(order.balance.clone())));
+            // the bids sell the `rel` for the `base` with the price in the `base` coin per the `rel` coin
+            if is_buy {(price, balance)} else {(MmNumber::from(1) / price.clone(), balance * price)}
+        }).collect()).unwrap_or_default();
+    if levels.is_empty() {
+        return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("There are no orders to fill the market order in the orderbook")));
     }
-    let rel_coin = try_h!(block_on(lp_coinfind(&ctx, &input.rel)));
-    let rel_coin = match rel_coin {
-        Some(c) => c,
-        None => return rpc_err_response(500, "Rel coin is not found or inactive")
+    // the best price first: the lowest ask or the highest bid
+    levels.sort_by(|(a, _), (b, _)| (if is_buy {a.partial_cmp(b)} else {b.partial_cmp(a)}).unwrap_or(Ordering::Equal));
+
+    let best = levels[0].0.clone();
+    let hundred = MmNumber::from(100);
+    let limit = if is_buy {
+        &best * &((&hundred + max_slippage) / hundred)
+    } else {
+        &best * &((hundred.clone() - max_slippage.clone()) / hundred)
     };
-    let base_coin = try_h!(block_on(lp_coinfind(&ctx, &input.base)));
-    let base_coin: MmCoinEnum = match base_coin {
-        Some(c) => c,
-        None => return rpc_err_response(500, "Base coin is not found or inactive")
+    let mut remaining = volume.clone();
+    let mut cost = MmNumber::from(0);
+    for (price, level_volume) in levels {
+        if remaining <= MmNumber::from(0) {break}
+        let within_limit = if is_buy {price <= limit} else {price >= limit};
+        if !within_limit {break}
+        let take = if level_volume < remaining {level_volume} else {remaining.clone()};
+        cost = cost + &take * &price;
+        remaining = remaining - take;
+    }
+    if remaining > MmNumber::from(0) {
+        let available = volume.clone() - remaining;
+        return Err(RpcError::new(
+            RpcErrorCode::InvalidRequest,
+            ERRL!("The orderbook is too thin to fill the volume {} within the max_slippage {}%, available {}", volume, max_slippage, available)
+        ).with_details(json!({
+            "volume": BigDecimal::from(volume.clone()),
+            "available": BigDecimal::from(available),
+        })))
+    }
+    Ok(MarketPrice {best, average: cost / volume.clone(), limit})
+}
+
+/// Sets the price of the market order to the limit price computed from the orderbook.
+/// The market orders aren't converted to the maker orders: they are `ImmediateOrCancel` unless `FillOrKill` is requested.
+fn resolve_market_order(ctx: &MmArc, input: &mut AutoBuyInput) -> Result<(), RpcError> {
+    let max_slippage = match &input.order_type {
+        OrderType::Limit => return Ok(()),
+        OrderType::Market { max_slippage } => max_slippage.clone(),
     };
+    if input.price != MmNumber::from(0) {
+        return Err(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The price of the market order is computed from the orderbook")));
+    }
+    match input.time_in_force {
+        TimeInForce::GoodTillCancelled => input.time_in_force = TimeInForce::ImmediateOrCancel,
+        TimeInForce::GoodTillTime { .. } => return Err(RpcError::new(
+            RpcErrorCode::InvalidRequest, ERRL!("The market orders are ImmediateOrCancel or FillOrKill"))),
+        TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill => (),
+    }
+    let is_buy = input.method == "buy";
+    let internal = |err: String| RpcError::new(RpcErrorCode::InternalError, ERRL!("{}", err));
+    let my_pubkey = hex::encode(&ctx.public_id().map_err(internal)?.bytes);
+    let ordermatch_ctx = OrdermatchContext::from_ctx(ctx).map_err(internal)?;
+    let orderbook = ordermatch_ctx.orderbook.lock().map_err(|err| internal(err.to_string()))?;
+    let pair = if is_buy {(input.base.clone(), input.rel.clone())} else {(input.rel.clone(), input.base.clone())};
+    let price = market_price(orderbook.get(&pair), &my_pubkey, is_buy, &input.volume, &max_slippage)?;
+    log!("Market " (input.method) " " (input.volume) " " (input.base) "/" (input.rel) ": the best price " (price.best)
+         ", the average price " (price.average) ", the limit price " (price.limit));
+    input.price = price.limit;
+    Ok(())
+}
+
+/// Makes the taker-only order `ImmediateOrCancel`: the order which isn't matched within the `ordermatch_timeout`
+/// is cancelled rather than left in the orderbook as a maker order.
+fn resolve_taker_only(ctx: &MmArc, input: &mut AutoBuyInput) -> Result<(), RpcError> {
+    let taker_only = input.taker_only.unwrap_or_else(|| ctx.conf["taker_only"].as_bool().unwrap_or(false));
+    if !taker_only {return Ok(())}
+    match input.time_in_force {
+        TimeInForce::GoodTillCancelled => input.time_in_force = TimeInForce::ImmediateOrCancel,
+        TimeInForce::GoodTillTime { .. } => return Err(RpcError::new(
+            RpcErrorCode::InvalidRequest, ERRL!("The taker_only orders are ImmediateOrCancel or FillOrKill"))),
+        TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill => (),
+    }
+    Ok(())
+}
+
+pub async fn buy(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let mut input: AutoBuyInput = try_tr!(InvalidRequest, json::from_value(req));
+    if input.base == input.rel {return RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")).into_response()}
+    if let Err(err) = resolve_market_order(&ctx, &mut input) {return err.into_response()}
+    if let Err(err) = resolve_taker_only(&ctx, &mut input) {return err.into_response()}
+    if let Err(err) = check_min_price(&input.price) {return err.into_response()}
+    if let Err(err) = input.time_in_force.check(true) {return err.into_response()}
+    let rel_coin = try_s!(lp_coinfind(&ctx, &input.rel).await);
+    let rel_coin = try_tr!(CoinNotEnabled, rel_coin.ok_or("Rel coin is not found or inactive"));
+    let base_coin = try_s!(lp_coinfind(&ctx, &input.base).await);
+    let base_coin = try_tr!(CoinNotEnabled, base_coin.ok_or("Base coin is not found or inactive"));
     let my_amount = &input.volume * &input.price;
-    Box::new(rel_coin.my_balance().and_then(move |my_balance| {
-        check_locked_coins(&ctx, &my_amount, &my_balance, rel_coin.ticker()).and_then(move |_| {
-            let dex_fee = dex_fee_amount(base_coin.ticker(), rel_coin.ticker(), &my_amount.clone().into());
-            let trade_info = TradeInfo::Taker(dex_fee);
-            rel_coin.check_i_have_enough_to_trade(&my_amount.clone().into(), &my_balance.clone().into(), trade_info).and_then(move |_|
-                base_coin.can_i_spend_other_payment().and_then(move |_|
-                    rpc_response(200, try_h!(lp_auto_buy(&ctx, input)))
-                )
-            )
-        })
-    }))
+    if input.time_in_force.is_immediate() {
+        let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+        let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+        let asks = orderbook.get(&(input.base.clone(), input.rel.clone()));
+        if let Err(err) = check_min_volume_of_matching_orders(asks, &input.price, &input.volume) {return err.into_response()}
+    }
+    let my_balance = try_tr!(TransportError, rel_coin.my_balance().compat().await);
+    if let Err(err) = check_locked_coins(&ctx, &my_amount, &my_balance, rel_coin.ticker()) {return err.into_response()}
+    let dex_fee = dex_fee_amount(base_coin.ticker(), rel_coin.ticker(), &my_amount.clone().into());
+    let trade_info = TradeInfo::Taker(dex_fee);
+    try_tr!(NotSufficientBalance, rel_coin.check_i_have_enough_to_trade(&my_amount.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
+    try_tr!(NotSufficientBalance, base_coin.can_i_spend_other_payment().compat().await);
+    let res = try_s!(lp_auto_buy(&ctx, input)).into_bytes();
+    Ok(try_s!(Response::builder().body(res)))
 }
 
 pub async fn sell(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
-    let input: AutoBuyInput = try_s!(json::from_value(req));
-    if input.base == input.rel {return ERR!("Base and rel must be different coins")}
+    let mut input: AutoBuyInput = try_tr!(InvalidRequest, json::from_value(req));
+    if input.base == input.rel {return RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")).into_response()}
+    if let Err(err) = resolve_market_order(&ctx, &mut input) {return err.into_response()}
+    if let Err(err) = resolve_taker_only(&ctx, &mut input) {return err.into_response()}
+    if let Err(err) = check_min_price(&input.price) {return err.into_response()}
+    if let Err(err) = input.time_in_force.check(true) {return err.into_response()}
     let base_coin = try_s!(lp_coinfind(&ctx, &input.base).await);
-    let base_coin = try_s!(base_coin.ok_or("Base coin is not found or inactive"));
+    let base_coin = try_tr!(CoinNotEnabled, base_coin.ok_or("Base coin is not found or inactive"));
     let rel_coin = try_s!(lp_coinfind(&ctx, &input.rel).await);
-    let rel_coin = try_s!(rel_coin.ok_or("Rel coin is not found or inactive"));
-    let my_balance = try_s!(base_coin.my_balance().compat().await);
-    try_s!(check_locked_coins(&ctx, &input.volume, &my_balance, base_coin.ticker()).compat().await);
+    let rel_coin = try_tr!(CoinNotEnabled, rel_coin.ok_or("Rel coin is not found or inactive"));
+    if input.time_in_force.is_immediate() {
+        // the maker orders selling the `rel` for the `base`, with the price and volume in the `rel` terms
+        let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+        let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+        let bids = orderbook.get(&(input.rel.clone(), input.base.clone()));
+        let max_price = MmNumber::from(1) / input.price.clone();
+        let rel_volume = &input.volume * &input.price;
+        if let Err(err) = check_min_volume_of_matching_orders(bids, &max_price, &rel_volume) {return err.into_response()}
+    }
+    let my_balance = try_tr!(TransportError, base_coin.my_balance().compat().await);
+    if let Err(err) = check_locked_coins(&ctx, &input.volume, &my_balance, base_coin.ticker()) {return err.into_response()}
     let dex_fee = dex_fee_amount(base_coin.ticker(), rel_coin.ticker(), &input.volume.clone().into());
     let trade_info = TradeInfo::Taker(dex_fee);
-    try_s!(base_coin.check_i_have_enough_to_trade(&input.volume.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
-    try_s!(rel_coin.can_i_spend_other_payment().compat().await);
+    try_tr!(NotSufficientBalance, base_coin.check_i_have_enough_to_trade(&input.volume.clone().into(), &my_balance.clone().into(), trade_info).compat().await);
+    try_tr!(NotSufficientBalance, rel_coin.can_i_spend_other_payment().compat().await);
     let res = try_s!(lp_auto_buy(&ctx, input)).into_bytes();
     Ok(try_s!(Response::builder().body(res)))
 }
@@ -786,6 +1362,7 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
         uuid,
         dest_pub_key: input.dest_pub_key,
         sender_pubkey: H256Json::from(our_public_id.bytes),
+        partial_fill: input.time_in_force != TimeInForce::FillOrKill,
         action,
     };
     ctx.broadcast_p2p_msg(&unwrap!(json::to_string(&request)));
@@ -796,8 +1373,12 @@ pub fn lp_auto_buy(ctx: &MmArc, input: AutoBuyInput) -> Result<String, String> {
         created_at: now_ms(),
         matches: HashMap::new(),
         request,
+        time_in_force: input.time_in_force,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
     };
     save_my_taker_order(ctx, &order);
+    publish_taker_order_event(ctx, &order, "Created");
     my_taker_orders.insert(uuid, order);
     drop(my_taker_orders);
     Ok(result)
@@ -814,7 +1395,38 @@ fn price_ping_sig_hash(timestamp: u32, pubsecp: &[u8], pubkey: &[u8], base: &[u8
     sha256(&input)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Hashes every field of the price ping used by the receiving node (all but the "method" and the signatures),
+/// each field is prefixed by its length so that the bytes can't be moved from one field to another.
+fn price_ping_sig_hash_v2(req: &PricePingRequest) -> Result<H256, String> {
+    fn push(input: &mut Vec<u8>, bytes: &[u8]) {
+        input.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        input.extend_from_slice(bytes);
+    }
+    fn push_opt<T: ToString>(input: &mut Vec<u8>, value: &Option<T>) {
+        match value {
+            Some(value) => {input.push(1); push(input, value.to_string().as_bytes())},
+            None => input.push(0),
+        }
+    }
+
+    let mut input = b"price_ping_v2".to_vec();
+    push(&mut input, &try_s!(hex::decode(&req.pubsecp)));
+    push(&mut input, &try_s!(hex::decode(&req.pubkey)));
+    push(&mut input, req.base.as_bytes());
+    push(&mut input, req.rel.as_bytes());
+    push(&mut input, &req.timestamp.to_le_bytes());
+    push(&mut input, req.price.to_string().as_bytes());
+    push_opt(&mut input, &req.price_rat);
+    push(&mut input, req.price64.as_bytes());
+    push(&mut input, req.balance.to_string().as_bytes());
+    push_opt(&mut input, &req.balance_rat);
+    push_opt(&mut input, &req.uuid);
+    push_opt(&mut input, &req.min_volume);
+    push_opt(&mut input, &req.min_volume_rat);
+    Ok(sha256(&input))
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct PricePingRequest {
     method: String,
     pubkey: String,
@@ -825,12 +1437,26 @@ struct PricePingRequest {
     price64: String,
     timestamp: u64,
     pubsecp: String,
+    /// The legacy signature of the `price_ping_sig_hash`, covering the pair and the `price64` only.
     sig: String,
+    /// The signature of the `price_ping_sig_hash_v2`, covering the whole ping.
+    /// Missing in the pings of the nodes not supporting the version 2.
+    #[serde(default)]
+    sig_v2: Option<String>,
     // TODO rename, it's called "balance", but it's actual meaning is max available volume to trade
     #[serde(rename="bal")]
     balance: BigDecimal,
     balance_rat: Option<BigRational>,
     uuid: Option<Uuid>,
+    /// The minimal volume the maker order can be matched with, in the base coin.
+    /// Missing in the pings of the nodes not supporting the `min_volume`.
+    #[serde(default)]
+    min_volume: Option<BigDecimal>,
+    #[serde(default)]
+    min_volume_rat: Option<BigRational>,
+    /// The time this node has received the first ping of the order at, the age of the order in the orderbook.
+    #[serde(skip)]
+    first_seen: u64,
 }
 
 impl PricePingRequest {
@@ -848,20 +1474,9 @@ impl PricePingRequest {
         let public_id = try_s!(ctx.public_id());
 
         let price64 = (&order.price * BigDecimal::from(100000000)).to_u64().unwrap();
-        let timestamp = now_ms() / 1000;
-        let sig_hash = price_ping_sig_hash(
-            timestamp as u32,
-            &**ctx.secp256k1_key_pair().public(),
-            &public_id.bytes,
-            order.base.as_bytes(),
-            order.rel.as_bytes(),
-            price64,
-        );
-
-        let sig = try_s!(ctx.secp256k1_key_pair().private().sign(&sig_hash));
 
         let available_amount: BigRational = order.available_amount().into();
-        let min_amount = BigRational::new(777.into(), 100000.into());
+        let min_amount: BigRational = min_order_volume(&ctx.conf, &order.base).into();
         let max_volume = if available_amount > min_amount {
             let my_balance = from_dec_to_ratio(try_s!(base_coin.my_balance().wait()));
             if available_amount <= my_balance && available_amount > BigRational::from_integer(0.into()) {
@@ -873,7 +1488,7 @@ impl PricePingRequest {
             BigRational::from_integer(0.into())
         };
 
-        Ok(PricePingRequest {
+        let mut req = PricePingRequest {
             method: "postprice".into(),
             pubkey: hex::encode(&public_id.bytes),
             base: order.base.clone(),
@@ -881,64 +1496,181 @@ impl PricePingRequest {
             price64: price64.to_string(),
             price: order.price.clone(),
             price_rat: Some(order.price_rat.clone()),
-            timestamp,
+            timestamp: now_ms() / 1000,
             pubsecp: hex::encode(&**ctx.secp256k1_key_pair().public()),
-            sig: hex::encode(&*sig),
+            sig: String::new(),
+            sig_v2: None,
             balance: from_ratio_to_dec(&max_volume),
             balance_rat: Some(max_volume),
             uuid: Some(order.uuid),
-        })
+            min_volume: Some(order.min_base_vol.clone()),
+            min_volume_rat: Some(order.min_base_vol_rat.clone()),
+            first_seen: 0,
+        };
+        try_s!(req.sign(ctx.secp256k1_key_pair()));
+        Ok(req)
+    }
+
+    /// Fills both the legacy `sig` (checked by the nodes not supporting the version 2) and the `sig_v2`.
+    fn sign(&mut self, key_pair: &KeyPair) -> Result<(), String> {
+        let sig_hash = price_ping_sig_hash(
+            self.timestamp as u32,
+            &try_s!(hex::decode(&self.pubsecp)),
+            &try_s!(hex::decode(&self.pubkey)),
+            self.base.as_bytes(),
+            self.rel.as_bytes(),
+            try_s!(self.price64.parse()),
+        );
+        self.sig = hex::encode(&*try_s!(key_pair.private().sign(&sig_hash)));
+        let sig_hash_v2 = try_s!(price_ping_sig_hash_v2(self));
+        self.sig_v2 = Some(hex::encode(&*try_s!(key_pair.private().sign(&sig_hash_v2))));
+        Ok(())
+    }
+
+    /// Checks the signature of the ping.
+    ///
+    /// The version 2 pings are verified as a whole, so a relaying node can't alter any of their fields.
+    /// The legacy pings only sign the pair and the `price64`, they are still accepted during the transition
+    /// unless `accept_legacy` is false or the signer has already sent us a version 2 ping:
+    /// the `v2_signers` protect the orders of the updated nodes from the relays stripping the `sig_v2` to alter the other fields.
+    fn verify(&self, v2_signers: &mut HashSet<Vec<u8>>, accept_legacy: bool) -> Result<(), String> {
+        let pubsecp = try_s!(hex::decode(&self.pubsecp));
+        let pub_secp = try_s!(Public::from_slice(&pubsecp));
+        match self.sig_v2 {
+            Some(ref sig_v2) => {
+                let signature: Signature = try_s!(sig_v2.parse());
+                if !try_s!(pub_secp.verify(&try_s!(price_ping_sig_hash_v2(self)), &signature)) {
+                    return ERR!("price ping invalid signature");
+                }
+                v2_signers.insert(pubsecp);
+            },
+            None => {
+                if !accept_legacy {return ERR!("price ping legacy signature is not accepted")}
+                if v2_signers.contains(&pubsecp) {return ERR!("price ping legacy signature from a version 2 signer")}
+                let signature: Signature = try_s!(self.sig.parse());
+                let sig_hash = price_ping_sig_hash(
+                    self.timestamp as u32,
+                    &pubsecp,
+                    &try_s!(hex::decode(&self.pubkey)),
+                    self.base.as_bytes(),
+                    self.rel.as_bytes(),
+                    try_s!(self.price64.parse()),
+                );
+                if !try_s!(pub_secp.verify(&sig_hash, &signature)) {return ERR!("price ping invalid signature")}
+            },
+        }
+        Ok(())
     }
+
+    fn get_price_rat(&self) -> BigRational {
+        self.price_rat.clone().unwrap_or_else(|| from_dec_to_ratio(self.price.clone()))
+    }
+
+    fn get_balance_rat(&self) -> BigRational {
+        self.balance_rat.clone().unwrap_or_else(|| from_dec_to_ratio(self.balance.clone()))
+    }
+
+    fn get_min_volume_rat(&self) -> BigRational {
+        match (&self.min_volume_rat, &self.min_volume) {
+            (Some(rat), _) => rat.clone(),
+            (None, Some(dec)) => from_dec_to_ratio(dec.clone()),
+            (None, None) => BigRational::zero(),
+        }
+    }
+}
+
+/// Checks the taker `volume` against the `min_volume` of the known maker `orders` having an acceptable price.
+///
+/// Only applied to the ImmediateOrCancel and FillOrKill requests: they're cancelled unless matched at once,
+/// so we'd rather tell the user beforehand that the request can only be matched by an order we don't know about.
+/// The other requests might still be matched by the private or not yet seen orders, or become the maker orders.
+fn check_min_volume_of_matching_orders(orders: Option<&HashMap<Uuid, PricePingRequest>>, max_price: &MmNumber, volume: &MmNumber)
+                                       -> Result<(), RpcError> {
+    let orders = match orders {Some(orders) => orders, None => return Ok(())};
+    let min_volumes: Vec<MmNumber> = orders.values()
+        .filter(|order| order.balance > 0.into() && MmNumber::from(order.get_price_rat()) <= *max_price)
+        .map(|order| order.get_min_volume_rat().into())
+        .collect();
+    if min_volumes.is_empty() || min_volumes.iter().any(|min_volume| min_volume <= volume) {return Ok(())}
+    let lowest = unwrap!(min_volumes.into_iter().fold(None, |lowest: Option<MmNumber>, min_volume| match lowest {
+        Some(lowest) if lowest <= min_volume => Some(lowest),
+        _ => Some(min_volume),
+    }));
+    Err(RpcError::new(
+        RpcErrorCode::InvalidRequest,
+        ERRL!("The volume {} is below the min_volume of the orders matching the price, the lowest min_volume is {}", volume, lowest)
+    ).with_details(json!({
+        "volume": BigDecimal::from(volume.clone()),
+        "min_volume": BigDecimal::from(lowest),
+    })))
 }
 
 pub fn lp_post_price_recv(ctx: &MmArc, req: Json) -> HyRes {
-    let req: PricePingRequest = try_h!(json::from_value(req));
-    let signature: Signature = try_h!(req.sig.parse());
-    let pub_secp = try_h!(Public::from_slice(&try_h!(hex::decode(&req.pubsecp))));
+    let mut req: PricePingRequest = try_h!(json::from_value(req));
     let pubkey = try_h!(hex::decode(&req.pubkey));
-    let sig_hash = price_ping_sig_hash(
-        req.timestamp as u32,
-        &*pub_secp,
-        &pubkey,
-        req.base.as_bytes(),
-        req.rel.as_bytes(),
-        try_h!(req.price64.parse()),
-    );
-    let sig_check = try_h!(pub_secp.verify(&sig_hash, &signature));
-    if sig_check {
-        // identify the order by first 16 bytes of node pubkey to keep backwards-compatibility
-        // TODO remove this when all nodes are updated
-        let mut bytes = [0; 16];
-        bytes.copy_from_slice(&pubkey[..16]);
-        let uuid = req.uuid.unwrap_or(Uuid::from_bytes(bytes));
-        let ordermatch_ctx: Arc<OrdermatchContext> = try_h!(OrdermatchContext::from_ctx(ctx));
-        let mut orderbook = try_h!(ordermatch_ctx.orderbook.lock());
-        match orderbook.entry((req.base.clone(), req.rel.clone())) {
-            Entry::Vacant(pair_orders) => if req.balance > 0.into() && req.price > 0.into() {
-                let mut orders = HashMap::new();
-                orders.insert(uuid, req);
-                pair_orders.insert(orders);
-            },
-            Entry::Occupied(mut pair_orders) => {
-                match pair_orders.get_mut().entry(uuid) {
-                    Entry::Vacant(order) => if req.balance > 0.into() && req.price > 0.into() {
-                        order.insert(req);
-                    },
-                    Entry::Occupied(mut order) => if req.balance > 0.into() {
-                        order.insert(req);
-                    } else {
-                        order.remove();
-                    },
-                }
+    let ordermatch_ctx: Arc<OrdermatchContext> = try_h!(OrdermatchContext::from_ctx(ctx));
+    let accept_legacy = ctx.conf["accept_legacy_price_pings"].as_bool().unwrap_or(true);
+    let verified = req.verify(&mut *try_h!(ordermatch_ctx.price_ping_v2_signers.lock()), accept_legacy);
+    if let Err(err) = verified {return rpc_err_response(400, &err)}
+    // the limits and the bans are checked against the same hex of the pubkey
+    req.pubkey = hex::encode(&pubkey);
+    if try_h!(ordermatch_ctx.banned_pubkeys.lock()).contains_key(&req.pubkey) {
+        return rpc_err_response(400, "price ping from a banned pubkey");
+    }
+
+    // identify the order by first 16 bytes of node pubkey to keep backwards-compatibility
+    // TODO remove this when all nodes are updated
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&pubkey[..16]);
+    let uuid = req.uuid.unwrap_or(Uuid::from_bytes(bytes));
+    let pair = (req.base.clone(), req.rel.clone());
+    let mut orderbook = try_h!(ordermatch_ctx.orderbook.lock());
+    let first_seen = match orderbook.get(&pair).and_then(|pair_orders| pair_orders.get(&uuid)) {
+        Some(order) if order.pubkey != req.pubkey => return rpc_err_response(400, &ERRL!("The order {} belongs to another pubkey", uuid)),
+        Some(order) => Some(order.first_seen),
+        None => None,
+    };
+    let known = first_seen.is_some();
+    // the ping timestamp is up to the maker, so the age of the order is counted by our clock
+    req.first_seen = first_seen.unwrap_or_else(|| now_ms() / 1000);
+    // the limits protect the orderbook from the other nodes, my own orders are always kept
+    let is_mine = hex::encode(&try_h!(ctx.public_id()).bytes) == req.pubkey;
+    if req.balance > 0.into() && !is_mine {
+        let checked = match check_min_order_volume(&ctx.conf, &req).and_then(|()| check_price_bounds(&ctx.conf, &req)) {
+            Ok(()) if !known => check_new_order_limits(&ordermatch_ctx.orderbook_limits, &orderbook, &req),
+            checked => checked,
+        };
+        if let Err(err) = checked {
+            // the known order updated below the min volume or out of the price bounds is dropped
+            if let Some(pair_orders) = orderbook.get_mut(&pair) {pair_orders.remove(&uuid);}
+            return rpc_err_response(400, &err);
+        }
+    }
+    match orderbook.entry(pair) {
+        Entry::Vacant(pair_orders) => if req.balance > 0.into() && req.price > 0.into() {
+            let mut orders = HashMap::new();
+            orders.insert(uuid, req);
+            pair_orders.insert(orders);
+        },
+        Entry::Occupied(mut pair_orders) => {
+            match pair_orders.get_mut().entry(uuid) {
+                Entry::Vacant(order) => if req.balance > 0.into() && req.price > 0.into() {
+                    order.insert(req);
+                },
+                Entry::Occupied(mut order) => if req.balance > 0.into() {
+                    order.insert(req);
+                } else {
+                    order.remove();
+                },
             }
         }
-        rpc_response(200, r#"{"result":"success"}"#)
-    } else {
-        rpc_err_response(400, "price ping invalid signature")
     }
+    rpc_response(200, r#"{"result":"success"}"#)
 }
 
-fn lp_send_price_ping(req: &PricePingRequest, ctx: &MmArc) -> Result<(), String> {
+/// Sends the price ping of my maker order to the other nodes.
+/// The ping of a private order is only sent to its `allowed_pubkeys`, cf. `send_private_price_ping`.
+fn lp_send_price_ping(req: &PricePingRequest, allowed_pubkeys: &[String], ctx: &MmArc) -> Result<(), String> {
     let req_string = try_s!(json::to_string(req));
 
     // TODO this is required to process the set price message on our own node, it's the easiest way now
@@ -950,10 +1682,172 @@ fn lp_send_price_ping(req: &PricePingRequest, ctx: &MmArc) -> Result<(), String>
         if let Err(err) = rc {log!("!lp_post_price_recv: "(err))}
     });
 
-    ctx.broadcast_p2p_msg(&req_string);
+    if allowed_pubkeys.is_empty() {
+        ctx.broadcast_p2p_msg(&req_string);
+    } else {
+        let uuid = try_s!(req.uuid.ok_or("The price ping has no uuid"));
+        try_s!(send_private_price_ping(ctx, uuid, &req_string, allowed_pubkeys));
+    }
+    Ok(())
+}
+
+/// The subject the private price pings of the `uuid` order of the `sender` maker are sent to the peers with.
+///
+/// The DHT keeps a single value per subject and recipient, so every private order needs a subject of its own,
+/// and the recipients learn the orders to poll from the `PrivatePriceNotify` broadcasts.
+fn private_price_ping_subject(sender: &str, uuid: &Uuid) -> String {
+    format!("private_price_ping@{}@{}", sender, uuid)
+}
+/// Sending the private ping until the next `broadcast_my_maker_orders`, and polling for them as often.
+const PRIVATE_PRICE_PING_INTERVAL: f64 = 10.;
+/// The seconds the peers are trying the DHT before the HTTP fallback.
+const PRIVATE_PRICE_PING_FALLBACK: u8 = 10;
+/// The orderbook drops the pings older than this (cf. `lp_ordermatch_loop`),
+/// the private pings older than this are the leftovers of the previous transmissions.
+const PRICE_PING_TTL: u64 = 30;
+/// We stop polling a private order whose maker hasn't notified us about it for this long.
+const PRIVATE_PRICE_NOTIFY_TTL: u64 = PRICE_PING_TTL * 2;
+/// The maximum number of the private orders we poll for the pings at once.
+const MAX_PRIVATE_PRICE_ORDERS: usize = 100;
+
+/// Broadcasted along with the private price pings to let their recipients know that there is something to receive.
+#[derive(Debug, Deserialize, Serialize)]
+struct PrivatePriceNotify {
+    method: String,
+    /// The `pubkey` of the maker sending the pings.
+    sender: String,
+    /// The uuid of the private order the pings are sent for.
+    uuid: Uuid,
+    /// The `dhash160` of the recipients' pubkeys, used as the lookup keys only.
+    /// The recipients aren't hidden: anyone can hash a known pubkey and compare.
+    recipients: Vec<String>,
+    timestamp: u64,
+}
+
+impl PrivatePriceNotify {
+    fn new(ctx: &MmArc, uuid: Uuid, allowed_pubkeys: &[bits256]) -> Result<PrivatePriceNotify, String> {
+        Ok(PrivatePriceNotify {
+            method: "private_price_ping_notify".into(),
+            sender: hex::encode(&try_s!(ctx.public_id()).bytes),
+            uuid,
+            recipients: allowed_pubkeys.iter().map(|pubkey| hex::encode(&*dhash160(&pubkey.bytes))).collect(),
+            timestamp: now_ms() / 1000,
+        })
+    }
+}
+
+fn send_private_price_ping(ctx: &MmArc, uuid: Uuid, req_string: &str, allowed_pubkeys: &[String]) -> Result<(), String> {
+    let mut peers = Vec::with_capacity(allowed_pubkeys.len());
+    for pubkey in allowed_pubkeys {
+        let mut peer = bits256::default();
+        let bytes = try_s!(hex::decode(pubkey));
+        if bytes.len() != 32 {return ERR!("Invalid pubkey {}", pubkey)}
+        peer.bytes.copy_from_slice(&bytes);
+        peers.push(peer);
+    }
+
+    let notify = try_s!(PrivatePriceNotify::new(ctx, uuid, &peers));
+    ctx.broadcast_p2p_msg(&try_s!(json::to_string(&notify)));
+
+    for peer in peers {
+        let ctx = ctx.clone();
+        let subject = private_price_ping_subject(&notify.sender, &uuid).into_bytes();
+        let payload = req_string.as_bytes().to_vec();
+        spawn(async move {
+            match peers::send(ctx, peer, subject, PRIVATE_PRICE_PING_FALLBACK, payload).await {
+                Ok(send_handler) => {
+                    Timer::sleep(PRIVATE_PRICE_PING_INTERVAL).await;
+                    drop(send_handler)
+                },
+                Err(err) => log!("Error sending the private price ping to " (hex::encode(&peer.bytes)) ": " (err)),
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Remembers the private order whose pings are sent to us, cf. `private_price_ping_loop`.
+pub fn lp_private_price_notify_recv(ctx: &MmArc, req: Json) -> HyRes {
+    let notify: PrivatePriceNotify = try_h!(json::from_value(req));
+    if notify.timestamp + PRIVATE_PRICE_NOTIFY_TTL <= now_ms() / 1000 {
+        return rpc_err_response(400, "The notify is expired");
+    }
+    let my_hash = hex::encode(&*dhash160(&try_h!(ctx.public_id()).bytes));
+    if !notify.recipients.contains(&my_hash) {
+        return rpc_response(200, r#"{"result":"success"}"#);
+    }
+
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(ctx));
+    let mut orders = try_h!(ordermatch_ctx.private_price_orders.lock());
+    let key = (notify.sender, notify.uuid);
+    if !orders.contains_key(&key) && orders.len() >= MAX_PRIVATE_PRICE_ORDERS {
+        // make room for the new order by forgetting the one notified the longest time ago
+        let oldest = orders.iter().min_by_key(|(_, notified_at)| **notified_at).map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {orders.remove(&oldest);}
+    }
+    orders.insert(key, notify.timestamp);
+    rpc_response(200, r#"{"result":"success"}"#)
+}
+
+/// The maker pubkeys and the uuids of the private orders we've recently been notified about.
+fn private_price_orders(ctx: &MmArc) -> Result<Vec<(String, Uuid)>, String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut orders = try_s!(ordermatch_ctx.private_price_orders.lock());
+    let now = now_ms() / 1000;
+    orders.retain(|_, notified_at| *notified_at + PRIVATE_PRICE_NOTIFY_TTL > now);
+    Ok(orders.keys().cloned().collect())
+}
+
+/// Adds the private price ping of the `uuid` order received from the `sender` maker to the orderbook.
+async fn process_private_price_ping(ctx: &MmArc, sender: &str, uuid: &Uuid, payload: &[u8]) -> Result<(), String> {
+    let req: Json = try_s!(json::from_slice(payload));
+    if req["uuid"].as_str() != Some(&uuid.to_string()[..]) {
+        return ERR!("The ping of the {} order is received from the {} order subject", req["uuid"], uuid);
+    }
+    // the subject is keyed by the sender, but anyone can put a value under it,
+    // so the ping must be signed by the sender (the public ID is the compressed `pubsecp` without its prefix)
+    let pubsecp = try_s!(hex::decode(req["pubsecp"].as_str().unwrap_or("")));
+    let is_sender = |pubkey: &[u8]| Some(pubkey) == pubsecp.get(1..);
+    if !is_sender(&try_s!(hex::decode(sender))) || !is_sender(&try_s!(hex::decode(req["pubkey"].as_str().unwrap_or("")))) {
+        return ERR!("The ping of {} is received from the {} subject", req["pubkey"], sender);
+    }
+    if req["timestamp"].as_u64().unwrap_or(0) + PRICE_PING_TTL <= now_ms() / 1000 {return Ok(())}
+    let res = try_s!(lp_post_price_recv(ctx, req).compat().await);
+    if res.status() != 200 {return ERR!("!lp_post_price_recv: {}", String::from_utf8_lossy(res.body()))}
     Ok(())
 }
 
+async fn recv_private_price_ping(ctx: MmArc, sender: String, uuid: Uuid) {
+    let subject = private_price_ping_subject(&sender, &uuid).into_bytes();
+    let recv_f = peers::recv(ctx.clone(), subject, PRIVATE_PRICE_PING_FALLBACK, FixedValidator::AnythingGoes);
+    match select(Box::pin(recv_f), Timer::sleep(PRIVATE_PRICE_PING_INTERVAL)).await {
+        Either::Left((Ok(payload), _)) => if let Err(err) = process_private_price_ping(&ctx, &sender, &uuid, &payload).await {
+            log!("Error processing the private price ping of " (sender) " order " (uuid) ": " (err))
+        },
+        Either::Left((Err(err), _)) => log!("Error receiving the private price ping of " (sender) " order " (uuid) ": " (err)),
+        Either::Right(_) => (),
+    }
+}
+
+/// Receives the private price pings sent to us by the makers (cf. `send_private_price_ping`),
+/// polling the subjects of the orders we've been notified about (cf. `lp_private_price_notify_recv`).
+async fn private_price_ping_loop(ctx: MmArc) {
+    loop {
+        if ctx.is_stopping() {break}
+        let orders = match private_price_orders(&ctx) {
+            Ok(orders) => orders,
+            Err(err) => {log!("!private_price_orders: " (err)); Vec::new()},
+        };
+        if orders.is_empty() {
+            Timer::sleep(PRIVATE_PRICE_PING_INTERVAL).await;
+            continue
+        }
+        join_all(orders.into_iter().map(|(sender, uuid)| recv_private_price_ping(ctx.clone(), sender, uuid))).await;
+        // the DHT keeps returning the last ping until a newer one comes
+        Timer::sleep(PRIVATE_PRICE_PING_INTERVAL / 2.).await;
+    }
+}
+
 fn one() -> u8 { 1 }
 
 fn get_true() -> bool { true }
@@ -970,85 +1864,201 @@ struct SetPriceReq {
     broadcast: u8,
     #[serde(default)]
     volume: MmNumber,
+    /// The takers requesting less than the `min_volume` of the `base` coin won't be matched with the order.
+    #[serde(default)]
+    min_volume: MmNumber,
     #[serde(default = "get_true")]
     cancel_previous: bool,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    /// Makes the order private: it's only sent to and matched with the nodes having these pubkeys.
+    #[serde(default)]
+    allowed_pubkeys: Vec<String>,
 }
 
-pub fn set_price(ctx: MmArc, req: Json) -> HyRes {
-    let req: SetPriceReq = try_h!(json::from_value(req));
-    if req.price < MmNumber::from(BigRational::new(1.into(), 100000000.into())) {
-        return rpc_err_response(500, "Price is too low, minimum is 0.00000001");
-    }
+pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: SetPriceReq = try_tr!(InvalidRequest, json::from_value(req));
+    if let Err(err) = check_min_price(&req.price) {return err.into_response()}
+    if let Err(err) = req.time_in_force.check(false) {return err.into_response()}
 
     if req.base == req.rel {
-        return rpc_err_response(500, "Base and rel must be different coins");
+        return RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")).into_response();
+    }
+    let mut allowed_pubkeys = Vec::with_capacity(req.allowed_pubkeys.len());
+    for pubkey in req.allowed_pubkeys.iter() {
+        let pubkey = try_tr!(InvalidRequest, normalize_pubkey(pubkey));
+        if !allowed_pubkeys.contains(&pubkey) {allowed_pubkeys.push(pubkey)}
     }
 
-    let base_coin: MmCoinEnum = match try_h!(block_on(lp_coinfind(&ctx, &req.base))) {
+    let base_coin: MmCoinEnum = match try_s!(lp_coinfind(&ctx, &req.base).await) {
         Some(coin) => coin,
-        None => return rpc_err_response(500, &format!("Base coin {} is not found", req.base)),
+        None => return RpcError::new(RpcErrorCode::CoinNotEnabled, ERRL!("Base coin {} is not found", req.base)).into_response(),
     };
 
-    let rel_coin: MmCoinEnum = match try_h!(block_on(lp_coinfind(&ctx, &req.rel))) {
+    let rel_coin: MmCoinEnum = match try_s!(lp_coinfind(&ctx, &req.rel).await) {
         Some(coin) => coin,
-        None => return rpc_err_response(500, &format!("Rel coin {} is not found", req.rel)),
+        None => return RpcError::new(RpcErrorCode::CoinNotEnabled, ERRL!("Rel coin {} is not found", req.rel)).into_response(),
     };
 
-    let balance_f = base_coin.my_balance();
-    let volume_f = if req.max {
+    let my_balance = try_tr!(TransportError, base_coin.my_balance().compat().await);
+    let volume = if req.max {
         // use entire balance deducting the locked amount and skipping "check_i_have_enough"
-        Either::A(balance_f.map(move |my_balance| (MmNumber::from(my_balance - get_locked_amount(&ctx, base_coin.ticker())), req, ctx)))
+        MmNumber::from(my_balance - get_locked_amount(&ctx, base_coin.ticker()))
     } else {
-        Either::B(balance_f.and_then(move |my_balance|
-            check_locked_coins(&ctx, &req.volume.clone().into(), &my_balance, base_coin.ticker()).and_then(move |_|
-                base_coin.check_i_have_enough_to_trade(&req.volume, &my_balance.clone().into(), TradeInfo::Maker).map(move |_|
-                    (req.volume.clone(), req, ctx)
-                )
-            )
-        ))
+        if let Err(err) = check_locked_coins(&ctx, &req.volume, &my_balance, base_coin.ticker()) {return err.into_response()}
+        try_tr!(NotSufficientBalance, base_coin.check_i_have_enough_to_trade(&req.volume, &my_balance.clone().into(), TradeInfo::Maker).compat().await);
+        req.volume.clone()
     };
+    try_tr!(NotSufficientBalance, rel_coin.can_i_spend_other_payment().compat().await);
+    if req.min_volume < MmNumber::from(0) || req.min_volume > volume {
+        let err = RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The min_volume {} must be between 0 and the volume {}", req.min_volume, volume));
+        return err.into_response()
+    }
 
-    Box::new(
-        volume_f.and_then(move |(volume, req, ctx)| {
-            rel_coin.can_i_spend_other_payment().and_then(move |_| {
-                let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
-                let mut my_orders = try_h!(ordermatch_ctx.my_maker_orders.lock());
-                if req.cancel_previous {
-                    // remove the previous orders if there're some to allow multiple setprice call per pair
-                    // it's common use case now as `autoprice` doesn't work with new ordermatching and
-                    // MM2 users request the coins price from aggregators by their own scripts issuing
-                    // repetitive setprice calls with new price
-                    *my_orders = my_orders.drain().filter(|(_, order)| {
-                        let to_delete = order.base == req.base && order.rel == req.rel;
-                        if to_delete {
-                            delete_my_maker_order(&ctx, &order);
-                        }
-                        !to_delete
-                    }).collect();
-                }
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+    let mut my_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
+    if req.cancel_previous {
+        // remove the previous orders if there're some to allow multiple setprice call per pair
+        // it's common use case now as `autoprice` doesn't work with new ordermatching and
+        // MM2 users request the coins price from aggregators by their own scripts issuing
+        // repetitive setprice calls with new price
+        *my_orders = my_orders.drain().filter(|(_, order)| {
+            let to_delete = order.base == req.base && order.rel == req.rel;
+            if to_delete {
+                delete_my_maker_order(&ctx, &order);
+                publish_maker_order_cancelled(&ctx, &order, OrderCancellationReason::Replaced);
+            }
+            !to_delete
+        }).collect();
+    }
 
-                let uuid = new_uuid();
-                let order = MakerOrder {
-                    max_base_vol: volume.clone().into(),
-                    max_base_vol_rat: volume.into(),
-                    min_base_vol: 0.into(),
-                    min_base_vol_rat: BigRational::from_integer(0.into()),
-                    price: req.price.clone().into(),
-                    price_rat: req.price.clone().into(),
-                    created_at: now_ms(),
-                    base: req.base,
-                    rel: req.rel,
-                    matches: HashMap::new(),
-                    started_swaps: Vec::new(),
-                    uuid,
-                };
-                let response = json!({"result":order}).to_string();
-                save_my_maker_order(&ctx, &order);
-                my_orders.insert(uuid, order);
-                rpc_response(200, response)
-            })
-        })
-    )
+    let uuid = new_uuid();
+    let order = MakerOrder {
+        max_base_vol: volume.clone().into(),
+        max_base_vol_rat: volume.into(),
+        min_base_vol: req.min_volume.clone().into(),
+        min_base_vol_rat: req.min_volume.into(),
+        price: req.price.clone().into(),
+        price_rat: req.price.clone().into(),
+        created_at: now_ms(),
+        base: req.base,
+        rel: req.rel,
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid,
+        time_in_force: req.time_in_force,
+        allowed_pubkeys,
+    };
+    let response = json!({"result":order}).to_string();
+    save_my_maker_order(&ctx, &order);
+    publish_maker_order_event(&ctx, &order, "Created");
+    my_orders.insert(uuid, order);
+    Ok(try_s!(Response::builder().body(response.into_bytes())))
+}
+
+#[derive(Deserialize)]
+struct UpdateMakerOrderReq {
+    uuid: Uuid,
+    price: Option<MmNumber>,
+    /// The new volume available for matching.
+    /// The amount reserved by the ongoing matches and the started swaps is added on top of it.
+    volume: Option<MmNumber>,
+    /// Use the entire balance as the available volume.
+    #[serde(default)]
+    max: bool,
+    min_volume: Option<MmNumber>,
+}
+
+/// Changes the price and the volumes of my maker order in place, keeping its uuid and matches.
+pub async fn update_maker_order(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
+    let req: UpdateMakerOrderReq = try_tr!(InvalidRequest, json::from_value(req));
+    if req.max && req.volume.is_some() {
+        return RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The volume and max can't be used together")).into_response()
+    }
+    if let Some(ref price) = req.price {
+        if let Err(err) = check_min_price(price) {return err.into_response()}
+    }
+
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(&ctx));
+    let (base, rel, available) = match try_s!(ordermatch_ctx.my_maker_orders.lock()).get(&req.uuid) {
+        Some(order) => (order.base.clone(), order.rel.clone(), order.available_amount()),
+        None => return RpcError::new(RpcErrorCode::OrderNotFound, ERRL!("Order with uuid {} is not found", req.uuid)).into_response(),
+    };
+    let base_coin: MmCoinEnum = match try_s!(lp_coinfind(&ctx, &base).await) {
+        Some(coin) => coin,
+        None => return RpcError::new(RpcErrorCode::CoinNotEnabled, ERRL!("Base coin {} is not found", base)).into_response(),
+    };
+    let rel_coin: MmCoinEnum = match try_s!(lp_coinfind(&ctx, &rel).await) {
+        Some(coin) => coin,
+        None => return RpcError::new(RpcErrorCode::CoinNotEnabled, ERRL!("Rel coin {} is not found", rel)).into_response(),
+    };
+
+    let my_balance = try_tr!(TransportError, base_coin.my_balance().compat().await);
+    let volume = if req.max {
+        Some(MmNumber::from(my_balance.clone() - get_locked_amount(&ctx, base_coin.ticker())))
+    } else if let Some(volume) = req.volume {
+        if let Err(err) = check_locked_coins(&ctx, &volume, &my_balance, base_coin.ticker()) {return err.into_response()}
+        Some(volume)
+    } else {
+        None
+    };
+    // the balances might have changed since the order was created or updated last time,
+    // so the updated order is checked the same way the `setprice` checks the new one
+    if !req.max {
+        let volume = volume.clone().unwrap_or(available);
+        try_tr!(NotSufficientBalance, base_coin.check_i_have_enough_to_trade(&volume, &my_balance.clone().into(), TradeInfo::Maker).compat().await);
+    }
+    try_tr!(NotSufficientBalance, rel_coin.can_i_spend_other_payment().compat().await);
+
+    // the order might have been changed or removed while we were checking the balance
+    let mut my_orders = try_s!(ordermatch_ctx.my_maker_orders.lock());
+    let order = match my_orders.get_mut(&req.uuid) {
+        Some(order) => order,
+        None => return RpcError::new(RpcErrorCode::OrderNotFound, ERRL!("Order with uuid {} is not found", req.uuid)).into_response(),
+    };
+    let reserved = MmNumber::from(order.max_base_vol_rat.clone()) - order.available_amount();
+    let new_max_vol = match volume {
+        Some(volume) => volume + reserved,
+        None => order.max_base_vol_rat.clone().into(),
+    };
+    let new_min_vol = req.min_volume.unwrap_or_else(|| order.min_base_vol_rat.clone().into());
+    if new_min_vol < MmNumber::from(0) || new_min_vol > new_max_vol {
+        let err = RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The min_volume {} must be between 0 and the volume {}", new_min_vol, new_max_vol));
+        return err.into_response()
+    }
+
+    if let Some(price) = req.price {
+        order.price = price.clone().into();
+        order.price_rat = price.into();
+    }
+    order.max_base_vol = new_max_vol.clone().into();
+    order.max_base_vol_rat = new_max_vol.into();
+    order.min_base_vol = new_min_vol.clone().into();
+    order.min_base_vol_rat = new_min_vol.into();
+    save_my_maker_order(&ctx, order);
+    publish_maker_order_event(&ctx, order, "Updated");
+    let res = try_s!(json::to_vec(&json!({"result": MakerOrderForRpc::from(&*order)})));
+    broadcast_maker_order_now(&ctx, order.clone());
+    Ok(try_s!(Response::builder().body(res)))
+}
+
+/// Broadcasts the order right away rather than on the next `broadcast_my_maker_orders` tick.
+fn broadcast_maker_order_now(ctx: &MmArc, order: MakerOrder) {
+    let ctx = ctx.clone();
+    // `PricePingRequest::new` is blocking on the balance request.
+    let rc = thread::Builder::new().name("maker_order_ping".into()).spawn(move || {
+        let ping = match PricePingRequest::new(&ctx, &order) {
+            Ok(p) => p,
+            Err(e) => {
+                ctx.log.log("", &[&"broadcast_maker_order_now", &order.base, &order.rel], &format! ("ping request creation failed {}", e));
+                return;
+            },
+        };
+        if let Err(e) = lp_send_price_ping(&ping, &order.allowed_pubkeys, &ctx) {
+            ctx.log.log("", &[&"broadcast_maker_order_now", &order.base, &order.rel], &format! ("ping request send failed {}", e));
+        }
+    });
+    if let Err(e) = rc {log!("Error spawning the maker_order_ping thread: " (e))}
 }
 
 pub fn broadcast_my_maker_orders(ctx: &MmArc) -> Result<(), String> {
@@ -1064,7 +2074,7 @@ pub fn broadcast_my_maker_orders(ctx: &MmArc) -> Result<(), String> {
             },
         };
 
-        if let Err(e) = lp_send_price_ping(&ping, ctx) {
+        if let Err(e) = lp_send_price_ping(&ping, &order.allowed_pubkeys, ctx) {
             ctx.log.log("", &[&"broadcast_my_maker_orders", &order.base, &order.rel], &format! ("ping request send failed {}", e));
             continue;
         }
@@ -1084,7 +2094,7 @@ pub fn broadcast_my_maker_orders(ctx: &MmArc) -> Result<(), String> {
             },
         };
 
-        if let Err(e) = lp_send_price_ping(&ping, ctx) {
+        if let Err(e) = lp_send_price_ping(&ping, &order.allowed_pubkeys, ctx) {
             ctx.log.log("", &[&"broadcast_cancelled_orders", &order.base, &order.rel], &format! ("ping request send failed {}", e));
             continue;
         }
@@ -1102,19 +2112,37 @@ enum OrderMatchResult {
     NotMatched,
 }
 
-/// Attempts to match the Maker's order and Taker's request
+/// Returns the `min_volume` of the maker order if the taker request for the same pair is below it.
+fn below_min_volume(maker: &MakerOrder, taker: &TakerRequest) -> Option<MmNumber> {
+    let maker_min_vol: MmNumber = maker.min_base_vol_rat.clone().into();
+    let taker_volume = match taker.action {
+        TakerAction::Buy if maker.base == taker.base && maker.rel == taker.rel => taker.get_base_amount(),
+        TakerAction::Sell if maker.base == taker.rel && maker.rel == taker.base => taker.get_rel_amount(),
+        _ => return None,
+    };
+    if taker_volume < maker_min_vol {Some(maker_min_vol)} else {None}
+}
+
+/// Attempts to match the Maker's order and Taker's request.
+/// The request allowing the partial fills is matched for the available amount of the order if it isn't enough for the whole request.
 fn match_order_and_request(maker: &MakerOrder, taker: &TakerRequest) -> OrderMatchResult {
     let taker_base_amount: MmNumber = taker.get_base_amount();
     let taker_rel_amount: MmNumber = taker.get_rel_amount();
     let maker_price: MmNumber = maker.price_rat.clone().into();
     let maker_min_vol: MmNumber = maker.min_base_vol_rat.clone().into();
+    let available = maker.available_amount();
+    let partial_fill = taker.partial_fill && available > MmNumber::from(0) && available >= maker_min_vol;
 
     match taker.action {
         TakerAction::Buy => {
-            if maker.base == taker.base && maker.rel == taker.rel && taker_base_amount <= maker.available_amount() && taker_base_amount >= maker_min_vol {
+            if maker.base == taker.base && maker.rel == taker.rel && taker_base_amount >= maker_min_vol {
                 let taker_price = &taker_rel_amount / &taker_base_amount;
-                if taker_price >= maker_price {
+                if taker_price < maker_price {
+                    OrderMatchResult::NotMatched
+                } else if taker_base_amount <= available {
                     OrderMatchResult::Matched((taker_base_amount.clone(), taker_base_amount * maker_price))
+                } else if partial_fill {
+                    OrderMatchResult::Matched((available.clone(), available * maker_price))
                 } else {
                     OrderMatchResult::NotMatched
                 }
@@ -1123,10 +2151,14 @@ fn match_order_and_request(maker: &MakerOrder, taker: &TakerRequest) -> OrderMat
             }
         },
         TakerAction::Sell => {
-            if maker.base == taker.rel && maker.rel == taker.base && taker_rel_amount <= maker.available_amount() && taker_rel_amount >= maker_min_vol {
+            if maker.base == taker.rel && maker.rel == taker.base && taker_rel_amount >= maker_min_vol {
                 let taker_price = &taker_base_amount / &taker_rel_amount;
-                if taker_price >= maker_price {
+                if taker_price < maker_price {
+                    OrderMatchResult::NotMatched
+                } else if taker_rel_amount <= available {
                     OrderMatchResult::Matched((&taker_base_amount / &maker_price, taker_base_amount))
+                } else if partial_fill {
+                    OrderMatchResult::Matched((available.clone(), available * maker_price))
                 } else {
                     OrderMatchResult::NotMatched
                 }
@@ -1143,7 +2175,7 @@ struct OrderStatusReq {
 }
 
 pub fn order_status(ctx: MmArc, req: Json) -> HyRes {
-    let req: OrderStatusReq = try_h!(json::from_value(req));
+    let req: OrderStatusReq = try_th!(InvalidRequest, json::from_value(req));
 
     let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
     let maker_orders = try_h!(ordermatch_ctx.my_maker_orders.lock());
@@ -1162,7 +2194,7 @@ pub fn order_status(ctx: MmArc, req: Json) -> HyRes {
         }).to_string());
     }
 
-    rpc_err_response(404, &format!("Order with uuid {} is not found", req.uuid))
+    rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderNotFound, ERRL!("Order with uuid {} is not found", req.uuid)))
 }
 
 #[derive(Deserialize)]
@@ -1171,18 +2203,19 @@ struct CancelOrderReq {
 }
 
 pub fn cancel_order(ctx: MmArc, req: Json) -> HyRes {
-    let req: CancelOrderReq = try_h!(json::from_value(req));
+    let req: CancelOrderReq = try_th!(InvalidRequest, json::from_value(req));
 
     let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
     let mut maker_orders = try_h!(ordermatch_ctx.my_maker_orders.lock());
     match maker_orders.entry(req.uuid) {
         Entry::Occupied(order) => {
             if !order.get().is_cancellable() {
-                return rpc_err_response(500, &format!("Order {} is being matched now, can't cancel", req.uuid));
+                return rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderIsMatching, ERRL!("Order {} is being matched now, can't cancel", req.uuid)));
             }
             let mut cancelled_orders = try_h!(ordermatch_ctx.my_cancelled_orders.lock());
             let order = order.remove();
             delete_my_maker_order(&ctx, &order);
+            publish_maker_order_cancelled(&ctx, &order, OrderCancellationReason::Cancelled);
             cancelled_orders.insert(req.uuid, order);
             return rpc_response(200, json!({
                 "result": "success"
@@ -1196,10 +2229,11 @@ pub fn cancel_order(ctx: MmArc, req: Json) -> HyRes {
     match taker_orders.entry(req.uuid) {
         Entry::Occupied(order) => {
             if !order.get().is_cancellable() {
-                return rpc_err_response(500, &format!("Order {} is being matched now, can't cancel", req.uuid));
+                return rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderIsMatching, ERRL!("Order {} is being matched now, can't cancel", req.uuid)));
             }
             let order = order.remove();
             delete_my_taker_order(&ctx, &order);
+            publish_taker_order_cancelled(&ctx, &order, OrderCancellationReason::Cancelled);
             return rpc_response(200, json!({
                 "result": "success"
             }).to_string())
@@ -1208,7 +2242,7 @@ pub fn cancel_order(ctx: MmArc, req: Json) -> HyRes {
         Entry::Vacant(_) => (),
     }
 
-    rpc_err_response(404, &format!("Order with uuid {} is not found", req.uuid))
+    rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderNotFound, ERRL!("Order with uuid {} is not found", req.uuid)))
 }
 
 #[derive(Serialize)]
@@ -1233,18 +2267,76 @@ impl<'a> From<&'a MakerOrder> for MakerOrderForRpc<'a> {
 struct TakerOrderForRpc<'a> {
     #[serde(flatten)]
     order: &'a TakerOrder,
-    cancellable: bool
+    cancellable: bool,
+    /// The amount of the base coin the swaps were started for.
+    filled_amount: BigDecimal,
+    filled_amount_rat: BigRational,
+    /// The amount of the base coin still to be filled.
+    remaining_amount: BigDecimal,
+    remaining_amount_rat: BigRational,
 }
 
 impl<'a> From<&'a TakerOrder> for TakerOrderForRpc<'a> {
     fn from(order: &'a TakerOrder) -> TakerOrderForRpc {
+        let filled = order.filled_base_amount();
+        let remaining = order.request.get_base_amount() - filled.clone();
         TakerOrderForRpc {
             order,
             cancellable: order.is_cancellable(),
+            filled_amount: filled.clone().into(),
+            filled_amount_rat: filled.into(),
+            remaining_amount: remaining.clone().into(),
+            remaining_amount_rat: remaining.into(),
         }
     }
 }
 
+/// Pushes the maker order event to the `event_stream` subscribers, recording the order history.
+fn publish_maker_order_event(ctx: &MmArc, order: &MakerOrder, event: &str) {
+    save_maker_order_history(ctx, order, None);
+    ctx.event_stream.publish(EventTopic::Order, &order.uuid.to_string(), event, json!({
+        "type": "Maker",
+        "order": MakerOrderForRpc::from(order),
+    }));
+}
+
+/// Pushes the taker order event to the `event_stream` subscribers, recording the order history.
+fn publish_taker_order_event(ctx: &MmArc, order: &TakerOrder, event: &str) {
+    save_taker_order_history(ctx, order, None);
+    ctx.event_stream.publish(EventTopic::Order, &order.request.uuid.to_string(), event, json!({
+        "type": "Taker",
+        "order": TakerOrderForRpc::from(order),
+    }));
+}
+
+/// Pushes the "Cancelled" event with the cancellation `reason`, logging the cancellations not requested by the user.
+fn publish_maker_order_cancelled(ctx: &MmArc, order: &MakerOrder, reason: OrderCancellationReason) {
+    match reason {
+        OrderCancellationReason::Cancelled | OrderCancellationReason::Replaced => (),
+        _ => log!("Maker order " (order.uuid) " is cancelled: " [reason]),
+    }
+    save_maker_order_history(ctx, order, Some(reason));
+    ctx.event_stream.publish(EventTopic::Order, &order.uuid.to_string(), "Cancelled", json!({
+        "type": "Maker",
+        "order": MakerOrderForRpc::from(order),
+        "reason": reason,
+    }));
+}
+
+/// Pushes the "Cancelled" event with the cancellation `reason`, logging the cancellations not requested by the user.
+fn publish_taker_order_cancelled(ctx: &MmArc, order: &TakerOrder, reason: OrderCancellationReason) {
+    match reason {
+        OrderCancellationReason::Cancelled | OrderCancellationReason::Replaced => (),
+        _ => log!("Taker order " (order.request.uuid) " is cancelled: " [reason]),
+    }
+    save_taker_order_history(ctx, order, Some(reason));
+    ctx.event_stream.publish(EventTopic::Order, &order.request.uuid.to_string(), "Cancelled", json!({
+        "type": "Taker",
+        "order": TakerOrderForRpc::from(order),
+        "reason": reason,
+    }));
+}
+
 pub fn my_orders(ctx: MmArc) -> HyRes {
     let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
     let maker_orders = try_h!(ordermatch_ctx.my_maker_orders.lock());
@@ -1259,6 +2351,32 @@ pub fn my_orders(ctx: MmArc) -> HyRes {
     }).to_string())
 }
 
+/// The ordermatch state sampled by the "/metrics" endpoint.
+pub struct OrdermatchMetrics {
+    /// The number of my maker orders by (base, rel).
+    pub maker_orders: HashMap<(String, String), usize>,
+    /// The number of my taker orders by (base, rel).
+    pub taker_orders: HashMap<(String, String), usize>,
+    /// The number of the orderbook entries by (base, rel).
+    pub orderbook: HashMap<(String, String), usize>,
+}
+
+pub fn ordermatch_metrics(ctx: &MmArc) -> Result<OrdermatchMetrics, String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut maker_orders = HashMap::new();
+    for order in try_s!(ordermatch_ctx.my_maker_orders.lock()).values() {
+        *maker_orders.entry((order.base.clone(), order.rel.clone())).or_insert(0) += 1;
+    }
+    let mut taker_orders = HashMap::new();
+    for order in try_s!(ordermatch_ctx.my_taker_orders.lock()).values() {
+        *taker_orders.entry((order.request.base.clone(), order.request.rel.clone())).or_insert(0) += 1;
+    }
+    let orderbook = try_s!(ordermatch_ctx.orderbook.lock()).iter()
+        .map(|(pair, orders)| (pair.clone(), orders.len()))
+        .collect();
+    Ok(OrdermatchMetrics {maker_orders, taker_orders, orderbook})
+}
+
 pub fn my_maker_orders_dir(ctx: &MmArc) -> PathBuf {
     ctx.dbdir().join("ORDERS").join("MY").join("MAKER")
 }
@@ -1327,6 +2445,7 @@ pub fn orders_kick_start(ctx: &MmArc) -> Result<HashSet<String>, String> {
             Err(_) => (),
         }
     });
+    coins.extend(try_s!(load_my_conditional_orders(ctx)));
     Ok(coins)
 }
 
@@ -1355,6 +2474,7 @@ pub fn cancel_orders_by(ctx: &MmArc, cancel_by: CancelBy) -> Result<(Vec<Uuid>,
             if $e {
                 if $order.is_cancellable() {
                     delete_my_maker_order(&ctx, &$order);
+                    publish_maker_order_cancelled(&ctx, &$order, OrderCancellationReason::Cancelled);
                     my_cancelled_orders.insert($uuid, $order);
                     cancelled.push($uuid);
                     None
@@ -1373,6 +2493,7 @@ pub fn cancel_orders_by(ctx: &MmArc, cancel_by: CancelBy) -> Result<(Vec<Uuid>,
             if $e {
                 if $order.is_cancellable() {
                     delete_my_taker_order(&ctx, &$order);
+                    publish_taker_order_cancelled(&ctx, &$order, OrderCancellationReason::Cancelled);
                     cancelled.push($uuid);
                     None
                 } else {
@@ -1416,7 +2537,7 @@ pub fn cancel_orders_by(ctx: &MmArc, cancel_by: CancelBy) -> Result<(Vec<Uuid>,
 }
 
 pub fn cancel_all_orders(ctx: MmArc, req: Json) -> HyRes {
-    let cancel_by: CancelBy = try_h!(json::from_value(req["cancel_by"].clone()));
+    let cancel_by: CancelBy = try_th!(InvalidRequest, json::from_value(req["cancel_by"].clone()));
 
     let (cancelled, currently_matching) = try_h!(cancel_orders_by(&ctx, cancel_by));
 
@@ -1428,29 +2549,107 @@ pub fn cancel_all_orders(ctx: MmArc, req: Json) -> HyRes {
     }).to_string())
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct OrderbookEntry {
     coin: String,
     address: String,
     price: BigDecimal,
     price_rat: BigRational,
+    /// The volume in the `coin`, which is the rel coin of the orderbook for the bids.
     #[serde(rename="maxvolume")]
     max_volume: f64,
     max_volume_rat: BigRational,
+    /// The `max_volume` in the base coin of the orderbook.
+    base_max_volume: BigDecimal,
+    base_max_volume_rat: BigRational,
+    /// The takers requesting less are not matched with the order.
+    min_volume: BigDecimal,
+    min_volume_rat: BigRational,
+    /// The cumulative volume of the orders up to and including this one, in the base coin of the orderbook.
+    depth: BigDecimal,
+    depth_rat: BigRational,
     pubkey: String,
     age: i64,
-    zcredits: u64,
+}
+
+/// The orders aggregated by price, the volumes are in the base coin of the orderbook.
+#[derive(Debug, Serialize)]
+pub struct PriceLevel {
+    price: BigDecimal,
+    price_rat: BigRational,
+    volume: BigDecimal,
+    volume_rat: BigRational,
+    /// The cumulative volume of the price levels up to and including this one.
+    depth: BigDecimal,
+    depth_rat: BigRational,
+    /// The number of the orders at this level.
+    orders: usize,
+}
+
+/// Sorts the `entries` by the price (ascending for the asks, descending for the bids),
+/// groups them into the price levels, keeps the first `depth` levels and computes the cumulative volumes of the base coin.
+///
+/// * `price_step` - Aggregates the orders into the levels of the given price step,
+///                  rounding the ask prices up and the bid prices down. The orders are grouped by the exact price otherwise.
+fn orderbook_side(mut entries: Vec<OrderbookEntry>, is_asks: bool, price_step: Option<&BigRational>, depth: Option<usize>)
+                  -> (Vec<OrderbookEntry>, Vec<PriceLevel>) {
+    entries.sort_by(|a, b| {
+        let by_price = if is_asks {a.price_rat.cmp(&b.price_rat)} else {b.price_rat.cmp(&a.price_rat)};
+        by_price.then_with(|| a.pubkey.cmp(&b.pubkey))
+    });
+    let level_price = |price: &BigRational| match price_step {
+        Some(step) if is_asks => (price / step).ceil() * step,
+        Some(step) => (price / step).floor() * step,
+        None => price.clone(),
+    };
+
+    let mut levels: Vec<PriceLevel> = Vec::new();
+    let mut orders = Vec::with_capacity(entries.len());
+    let mut depth_rat = BigRational::zero();
+    for mut entry in entries {
+        let price = level_price(&entry.price_rat);
+        let is_new_level = levels.last().map(|level| level.price_rat != price).unwrap_or(true);
+        if is_new_level {
+            if Some(levels.len()) == depth {break}
+            levels.push(PriceLevel {
+                price: from_ratio_to_dec(&price),
+                price_rat: price,
+                volume: 0.into(),
+                volume_rat: BigRational::zero(),
+                depth: 0.into(),
+                depth_rat: BigRational::zero(),
+                orders: 0,
+            });
+        }
+        depth_rat = depth_rat + &entry.base_max_volume_rat;
+        entry.depth = from_ratio_to_dec(&depth_rat);
+        entry.depth_rat = depth_rat.clone();
+        let level = unwrap!(levels.last_mut());
+        level.volume_rat = &level.volume_rat + &entry.base_max_volume_rat;
+        level.volume = from_ratio_to_dec(&level.volume_rat);
+        level.depth = entry.depth.clone();
+        level.depth_rat = depth_rat.clone();
+        level.orders += 1;
+        orders.push(entry);
+    }
+    (orders, levels)
 }
 
 #[derive(Serialize)]
 pub struct OrderbookResponse {
+    /// The total base coin volume of the returned asks, same as the `depth` of the last ask.
+    /// A fractional number now, it used to be an integer which was always 0.
     #[serde(rename="askdepth")]
-    ask_depth: u32,
+    ask_depth: f64,
     asks: Vec<OrderbookEntry>,
+    ask_levels: Vec<PriceLevel>,
     base: String,
+    /// The total base coin volume of the returned bids, same as the `depth` of the last bid.
+    /// A fractional number now, it used to be an integer which was always 0.
     #[serde(rename="biddepth")]
-    bid_depth: u32,
+    bid_depth: f64,
     bids: Vec<OrderbookEntry>,
+    bid_levels: Vec<PriceLevel>,
     netid: u16,
     #[serde(rename="numasks")]
     num_asks: usize,
@@ -1464,39 +2663,52 @@ pub struct OrderbookResponse {
 struct OrderbookReq {
     base: String,
     rel: String,
+    /// The maximal number of the price levels returned on each side.
+    depth: Option<usize>,
+    /// Aggregates the orders into the price levels of this step.
+    price_step: Option<MmNumber>,
 }
 
 pub fn orderbook(ctx: MmArc, req: Json) -> HyRes {
-    let req: OrderbookReq = try_h!(json::from_value(req));
+    let req: OrderbookReq = try_th!(InvalidRequest, json::from_value(req));
     if req.base == req.rel {
-        return rpc_err_response(500, "Base and rel must be different coins");
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")));
+    }
+    if req.depth == Some(0) {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The depth must be positive")));
+    }
+    let price_step: Option<BigRational> = req.price_step.clone().map(|step| step.into());
+    if let Some(ref step) = price_step {
+        if *step <= BigRational::zero() {
+            return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The price_step must be positive")));
+        }
     }
     let rel_coin = try_h!(block_on(lp_coinfind(&ctx, &req.rel)));
-    let rel_coin = match rel_coin {
-        Some(c) => c,
-        None => return rpc_err_response(500, "Rel coin is not found or inactive")
-    };
+    let rel_coin = try_th!(CoinNotEnabled, rel_coin.ok_or("Rel coin is not found or inactive"));
     let base_coin = try_h!(block_on(lp_coinfind(&ctx, &req.base)));
-    let base_coin: MmCoinEnum = match base_coin {
-        Some(c) => c,
-        None => return rpc_err_response(500, "Base coin is not found or inactive")
-    };
+    let base_coin: MmCoinEnum = try_th!(CoinNotEnabled, base_coin.ok_or("Base coin is not found or inactive"));
     let ordermatch_ctx: Arc<OrdermatchContext> = try_h!(OrdermatchContext::from_ctx(&ctx));
     let orderbook = try_h!(ordermatch_ctx.orderbook.lock());
     let asks = match orderbook.get(&(req.base.clone(), req.rel.clone())) {
         Some(asks) => {
             let mut orderbook_entries = vec![];
             for (_, ask) in asks.iter() {
+                let max_volume_rat = ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone()));
                 orderbook_entries.push(OrderbookEntry {
                     coin: req.base.clone(),
                     address: try_h!(base_coin.address_from_pubkey_str(&ask.pubsecp)),
                     price: ask.price.clone(),
                     price_rat: ask.price_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.price.clone())),
                     max_volume: unwrap!(ask.balance.to_f64()),
-                    max_volume_rat: ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone())),
+                    base_max_volume: from_ratio_to_dec(&max_volume_rat),
+                    base_max_volume_rat: max_volume_rat.clone(),
+                    max_volume_rat,
+                    min_volume: from_ratio_to_dec(&ask.get_min_volume_rat()),
+                    min_volume_rat: ask.get_min_volume_rat(),
+                    depth: 0.into(),
+                    depth_rat: BigRational::zero(),
                     pubkey: ask.pubkey.clone(),
                     age: (now_ms() as i64 / 1000) - ask.timestamp as i64,
-                    zcredits: 0,
                 })
             }
             orderbook_entries
@@ -1507,32 +2719,46 @@ pub fn orderbook(ctx: MmArc, req: Json) -> HyRes {
         Some(asks) => {
             let mut orderbook_entries = vec![];
             for (_, ask) in asks.iter() {
+                let ask_price_rat = ask.price_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.price.clone()));
+                let max_volume_rat = ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone()));
+                // the bid is an ask of the reversed pair, its balance is in the rel coin and its price is in the base coin
+                let base_max_volume_rat = &max_volume_rat * &ask_price_rat;
                 orderbook_entries.push(OrderbookEntry {
                     coin: req.rel.clone(),
                     address: try_h!(rel_coin.address_from_pubkey_str(&ask.pubsecp)),
                     // NB: 1/x can not be represented as a decimal and introduces a rounding error
                     // cf. https://github.com/KomodoPlatform/atomicDEX-API/issues/495#issuecomment-516365682
                     price: BigDecimal::from (1) / &ask.price,
-                    price_rat: BigRational::from_integer(1.into()) / ask.price_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.price.clone())),
+                    price_rat: BigRational::from_integer(1.into()) / ask_price_rat,
                     max_volume: unwrap!(ask.balance.to_f64()),
-                    max_volume_rat: ask.balance_rat.as_ref().map(|p| p.clone()).unwrap_or(from_dec_to_ratio(ask.balance.clone())),
+                    max_volume_rat,
+                    base_max_volume: from_ratio_to_dec(&base_max_volume_rat),
+                    base_max_volume_rat,
+                    min_volume: from_ratio_to_dec(&ask.get_min_volume_rat()),
+                    min_volume_rat: ask.get_min_volume_rat(),
+                    depth: 0.into(),
+                    depth_rat: BigRational::zero(),
                     pubkey: ask.pubkey.clone(),
                     age: (now_ms() as i64 / 1000) - ask.timestamp as i64,
-                    zcredits: 0,
                 })
             }
             orderbook_entries
         },
         None => vec![],
     };
+    drop(orderbook);
+    let (asks, ask_levels) = orderbook_side(asks, true, price_step.as_ref(), req.depth);
+    let (bids, bid_levels) = orderbook_side(bids, false, price_step.as_ref(), req.depth);
     let response = OrderbookResponse {
         num_asks: asks.len(),
         num_bids: bids.len(),
-        ask_depth: 0,
+        ask_depth: ask_levels.last().and_then(|level| level.depth.to_f64()).unwrap_or(0.),
         asks,
+        ask_levels,
         base: req.base,
-        bid_depth: 0,
+        bid_depth: bid_levels.last().and_then(|level| level.depth.to_f64()).unwrap_or(0.),
         bids,
+        bid_levels,
         netid: ctx.netid(),
         rel: req.rel,
         timestamp: now_ms() / 1000,
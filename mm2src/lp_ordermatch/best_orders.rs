@@ -0,0 +1,112 @@
+//! The best orders of the orderbook to trade a coin for any other coin, in a single call.
+//!
+//!     {"method": "best_orders", "coin": "KMD", "action": "buy", "volume": "10"}
+//!
+//! The orders are grouped by the counterpart coin, the prices and the volumes are given from the taker side:
+//! the price is in the counterpart coin per the `coin` and the volumes are in the `coin`.
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BestOrdersAction {
+    /// Buy the `coin` from the makers selling it.
+    Buy,
+    /// Sell the `coin` to the makers buying it.
+    Sell,
+}
+
+#[derive(Deserialize)]
+struct BestOrdersReq {
+    coin: String,
+    action: BestOrdersAction,
+    /// The volume of the `coin` to trade, the orders not able to match it are skipped.
+    volume: MmNumber,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BestOrder {
+    pub uuid: Uuid,
+    pub pubkey: String,
+    /// The counterpart coin.
+    pub coin: String,
+    /// In the counterpart coin per the requested coin.
+    pub price: BigDecimal,
+    pub price_rat: BigRational,
+    /// In the requested coin.
+    pub max_volume: BigDecimal,
+    pub max_volume_rat: BigRational,
+    /// In the requested coin.
+    pub min_volume: BigDecimal,
+    pub min_volume_rat: BigRational,
+    /// The seconds since the last price ping of the order.
+    pub age: i64,
+}
+
+/// The orders of the other nodes able to match the `volume` of the `coin`, by the counterpart coin, the best price first.
+pub fn find_best_orders(
+    orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>,
+    my_pubkey: &str,
+    coin: &str,
+    action: BestOrdersAction,
+    volume: &MmNumber,
+) -> HashMap<String, Vec<BestOrder>> {
+    let mut best: HashMap<String, Vec<BestOrder>> = HashMap::new();
+    for ((base, rel), orders) in orderbook.iter() {
+        // the maker orders always sell the `base` for the `rel`
+        let counterpart = match action {
+            BestOrdersAction::Buy if base == coin => rel,
+            BestOrdersAction::Sell if rel == coin => base,
+            _ => continue,
+        };
+        for (uuid, order) in orders.iter() {
+            if order.pubkey == my_pubkey || order.balance <= 0.into() {continue}
+            let maker_price = order.get_price_rat();
+            if maker_price <= BigRational::zero() {continue}
+            let (price, max_volume, min_volume) = match action {
+                BestOrdersAction::Buy => (maker_price, order.get_balance_rat(), order.get_min_volume_rat()),
+                BestOrdersAction::Sell => (
+                    BigRational::from_integer(1.into()) / &maker_price,
+                    order.get_balance_rat() * &maker_price,
+                    order.get_min_volume_rat() * &maker_price,
+                ),
+            };
+            if MmNumber::from(max_volume.clone()) < *volume || MmNumber::from(min_volume.clone()) > *volume {continue}
+            best.entry(counterpart.clone()).or_insert_with(Vec::new).push(BestOrder {
+                uuid: *uuid,
+                pubkey: order.pubkey.clone(),
+                coin: counterpart.clone(),
+                price: from_ratio_to_dec(&price),
+                price_rat: price,
+                max_volume: from_ratio_to_dec(&max_volume),
+                max_volume_rat: max_volume,
+                min_volume: from_ratio_to_dec(&min_volume),
+                min_volume_rat: min_volume,
+                age: (now_ms() / 1000) as i64 - order.timestamp as i64,
+            });
+        }
+    }
+    for orders in best.values_mut() {
+        // the lowest price to buy the coin for, the highest price to sell it for
+        orders.sort_by(|a, b| match action {
+            BestOrdersAction::Buy => a.price_rat.cmp(&b.price_rat),
+            BestOrdersAction::Sell => b.price_rat.cmp(&a.price_rat),
+        }.then_with(|| a.uuid.cmp(&b.uuid)));
+    }
+    best
+}
+
+pub fn best_orders(ctx: MmArc, req: Json) -> HyRes {
+    let req: BestOrdersReq = try_th!(InvalidRequest, json::from_value(req));
+    if req.volume <= MmNumber::from(0) {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The volume must be positive")));
+    }
+    let my_pubkey = hex::encode(&try_h!(ctx.public_id()).bytes);
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let orderbook = try_h!(ordermatch_ctx.orderbook.lock());
+    let best = find_best_orders(&orderbook, &my_pubkey, &req.coin, req.action, &req.volume);
+    drop(orderbook);
+    rpc_response(200, json!({
+        "result": best
+    }).to_string())
+}
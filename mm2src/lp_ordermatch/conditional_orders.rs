@@ -0,0 +1,409 @@
+//! The stop-loss and take-profit orders: the "buy" or "sell" request sent when the price crosses the trigger price.
+//!
+//!     {"method": "conditional_order", "trigger": {"type": "StopLoss", "price": "0.0001"},
+//!      "order": {"method": "sell", "base": "KMD", "rel": "BTC", "volume": "10", "order_type": {"type": "Market", "max_slippage": "1"}}}
+//!
+//! The orders are evaluated by the `lp_ordermatch_loop` against the `price_source`:
+//! - "orderbook": the price of the other nodes' orders in the local orderbook,
+//!   the bids when selling the `base` and the asks when buying it, walked from the best price
+//!   until their `base` volume reaches the `min_depth` of the trigger (the order volume by default),
+//!   so that a dust order at an extreme price doesn't trigger the order;
+//! - "external": the price from CoinGecko or CoinMarketCap, cf. the `external_prices` module,
+//!   fetched every `EXTERNAL_PRICES_INTERVAL` seconds for the coins of the pending orders.
+//! The triggered request is sent in the background, not to stall the `lp_ordermatch_loop`.
+//!
+//! A failed request is retried on the next triggers, up to `MAX_TRIGGER_ATTEMPTS` times,
+//! and every trigger is appended to the history of the order.
+//! The orders are kept in the "ORDERS/MY/CONDITIONAL" directory after they are triggered or cancelled,
+//! the "my_conditional_orders" RPC lists them with their trigger history.
+
+use super::*;
+use super::external_prices::{fetch_external_prices, PricingProvider};
+
+/// The request of the triggered order is sent at most this many times.
+const MAX_TRIGGER_ATTEMPTS: usize = 3;
+/// The order isn't triggered again for this long after its request has failed, in milliseconds.
+const TRIGGER_RETRY_INTERVAL_MS: u64 = 60000;
+/// The external prices are fetched this often, in seconds.
+const EXTERNAL_PRICES_INTERVAL: f64 = 60.;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum TriggerType {
+    /// Sells when the price falls to the trigger price, buys when it rises to it.
+    StopLoss,
+    /// Sells when the price rises to the trigger price, buys when it falls to it.
+    TakeProfit,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Trigger {
+    #[serde(rename = "type")]
+    pub trigger_type: TriggerType,
+    /// In the `rel` coin per the `base` coin.
+    pub price: MmNumber,
+    /// The `base` coin volume of the orders needed at the trigger price or better, the "orderbook" source only.
+    #[serde(default)]
+    pub min_depth: Option<MmNumber>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    /// The price of the other nodes' orders in the local orderbook at the `min_depth` of the trigger.
+    Orderbook,
+    /// The price from the external pricing provider, cf. the `external_prices` module.
+    External,
+}
+
+impl Default for PriceSource {
+    fn default() -> PriceSource { PriceSource::Orderbook }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ConditionalOrderStatus {
+    /// Waiting for the trigger price.
+    Pending,
+    /// The trigger price is reached and the "buy" or "sell" request is being sent.
+    Sending,
+    /// The taker order is created.
+    Triggered,
+    /// The "buy" or "sell" request has failed `MAX_TRIGGER_ATTEMPTS` times, cf. the `error` of the trigger records.
+    Failed,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TriggerRecord {
+    /// UNIX timestamp in milliseconds.
+    pub timestamp: u64,
+    /// The price which has triggered the order: the orderbook price at the `min_depth` or the external price.
+    pub price: BigDecimal,
+    /// The uuid of the created taker order.
+    pub taker_uuid: Option<Uuid>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConditionalOrder {
+    pub uuid: Uuid,
+    /// "buy" or "sell".
+    pub method: String,
+    pub base: String,
+    pub rel: String,
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub price_source: PriceSource,
+    /// The parameters of the "buy" or "sell" request sent when the order is triggered.
+    pub order: Json,
+    pub status: ConditionalOrderStatus,
+    /// The triggers of the order, the oldest first.
+    #[serde(default)]
+    pub history: Vec<TriggerRecord>,
+    /// UNIX timestamp in milliseconds.
+    pub created_at: u64,
+    /// UNIX timestamp in milliseconds.
+    pub updated_at: u64,
+}
+
+impl ConditionalOrder {
+    fn is_buy(&self) -> bool { self.method == "buy" }
+
+    /// Whether the order is waiting for the trigger price, and isn't retried too soon after a failed request.
+    pub fn is_pending(&self, now: u64) -> bool {
+        self.status == ConditionalOrderStatus::Pending &&
+            self.history.last().map_or(true, |record| record.timestamp + TRIGGER_RETRY_INTERVAL_MS <= now)
+    }
+
+    /// Whether the `price` triggers the order.
+    pub fn is_triggered(&self, price: &BigRational) -> bool {
+        let trigger_price: BigRational = self.trigger.price.clone().into();
+        match (self.is_buy(), self.trigger.trigger_type) {
+            (false, TriggerType::StopLoss) | (true, TriggerType::TakeProfit) => *price <= trigger_price,
+            (false, TriggerType::TakeProfit) | (true, TriggerType::StopLoss) => *price >= trigger_price,
+        }
+    }
+}
+
+/// The price of the orders of the other nodes in the `orderbook` at the `depth`, in the `rel` coin per the `base` coin:
+/// the price of the worst order needed to trade the `depth` volume of the `base`,
+/// walking the asks from the lowest price when buying the `base` and the bids from the highest price when selling it.
+/// `None` if the orders don't have that much volume.
+pub fn orderbook_depth_price(
+    orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>,
+    my_pubkey: &str,
+    base: &str,
+    rel: &str,
+    is_buy: bool,
+    depth: &BigRational,
+) -> Option<BigRational> {
+    // the bids sell the `rel` for the `base` with the price in the `base` coin per the `rel` coin
+    let pair = if is_buy {(base.to_owned(), rel.to_owned())} else {(rel.to_owned(), base.to_owned())};
+    let mut orders: Vec<(BigRational, BigRational)> = orderbook.get(&pair)?.values()
+        .filter(|order| order.pubkey != my_pubkey && order.balance > 0.into())
+        .map(|order| (order.get_price_rat(), order.get_balance_rat()))
+        .filter(|(price, _)| *price > BigRational::zero())
+        .map(|(price, balance)| if is_buy {(price, balance)} else {(price.recip(), balance * price)})
+        .collect();
+    if is_buy {
+        orders.sort_by(|(a, _), (b, _)| a.cmp(b));
+    } else {
+        orders.sort_by(|(a, _), (b, _)| b.cmp(a));
+    }
+    let mut volume = BigRational::zero();
+    for (price, order_volume) in orders {
+        volume = volume + order_volume;
+        if volume >= *depth {return Some(price)}
+    }
+    None
+}
+
+fn my_conditional_orders_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("ORDERS").join("MY").join("CONDITIONAL")
+}
+
+fn my_conditional_order_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
+    my_conditional_orders_dir(ctx).join(format!("{}.json", uuid))
+}
+
+fn save_my_conditional_order(ctx: &MmArc, order: &ConditionalOrder) -> Result<(), String> {
+    let content = try_s!(json::to_vec(order));
+    try_s!(write(&my_conditional_order_file_path(ctx, &order.uuid), &content));
+    Ok(())
+}
+
+/// Loads my conditional orders, returning the coins of the pending ones.
+pub fn load_my_conditional_orders(ctx: &MmArc) -> Result<HashSet<String>, String> {
+    let mut coins = HashSet::new();
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    for entry in try_s!(json_dir_entries(&my_conditional_orders_dir(ctx))) {
+        match json::from_slice::<ConditionalOrder>(&slurp(&entry.path())) {
+            Ok(mut order) => {
+                if order.status == ConditionalOrderStatus::Sending {
+                    // the node has been stopped before the request result is recorded
+                    order.status = ConditionalOrderStatus::Pending;
+                    order.history.push(TriggerRecord {timestamp: now_ms(), price: 0.into(), taker_uuid: None,
+                        error: Some("The node has been stopped while sending the request".into())});
+                    try_s!(save_my_conditional_order(ctx, &order));
+                }
+                if order.status == ConditionalOrderStatus::Pending {
+                    coins.insert(order.base.clone());
+                    coins.insert(order.rel.clone());
+                }
+                orders.insert(order.uuid, order);
+            },
+            Err(e) => log!("Error " (e) " parsing JSON from " (entry.path().display())),
+        }
+    }
+    Ok(coins)
+}
+
+/// Sends the "buy" or "sell" request of the triggered order, returning the uuid of the created taker order.
+async fn send_triggered_order(ctx: MmArc, order: ConditionalOrder) -> Result<Uuid, String> {
+    let response = if order.is_buy() {try_s!(buy(ctx, order.order).await)} else {try_s!(sell(ctx, order.order).await)};
+    let body: Json = try_s!(json::from_slice(response.body()));
+    if !response.status().is_success() {
+        return ERR!("{}", body["error"].as_str().unwrap_or("unknown error"));
+    }
+    Ok(try_s!(json::from_value(body["result"]["uuid"].clone())))
+}
+
+/// Records the `result` of the request sent when the `uuid` order has been triggered by the `price`.
+fn record_trigger(ctx: &MmArc, uuid: Uuid, price: BigRational, result: Result<Uuid, String>) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let mut orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    let order = try_s!(orders.get_mut(&uuid).ok_or("The order is not found"));
+    let (status, taker_uuid, error) = match result {
+        Ok(taker_uuid) => (ConditionalOrderStatus::Triggered, Some(taker_uuid), None),
+        Err(err) => {
+            log!("Conditional order " (uuid) " request has failed: " (err));
+            let status = if order.history.len() + 1 >= MAX_TRIGGER_ATTEMPTS {
+                ConditionalOrderStatus::Failed
+            } else {
+                ConditionalOrderStatus::Pending
+            };
+            (status, None, Some(err))
+        },
+    };
+    order.status = status;
+    order.history.push(TriggerRecord {timestamp: now_ms(), price: from_ratio_to_dec(&price), taker_uuid, error});
+    order.updated_at = now_ms();
+    try_s!(save_my_conditional_order(ctx, order));
+    Ok(())
+}
+
+/// Sends the requests of my pending conditional orders triggered by the current orderbook prices.
+pub fn process_conditional_orders(ctx: &MmArc) -> Result<(), String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let my_pubkey = hex::encode(&try_s!(ctx.public_id()).bytes);
+    let now = now_ms();
+    let mut triggered = Vec::new();
+    {
+        let mut orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+        let orderbook = try_s!(ordermatch_ctx.orderbook.lock());
+        let external_prices = try_s!(ordermatch_ctx.external_prices.lock());
+        for order in orders.values_mut().filter(|order| order.is_pending(now)) {
+            let price = match order.price_source {
+                PriceSource::Orderbook => {
+                    let depth: BigRational = order.trigger.min_depth.clone().unwrap_or_else(|| MmNumber::from(0)).into();
+                    orderbook_depth_price(&orderbook, &my_pubkey, &order.base, &order.rel, order.is_buy(), &depth)
+                },
+                PriceSource::External => external_prices.price(&order.base, &order.rel, now),
+            };
+            let price = match price {
+                Some(price) => price,
+                None => continue,
+            };
+            if !order.is_triggered(&price) {continue}
+            log!("Conditional order " (order.uuid) " is triggered by the " (order.base) "/" (order.rel) " price " (from_ratio_to_dec(&price)));
+            order.status = ConditionalOrderStatus::Sending;
+            order.updated_at = now;
+            try_s!(save_my_conditional_order(ctx, order));
+            triggered.push((order.clone(), price));
+        }
+    }
+
+    for (order, price) in triggered {
+        let ctx = ctx.clone();
+        spawn(async move {
+            let uuid = order.uuid;
+            let result = send_triggered_order(ctx.clone(), order).await;
+            if let Err(err) = record_trigger(&ctx, uuid, price, result) {
+                log!("Error recording the trigger of the conditional order " (uuid) ": " (err))
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The coins of my pending conditional orders with the "external" price source.
+fn external_price_coins(ctx: &MmArc) -> Result<Vec<String>, String> {
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let orders = try_s!(ordermatch_ctx.my_conditional_orders.lock());
+    let mut coins = HashSet::new();
+    for order in orders.values() {
+        if order.status != ConditionalOrderStatus::Pending || order.price_source != PriceSource::External {continue}
+        coins.insert(order.base.clone());
+        coins.insert(order.rel.clone());
+    }
+    Ok(coins.into_iter().collect())
+}
+
+async fn refresh_external_prices(ctx: &MmArc) -> Result<(), String> {
+    let coins = try_s!(external_price_coins(ctx));
+    if coins.is_empty() {return Ok(())}
+    let provider = try_s!(PricingProvider::from_conf(&ctx.conf));
+    let prices = try_s!(fetch_external_prices(&ctx.conf, &provider, &coins).await);
+    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    *try_s!(ordermatch_ctx.external_prices.lock()) = ExternalPrices {prices, at: now_ms()};
+    Ok(())
+}
+
+/// Fetches the external prices of the coins of my pending conditional orders with the "external" price source.
+pub async fn external_prices_loop(ctx: MmArc) {
+    loop {
+        if ctx.is_stopping() {break}
+        if let Err(err) = refresh_external_prices(&ctx).await {
+            log!("Error fetching the external prices: " (err));
+        }
+        Timer::sleep(EXTERNAL_PRICES_INTERVAL).await;
+    }
+}
+
+#[derive(Deserialize)]
+struct ConditionalOrderReq {
+    trigger: Trigger,
+    #[serde(default)]
+    price_source: PriceSource,
+    /// The "buy" or "sell" request parameters.
+    order: Json,
+}
+
+pub fn conditional_order(ctx: MmArc, req: Json) -> HyRes {
+    let mut req: ConditionalOrderReq = try_th!(InvalidRequest, json::from_value(req));
+    let input: AutoBuyInput = try_th!(InvalidRequest, json::from_value(req.order.clone()));
+    if input.method != "buy" && input.method != "sell" {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The order method must be buy or sell")));
+    }
+    if input.base == input.rel {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")));
+    }
+    if input.volume <= MmNumber::from(0) {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The volume must be positive")));
+    }
+    if let Err(err) = check_min_price(&req.trigger.price) {return rpc_typed_err_response(err)}
+    match req.trigger.min_depth {
+        Some(ref min_depth) if *min_depth < MmNumber::from(0) => return rpc_typed_err_response(RpcError::new(
+            RpcErrorCode::InvalidRequest, ERRL!("The min_depth must not be negative"))),
+        Some(_) => (),
+        None => req.trigger.min_depth = Some(input.volume.clone()),
+    }
+    if req.price_source == PriceSource::External {
+        let provider = try_th!(InvalidRequest, PricingProvider::from_conf(&ctx.conf));
+        try_th!(InvalidRequest, provider.coin_id(&ctx.conf, &input.base));
+        try_th!(InvalidRequest, provider.coin_id(&ctx.conf, &input.rel));
+    }
+    if input.order_type == OrderType::Limit {
+        if let Err(err) = check_min_price(&input.price) {return rpc_typed_err_response(err)}
+    } else if input.price != MmNumber::from(0) {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest,
+            ERRL!("The price of the market order is computed from the orderbook")));
+    }
+    if let Err(err) = input.time_in_force.check(true) {return rpc_typed_err_response(err)}
+
+    let order = ConditionalOrder {
+        uuid: new_uuid(),
+        method: input.method,
+        base: input.base,
+        rel: input.rel,
+        trigger: req.trigger,
+        price_source: req.price_source,
+        order: req.order,
+        status: ConditionalOrderStatus::Pending,
+        history: Vec::new(),
+        created_at: now_ms(),
+        updated_at: now_ms(),
+    };
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    try_h!(save_my_conditional_order(&ctx, &order));
+    try_h!(ordermatch_ctx.my_conditional_orders.lock()).insert(order.uuid, order.clone());
+    rpc_response(200, json!({
+        "result": order
+    }).to_string())
+}
+
+#[derive(Deserialize)]
+struct CancelConditionalOrderReq {
+    uuid: Uuid,
+}
+
+pub fn cancel_conditional_order(ctx: MmArc, req: Json) -> HyRes {
+    let req: CancelConditionalOrderReq = try_th!(InvalidRequest, json::from_value(req));
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut orders = try_h!(ordermatch_ctx.my_conditional_orders.lock());
+    let order = match orders.get_mut(&req.uuid) {
+        Some(order) => order,
+        None => return rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderNotFound,
+            ERRL!("Conditional order {} is not found", req.uuid))),
+    };
+    if order.status != ConditionalOrderStatus::Pending {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest,
+            ERRL!("Conditional order {} is {:?} already", req.uuid, order.status)));
+    }
+    let mut cancelled = order.clone();
+    cancelled.status = ConditionalOrderStatus::Cancelled;
+    cancelled.updated_at = now_ms();
+    try_h!(save_my_conditional_order(&ctx, &cancelled));
+    *order = cancelled;
+    rpc_response(200, r#"{"result":"success"}"#)
+}
+
+/// My conditional orders, the most recently created first.
+pub fn my_conditional_orders(ctx: MmArc) -> HyRes {
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut orders: Vec<ConditionalOrder> = try_h!(ordermatch_ctx.my_conditional_orders.lock()).values().cloned().collect();
+    orders.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.uuid.cmp(&b.uuid)));
+    rpc_response(200, json!({
+        "result": orders
+    }).to_string())
+}
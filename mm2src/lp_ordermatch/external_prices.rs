@@ -0,0 +1,114 @@
+//! The BTC prices of the coins from the external pricing providers, the conditional orders with the "external" price source
+//! are triggered by (cf. `conditional_orders::external_prices_loop`).
+//!
+//! This is the `lp_btcprice` of the `portfolio` crate, which is disabled in the workspace (cf. Cargo.toml)
+//! and depends on the legacy C code: the prices are fetched from CoinGecko by default
+//! and from CoinMarketCap when the "cmc_key" is configured.
+//! CoinGecko identifies the coins by the lowercased "name" of their configuration ("komodo", "bitcoin"),
+//! CoinMarketCap by their ticker.
+
+use coins::coin_conf;
+use common::wio::slurp_req;
+use http::{Request, StatusCode};
+use super::*;
+
+/// The prices older than this don't trigger the orders, in milliseconds.
+const EXTERNAL_PRICES_TTL_MS: u64 = 300000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PricingProvider {
+    CoinGecko,
+    /// With the API key.
+    CoinMarketCap(String),
+}
+
+impl PricingProvider {
+    /// CoinMarketCap if the "cmc_key" is configured, CoinGecko otherwise.
+    pub fn from_conf(conf: &Json) -> Result<PricingProvider, String> {
+        match &conf["cmc_key"] {
+            Json::Null => Ok(PricingProvider::CoinGecko),
+            Json::String(key) => Ok(PricingProvider::CoinMarketCap(key.clone())),
+            _ => ERR!("cmc_key is not a string"),
+        }
+    }
+
+    /// The identifier of the `ticker` coin at the provider.
+    pub fn coin_id(&self, conf: &Json, ticker: &str) -> Result<String, String> {
+        match self {
+            PricingProvider::CoinGecko => match coin_conf(conf, ticker)["name"].as_str() {
+                Some(name) => Ok(name.to_lowercase()),
+                None => ERR!("The {} coin configuration has no \"name\" identifying the coin at CoinGecko", ticker),
+            },
+            PricingProvider::CoinMarketCap(_) => Ok(ticker.into()),
+        }
+    }
+}
+
+/// The prices of the coins in BTC, keyed by the coin ticker.
+#[derive(Clone, Debug, Default)]
+pub struct ExternalPrices {
+    pub prices: HashMap<String, BigRational>,
+    /// UNIX timestamp in milliseconds.
+    pub at: u64,
+}
+
+impl ExternalPrices {
+    /// The price of the `base` coin in the `rel` coin, `None` if either price is unknown or outdated.
+    pub fn price(&self, base: &str, rel: &str, now: u64) -> Option<BigRational> {
+        if self.at + EXTERNAL_PRICES_TTL_MS <= now {return None}
+        let base_price = self.prices.get(base)?;
+        let rel_price = self.prices.get(rel)?;
+        if rel_price.is_zero() {return None}
+        Some(base_price / rel_price)
+    }
+}
+
+fn price_from_json(price: &Json) -> Result<BigRational, String> {
+    match price {
+        Json::Number(price) => Ok(from_dec_to_ratio(try_s!(price.to_string().parse::<BigDecimal>()))),
+        _ => ERR!("Invalid price {}", price),
+    }
+}
+
+/// Parses the BTC prices from the `reply` of the provider, the `ids` map the provider coin identifiers to the tickers.
+/// The coins unknown to the provider are skipped.
+pub fn parse_external_prices(provider: &PricingProvider, ids: &HashMap<String, String>, reply: &Json)
+                             -> Result<HashMap<String, BigRational>, String> {
+    let mut prices = HashMap::new();
+    for (id, ticker) in ids {
+        let price = match provider {
+            // {"komodo": {"btc": 0.0001}}
+            PricingProvider::CoinGecko => &reply[id]["btc"],
+            // {"data": {"KMD": {"quote": {"BTC": {"price": 0.0001}}}}}
+            PricingProvider::CoinMarketCap(_) => &reply["data"][id]["quote"]["BTC"]["price"],
+        };
+        if price.is_null() {continue}
+        prices.insert(ticker.clone(), try_s!(price_from_json(price)));
+    }
+    Ok(prices)
+}
+
+/// Fetches the BTC prices of the `tickers` coins from the `provider`.
+pub async fn fetch_external_prices(conf: &Json, provider: &PricingProvider, tickers: &[String])
+                                   -> Result<HashMap<String, BigRational>, String> {
+    let mut ids = HashMap::new();
+    for ticker in tickers {
+        ids.insert(try_s!(provider.coin_id(conf, ticker)), ticker.clone());
+    }
+    let ids_param = ids.keys().cloned().collect::<Vec<_>>().join(",");
+    let request = match provider {
+        PricingProvider::CoinGecko => try_s!(Request::builder()
+            .uri(format!("https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=btc", ids_param))
+            .body(Vec::new())),
+        PricingProvider::CoinMarketCap(key) => try_s!(Request::builder()
+            .uri(format!("https://pro-api.coinmarketcap.com/v1/cryptocurrency/quotes/latest?symbol={}&convert=BTC", ids_param))
+            .header("X-CMC_PRO_API_KEY", &key[..])
+            .body(Vec::new())),
+    };
+    let (status, _headers, body) = try_s!(slurp_req(request).compat().await);
+    if status != StatusCode::OK {
+        return ERR!("The pricing provider has replied with {}: {}", status, String::from_utf8_lossy(&body));
+    }
+    let reply: Json = try_s!(json::from_slice(&body));
+    parse_external_prices(provider, &ids, &reply)
+}
@@ -0,0 +1,266 @@
+//! Protects our orderbook from the spam of the other nodes.
+//!
+//! The price pings of the new orders exceeding the limits of their pubkey are dropped by `lp_post_price_recv`:
+//!
+//!     "orderbook_limits": {"max_orders_per_pubkey": 100, "max_orders_per_pair": 10, "max_pairs_per_pubkey": 30}
+//!
+//! The orders selling or buying less than the "min_order_volume" of the coin configuration are dropped as well
+//! (my own orders having less than the `min_order_volume` of the `base` available are cancelled),
+//! and so are the orders priced out of the "min_price" and "max_price" bounds of the coin configuration,
+//! which are keyed by the other coin of the pair:
+//!
+//!     {"coin": "KMD", "min_price": {"BTC": "0.00001"}, "max_price": {"BTC": "0.001"}}
+//! The orders of the pubkeys banned with the "ban_pubkey" RPC are dropped altogether,
+//! the ban stays across restarts (we remember the banned pubkeys in the database) until the "unban_pubkey".
+//! The limits, the bans and the number of the orders per pubkey are listed with the "orderbook_limits" RPC.
+
+use coins::coin_conf;
+use super::*;
+
+fn default_max_orders_per_pubkey() -> usize { 100 }
+
+fn default_max_orders_per_pair() -> usize { 10 }
+
+fn default_max_pairs_per_pubkey() -> usize { 30 }
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OrderbookLimits {
+    /// The maximal number of the orders of a pubkey in the orderbook.
+    #[serde(default = "default_max_orders_per_pubkey")]
+    pub max_orders_per_pubkey: usize,
+    /// The maximal number of the orders of a pubkey for the same pair.
+    #[serde(default = "default_max_orders_per_pair")]
+    pub max_orders_per_pair: usize,
+    /// The maximal number of the distinct pairs a pubkey has the orders for.
+    #[serde(default = "default_max_pairs_per_pubkey")]
+    pub max_pairs_per_pubkey: usize,
+}
+
+impl Default for OrderbookLimits {
+    fn default() -> OrderbookLimits {
+        OrderbookLimits {
+            max_orders_per_pubkey: default_max_orders_per_pubkey(),
+            max_orders_per_pair: default_max_orders_per_pair(),
+            max_pairs_per_pubkey: default_max_pairs_per_pubkey(),
+        }
+    }
+}
+
+impl OrderbookLimits {
+    pub fn from_conf(conf: &Json) -> Result<OrderbookLimits, String> {
+        if conf["orderbook_limits"].is_null() {return Ok(OrderbookLimits::default())}
+        match json::from_value(conf["orderbook_limits"].clone()) {
+            Ok(limits) => Ok(limits),
+            Err(err) => ERR!("Invalid orderbook_limits configuration: {}", err),
+        }
+    }
+}
+
+/// Checks the price ping of an order which is not in the `orderbook` yet against the limits of its pubkey.
+pub fn check_new_order_limits(
+    limits: &OrderbookLimits,
+    orderbook: &HashMap<(String, String), HashMap<Uuid, PricePingRequest>>,
+    req: &PricePingRequest,
+) -> Result<(), String> {
+    let mut orders = 0;
+    let mut pair_orders = 0;
+    let mut pairs = 0;
+    for ((base, rel), pair_orderbook) in orderbook.iter() {
+        let count = pair_orderbook.values().filter(|order| order.pubkey == req.pubkey).count();
+        if count == 0 {continue}
+        orders += count;
+        pairs += 1;
+        if *base == req.base && *rel == req.rel {pair_orders = count}
+    }
+    if orders >= limits.max_orders_per_pubkey {
+        return ERR!("The pubkey {} has reached the limit of {} orders", req.pubkey, limits.max_orders_per_pubkey);
+    }
+    if pair_orders >= limits.max_orders_per_pair {
+        return ERR!("The pubkey {} has reached the limit of {} orders for the {}/{} pair",
+            req.pubkey, limits.max_orders_per_pair, req.base, req.rel);
+    }
+    if pair_orders == 0 && pairs >= limits.max_pairs_per_pubkey {
+        return ERR!("The pubkey {} has reached the limit of {} pairs", req.pubkey, limits.max_pairs_per_pubkey);
+    }
+    Ok(())
+}
+
+/// The "min_order_volume" of the `coin` configuration.
+fn coin_min_order_volume(conf: &Json, coin: &str) -> Option<MmNumber> {
+    let conf = coin_conf(conf, coin);
+    if conf["min_order_volume"].is_null() {return None}
+    json::from_value(conf["min_order_volume"].clone()).ok()
+}
+
+/// The volume of the `coin` my maker order must have available to stay in the orderbook:
+/// the "min_order_volume" of the coin configuration or 0.00777.
+pub fn min_order_volume(conf: &Json, coin: &str) -> MmNumber {
+    coin_min_order_volume(conf, coin).unwrap_or_else(|| BigRational::new(777.into(), 100000.into()).into())
+}
+
+/// Checks that the order sells at least the "min_order_volume" of the `base` coin
+/// and buys at least the "min_order_volume" of the `rel` coin at its price.
+pub fn check_min_order_volume(conf: &Json, req: &PricePingRequest) -> Result<(), String> {
+    let volume = req.get_balance_rat();
+    if let Some(min_volume) = coin_min_order_volume(conf, &req.base) {
+        if MmNumber::from(volume.clone()) < min_volume {
+            return ERR!("The volume {} is below the min_order_volume {} of {}", from_ratio_to_dec(&volume), min_volume, req.base);
+        }
+    }
+    if let Some(min_volume) = coin_min_order_volume(conf, &req.rel) {
+        let rel_volume = volume * req.get_price_rat();
+        if MmNumber::from(rel_volume.clone()) < min_volume {
+            return ERR!("The {} volume {} is below the min_order_volume {} of {}",
+                req.rel, from_ratio_to_dec(&rel_volume), min_volume, req.rel);
+        }
+    }
+    Ok(())
+}
+
+/// The `field` ("min_price" or "max_price") of the `coin` configuration, the bound of the `coin` price in the `other` coin.
+fn coin_price_bound(conf: &Json, coin: &str, other: &str, field: &str) -> Option<MmNumber> {
+    let bound = &coin_conf(conf, coin)[field][other];
+    if bound.is_null() {return None}
+    json::from_value(bound.clone()).ok()
+}
+
+/// Checks the `price` of the `coin` in the `other` coin against the bounds of the `coin` configuration.
+fn check_coin_price(conf: &Json, coin: &str, other: &str, price: &MmNumber) -> Result<(), String> {
+    if let Some(min_price) = coin_price_bound(conf, coin, other, "min_price") {
+        if *price < min_price {return ERR!("The {} price {} in {} is below its min_price {}", coin, price, other, min_price)}
+    }
+    if let Some(max_price) = coin_price_bound(conf, coin, other, "max_price") {
+        if *price > max_price {return ERR!("The {} price {} in {} is above its max_price {}", coin, price, other, max_price)}
+    }
+    Ok(())
+}
+
+/// Checks the price of the order against the "min_price" and "max_price" of the `base` and the `rel` coin configurations.
+pub fn check_price_bounds(conf: &Json, req: &PricePingRequest) -> Result<(), String> {
+    let price = req.get_price_rat();
+    try_s!(check_coin_price(conf, &req.base, &req.rel, &price.clone().into()));
+    if price > BigRational::zero() {
+        try_s!(check_coin_price(conf, &req.rel, &req.base, &price.recip().into()));
+    }
+    Ok(())
+}
+
+fn banned_pubkeys_path(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("BANNED_PUBKEYS.json")
+}
+
+/// The banned pubkeys and the reasons they are banned for.
+pub fn load_banned_pubkeys(ctx: &MmArc) -> Result<HashMap<String, String>, String> {
+    let path = banned_pubkeys_path(ctx);
+    let content = slurp(&path);
+    if content.is_empty() {return Ok(HashMap::new())}
+    match json::from_slice(&content) {
+        Ok(banned) => Ok(banned),
+        Err(err) => ERR!("Error parsing {}: {}", path.display(), err),
+    }
+}
+
+fn save_banned_pubkeys(ctx: &MmArc, banned: &HashMap<String, String>) -> Result<(), String> {
+    let content = try_s!(json::to_vec(banned));
+    write(&banned_pubkeys_path(ctx), &content)
+}
+
+/// Removes the orders of the `pubkey` from the `orderbook`, returning the number of the removed orders.
+pub fn remove_pubkey_orders(orderbook: &mut HashMap<(String, String), HashMap<Uuid, PricePingRequest>>, pubkey: &str) -> usize {
+    let mut removed = 0;
+    for pair_orderbook in orderbook.values_mut() {
+        let before = pair_orderbook.len();
+        pair_orderbook.retain(|_, order| order.pubkey != pubkey);
+        removed += before - pair_orderbook.len();
+    }
+    orderbook.retain(|_, pair_orderbook| !pair_orderbook.is_empty());
+    removed
+}
+
+/// The lowercased hex of the `pubkey`, which is how the pubkeys are kept in the orderbook.
+pub fn normalize_pubkey(pubkey: &str) -> Result<String, String> {
+    let bytes = try_s!(hex::decode(pubkey));
+    if bytes.len() != 32 {return ERR!("The pubkey must be 32 bytes long, got {}", bytes.len())}
+    Ok(hex::encode(&bytes))
+}
+
+#[derive(Deserialize)]
+struct BanPubkeyReq {
+    pubkey: String,
+    #[serde(default)]
+    reason: String,
+}
+
+pub fn ban_pubkey(ctx: MmArc, req: Json) -> HyRes {
+    let req: BanPubkeyReq = try_th!(InvalidRequest, json::from_value(req));
+    let pubkey = try_th!(InvalidRequest, normalize_pubkey(&req.pubkey));
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut banned = try_h!(ordermatch_ctx.banned_pubkeys.lock());
+    banned.insert(pubkey.clone(), req.reason);
+    try_h!(save_banned_pubkeys(&ctx, &banned));
+    drop(banned);
+
+    let removed = remove_pubkey_orders(&mut *try_h!(ordermatch_ctx.orderbook.lock()), &pubkey);
+    log!("The pubkey " (pubkey) " is banned, " (removed) " orders removed from the orderbook");
+    rpc_response(200, json!({
+        "result": {
+            "pubkey": pubkey,
+            "removed_orders": removed,
+        }
+    }).to_string())
+}
+
+#[derive(Deserialize)]
+struct UnbanPubkeyReq {
+    pubkey: String,
+}
+
+pub fn unban_pubkey(ctx: MmArc, req: Json) -> HyRes {
+    let req: UnbanPubkeyReq = try_th!(InvalidRequest, json::from_value(req));
+    let pubkey = try_th!(InvalidRequest, normalize_pubkey(&req.pubkey));
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut banned = try_h!(ordermatch_ctx.banned_pubkeys.lock());
+    if banned.remove(&pubkey).is_none() {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("The pubkey {} is not banned", pubkey)));
+    }
+    try_h!(save_banned_pubkeys(&ctx, &banned));
+    rpc_response(200, r#"{"result":"success"}"#)
+}
+
+#[derive(Serialize)]
+struct PubkeyOrders {
+    pubkey: String,
+    orders: usize,
+    pairs: usize,
+}
+
+pub fn orderbook_limits(ctx: MmArc) -> HyRes {
+    let ordermatch_ctx = try_h!(OrdermatchContext::from_ctx(&ctx));
+    let mut banned: Vec<Json> = try_h!(ordermatch_ctx.banned_pubkeys.lock()).iter()
+        .map(|(pubkey, reason)| json!({"pubkey": pubkey, "reason": reason}))
+        .collect();
+    banned.sort_by(|a, b| a["pubkey"].as_str().cmp(&b["pubkey"].as_str()));
+
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for pair_orderbook in try_h!(ordermatch_ctx.orderbook.lock()).values() {
+        let mut pair_counts: HashMap<&str, usize> = HashMap::new();
+        for order in pair_orderbook.values() {*pair_counts.entry(order.pubkey.as_str()).or_insert(0) += 1}
+        for (pubkey, count) in pair_counts {
+            let entry = counts.entry(pubkey.to_owned()).or_insert((0, 0));
+            entry.0 += count;
+            entry.1 += 1;
+        }
+    }
+    let mut pubkeys: Vec<PubkeyOrders> = counts.into_iter()
+        .map(|(pubkey, (orders, pairs))| PubkeyOrders {pubkey, orders, pairs})
+        .collect();
+    pubkeys.sort_by(|a, b| b.orders.cmp(&a.orders).then_with(|| a.pubkey.cmp(&b.pubkey)));
+
+    rpc_response(200, json!({
+        "result": {
+            "limits": ordermatch_ctx.orderbook_limits,
+            "banned_pubkeys": banned,
+            "pubkeys": pubkeys,
+        }
+    }).to_string())
+}
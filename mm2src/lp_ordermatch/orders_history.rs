@@ -0,0 +1,237 @@
+//! The history of my maker and taker orders, kept after the orders are removed.
+//!
+//! Every order event (cf. `publish_maker_order_event`, `publish_taker_order_event`) updates the record of the order
+//! in the "ORDERS/HISTORY" directory. The records are listed with the "orders_history" RPC.
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum OrderStatus {
+    /// The order is waiting to be matched.
+    Created,
+    /// The order is matched and waiting for the counterparty to start the swap.
+    Matched,
+    /// Some swaps are started, the order is still active.
+    PartiallyFilled,
+    /// The swaps are started for the whole volume of the order.
+    Filled,
+    /// The order is cancelled, cf. the `cancellation_reason`.
+    Cancelled,
+    /// The `GoodTillTime` order has expired.
+    Expired,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OrderHistoryRecord {
+    pub uuid: Uuid,
+    /// "Maker" or "Taker". The unmatched taker order converted to a maker order is recorded as the "Maker" one.
+    #[serde(rename = "type")]
+    pub order_type: String,
+    /// "Buy" or "Sell", the maker orders always "Sell" the `base`.
+    pub action: String,
+    pub base: String,
+    pub rel: String,
+    /// In the `rel` coin per the `base` coin.
+    pub price: BigDecimal,
+    pub price_rat: BigRational,
+    /// In the `base` coin.
+    pub volume: BigDecimal,
+    pub volume_rat: BigRational,
+    pub status: OrderStatus,
+    pub cancellation_reason: Option<OrderCancellationReason>,
+    /// The uuids of the swaps started by the order.
+    pub swaps: Vec<Uuid>,
+    /// UNIX timestamp in milliseconds.
+    pub created_at: u64,
+    /// UNIX timestamp in milliseconds.
+    pub updated_at: u64,
+}
+
+impl OrderHistoryRecord {
+    pub fn from_maker_order(order: &MakerOrder, reason: Option<OrderCancellationReason>) -> OrderHistoryRecord {
+        let status = match reason {
+            Some(OrderCancellationReason::Expired) => OrderStatus::Expired,
+            // the order is drained by the swaps
+            Some(OrderCancellationReason::InsufficientVolume) if !order.started_swaps.is_empty() => OrderStatus::Filled,
+            Some(_) => OrderStatus::Cancelled,
+            None if !order.started_swaps.is_empty() => OrderStatus::PartiallyFilled,
+            None if order.matches.is_empty() => OrderStatus::Created,
+            None => OrderStatus::Matched,
+        };
+        OrderHistoryRecord {
+            uuid: order.uuid,
+            order_type: "Maker".into(),
+            action: "Sell".into(),
+            base: order.base.clone(),
+            rel: order.rel.clone(),
+            price: order.price.clone(),
+            price_rat: order.price_rat.clone(),
+            volume: order.max_base_vol.clone(),
+            volume_rat: order.max_base_vol_rat.clone(),
+            status,
+            cancellation_reason: reason,
+            swaps: order.started_swaps.clone(),
+            created_at: order.created_at,
+            updated_at: now_ms(),
+        }
+    }
+
+    pub fn from_taker_order(order: &TakerOrder, reason: Option<OrderCancellationReason>) -> OrderHistoryRecord {
+        let status = match reason {
+            Some(OrderCancellationReason::Expired) => OrderStatus::Expired,
+            Some(_) => OrderStatus::Cancelled,
+            None if order.is_filled() => OrderStatus::Filled,
+            None if !order.started_swaps.is_empty() => OrderStatus::PartiallyFilled,
+            None if order.matches.is_empty() => OrderStatus::Created,
+            None => OrderStatus::Matched,
+        };
+        let price = order.request.get_rel_amount() / order.request.get_base_amount();
+        OrderHistoryRecord {
+            uuid: order.request.uuid,
+            order_type: "Taker".into(),
+            action: match order.request.action {
+                TakerAction::Buy => "Buy".into(),
+                TakerAction::Sell => "Sell".into(),
+            },
+            base: order.request.base.clone(),
+            rel: order.request.rel.clone(),
+            price: price.clone().into(),
+            price_rat: price.into(),
+            volume: order.request.base_amount.clone(),
+            volume_rat: order.request.get_base_amount().into(),
+            status,
+            cancellation_reason: reason,
+            swaps: order.started_swaps.clone(),
+            created_at: order.created_at,
+            updated_at: now_ms(),
+        }
+    }
+
+    /// Keeps the creation time and the swaps of the `previous` record of the order,
+    /// which is the taker record when the taker order is converted to a maker order.
+    pub fn merge(mut self, previous: Option<OrderHistoryRecord>) -> OrderHistoryRecord {
+        if let Some(previous) = previous {
+            self.created_at = previous.created_at;
+            let mut swaps = previous.swaps;
+            for uuid in self.swaps {
+                if !swaps.contains(&uuid) {swaps.push(uuid)}
+            }
+            self.swaps = swaps;
+        }
+        self
+    }
+}
+
+pub fn orders_history_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("ORDERS").join("HISTORY")
+}
+
+fn order_history_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
+    orders_history_dir(ctx).join(format!("{}.json", uuid))
+}
+
+fn load_order_history_record(ctx: &MmArc, uuid: &Uuid) -> Option<OrderHistoryRecord> {
+    let content = slurp(&order_history_file_path(ctx, uuid));
+    if content.is_empty() {return None}
+    json::from_slice(&content).ok()
+}
+
+/// The history is auxiliary, failing to save it doesn't affect the order.
+fn save_order_history_record(ctx: &MmArc, record: OrderHistoryRecord) {
+    let record = record.merge(load_order_history_record(ctx, &record.uuid));
+    let content = unwrap!(json::to_vec(&record));
+    if let Err(err) = write(&order_history_file_path(ctx, &record.uuid), &content) {
+        log!("Error saving the history of the order " (record.uuid) ": " (err));
+    }
+}
+
+/// Records the current state of my maker order, the `reason` is given when the order is cancelled.
+pub fn save_maker_order_history(ctx: &MmArc, order: &MakerOrder, reason: Option<OrderCancellationReason>) {
+    save_order_history_record(ctx, OrderHistoryRecord::from_maker_order(order, reason))
+}
+
+/// Records the current state of my taker order, the `reason` is given when the order is cancelled.
+pub fn save_taker_order_history(ctx: &MmArc, order: &TakerOrder, reason: Option<OrderCancellationReason>) {
+    save_order_history_record(ctx, OrderHistoryRecord::from_taker_order(order, reason))
+}
+
+fn default_limit() -> usize { 10 }
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OrdersHistoryFilter {
+    base: Option<String>,
+    rel: Option<String>,
+    /// "Maker" or "Taker".
+    #[serde(rename = "type")]
+    order_type: Option<String>,
+    status: Option<OrderStatus>,
+    /// The orders created at or after this UNIX timestamp (in seconds).
+    from_timestamp: Option<u64>,
+    /// The orders created before this UNIX timestamp (in seconds).
+    to_timestamp: Option<u64>,
+}
+
+impl OrdersHistoryFilter {
+    fn matches(&self, record: &OrderHistoryRecord) -> bool {
+        fn eq<T: PartialEq>(filter: &Option<T>, value: &T) -> bool {
+            filter.as_ref().map(|f| f == value).unwrap_or(true)
+        }
+        let created_at = record.created_at / 1000;
+        eq(&self.base, &record.base) && eq(&self.rel, &record.rel) && eq(&self.order_type, &record.order_type)
+            && eq(&self.status, &record.status)
+            && self.from_timestamp.map(|from| created_at >= from).unwrap_or(true)
+            && self.to_timestamp.map(|to| created_at < to).unwrap_or(true)
+    }
+}
+
+#[derive(Deserialize)]
+struct OrdersHistoryReq {
+    #[serde(flatten)]
+    filter: OrdersHistoryFilter,
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Skip the records up to and including this one.
+    from_uuid: Option<Uuid>,
+}
+
+/// The records passing the `filter`, the most recently created first.
+pub fn filter_orders_history(records: Vec<OrderHistoryRecord>, filter: &OrdersHistoryFilter) -> Vec<OrderHistoryRecord> {
+    let mut records: Vec<OrderHistoryRecord> = records.into_iter().filter(|record| filter.matches(record)).collect();
+    records.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.uuid.cmp(&b.uuid)));
+    records
+}
+
+pub fn orders_history(ctx: MmArc, req: Json) -> HyRes {
+    let req: OrdersHistoryReq = try_th!(InvalidRequest, json::from_value(req));
+    let entries = try_h!(json_dir_entries(&orders_history_dir(&ctx)));
+    let records: Vec<OrderHistoryRecord> = entries.iter().filter_map(|entry|
+        match json::from_slice::<OrderHistoryRecord>(&slurp(&entry.path())) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log!("Error " (e) " parsing JSON from " (entry.path().display()));
+                None
+            },
+        }
+    ).collect();
+    let records = filter_orders_history(records, &req.filter);
+
+    let skip = match req.from_uuid {
+        Some(uuid) => match records.iter().position(|record| record.uuid == uuid) {
+            Some(pos) => pos + 1,
+            None => return rpc_typed_err_response(RpcError::new(RpcErrorCode::OrderNotFound,
+                ERRL!("from_uuid {} order is not found", uuid))),
+        },
+        None => 0,
+    };
+    let orders: Vec<&OrderHistoryRecord> = records.iter().skip(skip).take(req.limit).collect();
+
+    rpc_response(200, json!({
+        "result": {
+            "orders": orders,
+            "from_uuid": req.from_uuid,
+            "skipped": skip,
+            "limit": req.limit,
+            "total": records.len(),
+        }
+    }).to_string())
+}
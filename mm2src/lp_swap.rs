@@ -59,8 +59,10 @@
 
 use bigdecimal::BigDecimal;
 use rpc::v1::types::{Bytes as BytesJson, H160 as H160Json, H256 as H256Json, H264 as H264Json};
-use coins::{lp_coinfind, MmCoinEnum, TradeInfo, TransactionDetails, TransactionEnum};
+use coins::{coin_conf, lp_coinfind, MmCoinEnum, TradeInfo, TransactionDetails, TransactionEnum};
 use common::{bits256, HyRes, rpc_response};
+use common::event_stream::EventTopic;
+use common::rpc_error::{rpc_typed_err_response, RpcError, RpcErrorCode};
 use common::executor::Timer;
 use common::log::{TagParam};
 use common::mm_ctx::{from_ctx, MmArc};
@@ -74,6 +76,7 @@ use serde_json::{self as json, Value as Json};
 use serialization::{deserialize, serialize};
 use std::collections::{HashSet, HashMap};
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs::{File, DirEntry};
 use std::io::prelude::*;
 use std::path::PathBuf;
@@ -120,6 +123,14 @@ macro_rules! recv_ {
     }}
 }
 
+macro_rules! recover_err {
+    ($kind: ident, $($args: tt)+) => {Err(RecoverFundsError::$kind(ERRL!($($args)+)))};
+}
+
+macro_rules! try_recover {
+    ($kind: ident, $e: expr) => {match $e {Ok(ok) => ok, Err(err) => return recover_err!($kind, "{}", err)}};
+}
+
 macro_rules! recv {
     ($selff: ident, $subj: expr, $timeout_sec: expr, $ec: expr, $validator: expr) => {
         recv_! ($selff, $subj, $timeout_sec, $ec, $validator)
@@ -136,11 +147,18 @@ macro_rules! recv {
 mod maker_swap;
 #[path = "lp_swap/taker_swap.rs"]
 mod taker_swap;
-
-use maker_swap::{MakerSavedSwap, stats_maker_swap_file_path};
-use taker_swap::{TakerSavedSwap, stats_taker_swap_file_path};
+#[path = "lp_swap/swap_stats.rs"]
+mod swap_stats;
+#[path = "lp_swap/swap_recovery.rs"]
+mod swap_recovery;
+
+use maker_swap::{MakerSavedSwap, save_recovered_maker_swap, stats_maker_swap_dir, stats_maker_swap_file_path};
+use taker_swap::{TakerSavedSwap, save_recovered_taker_swap, stats_taker_swap_dir, stats_taker_swap_file_path};
+use swap_recovery::{recover_swap_funds, SwapRecoveries};
 pub use maker_swap::{MakerSwap, run_maker_swap};
 pub use taker_swap::{TakerSwap, run_taker_swap};
+pub use swap_stats::{ohlc, ticker};
+pub use swap_recovery::{pending_recoveries, swap_recovery_loop};
 
 /// Includes the grace time we add to the "normal" timeouts
 /// in order to give different and/or heavy communication channels a chance.
@@ -150,22 +168,42 @@ const BASIC_COMM_TIMEOUT: u64 = 90;
 /// Maker sends payment with LOCKTIME * 2
 /// Taker sends payment with LOCKTIME
 const PAYMENT_LOCKTIME: u64 = 3600 * 2 + 300 * 2;
+/// The payment is refunded this many seconds after its locktime, as the coins having the BIP113 activated
+/// check the locktime against the median time past of the chain which lags behind the wall clock.
+const REFUND_DELAY: u64 = 3700;
 const _SWAP_DEFAULT_NUM_CONFIRMS: u32 = 1;
 const _SWAP_DEFAULT_MAX_CONFIRMS: u32 = 6;
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum RecoveredSwapAction {
     RefundedMyPayment,
     SpentOtherPayment,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct RecoveredSwap {
     action: RecoveredSwapAction,
     coin: String,
     transaction: TransactionEnum,
 }
 
+/// Why the funds of the swap aren't recovered.
+#[derive(Debug, PartialEq)]
+pub enum RecoverFundsError {
+    /// There is nothing to recover, e.g. my payment was never sent or it's spent or refunded already.
+    Unrecoverable(String),
+    /// The recovery can succeed later, e.g. after the locktime passes or when the coin is reachable again.
+    Temporary(String),
+}
+
+impl fmt::Display for RecoverFundsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecoverFundsError::Unrecoverable(e) | RecoverFundsError::Temporary(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 /// Represents the amount of a coin locked by ongoing swap
 pub struct LockedAmount {
     coin: String,
@@ -180,10 +218,17 @@ pub trait AtomicSwap: Send + Sync {
     fn maker_coin(&self) -> &str;
 
     fn taker_coin(&self) -> &str;
+
+    /// "Maker" or "Taker".
+    fn swap_type(&self) -> &'static str;
+
+    /// The type of the last event applied to the swap, like "MakerPaymentSent".
+    fn state(&self) -> &str;
 }
 
 struct SwapsContext {
     running_swaps: Mutex<Vec<Weak<RwLock<dyn AtomicSwap>>>>,
+    recoveries: Mutex<SwapRecoveries>,
 }
 
 impl SwapsContext {
@@ -192,6 +237,7 @@ impl SwapsContext {
         Ok (try_s! (from_ctx (&ctx.swaps_ctx, move || {
             Ok (SwapsContext {
                 running_swaps: Mutex::new(vec![]),
+                recoveries: Mutex::new(SwapRecoveries::default()),
             })
         })))
     }
@@ -267,19 +313,83 @@ pub fn active_swaps_using_coin(ctx: &MmArc, coin: &str) -> Result<Vec<Uuid>, Str
     Ok(uuids)
 }
 
+/// The number of the running swaps by the swap type and state, for the "/metrics" endpoint.
+pub fn active_swaps_by_state(ctx: &MmArc) -> Result<HashMap<(&'static str, String), usize>, String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(&ctx));
+    let swaps = try_s!(swap_ctx.running_swaps.lock());
+    let mut states = HashMap::new();
+    for swap in swaps.iter() {
+        if let Some(swap) = swap.upgrade() {
+            let swap = try_s!(swap.read());
+            *states.entry((swap.swap_type(), swap.state().to_owned())).or_insert(0) += 1;
+        }
+    }
+    Ok(states)
+}
+
+/// Updates the "mm2_swaps_finished_total" or the "mm2_swaps_failed_total" counter when the swap loop is over.
+fn count_finished_swap(ctx: &MmArc, swap: &dyn AtomicSwap, is_success: bool) {
+    let name = if is_success {"mm2_swaps_finished_total"} else {"mm2_swaps_failed_total"};
+    ctx.metrics.inc_counter(name, &[("maker_coin", swap.maker_coin()), ("taker_coin", swap.taker_coin())]);
+}
+
 /// Some coins are "slow" (block time is high - e.g. BTC average block time is ~10 minutes).
 /// https://bitinfocharts.com/comparison/bitcoin-confirmationtime.html
-/// We need to increase payment locktime accordingly when at least 1 side of swap uses "slow" coin.
-fn lp_atomic_locktime(base: &str, rel: &str) -> u64 {
-    if base == "BTC" || rel == "BTC" {
-        PAYMENT_LOCKTIME * 10
-    } else if base == "BCH" || rel == "BCH" || base == "BTG" || rel == "BTG" || base == "SBTC" || rel == "SBTC" {
-        PAYMENT_LOCKTIME * 4
-    } else {
-        PAYMENT_LOCKTIME
+/// The "block_time_class" of the coin configuration, the payment locktime is multiplied accordingly.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum BlockTimeClass {
+    Normal,
+    Slow,
+    VerySlow,
+}
+
+impl BlockTimeClass {
+    /// The "block_time_class" of the `ticker` coin configuration, `None` if the coin doesn't have one.
+    fn from_coin_conf(conf: &Json, ticker: &str) -> Result<Option<BlockTimeClass>, String> {
+        let class = &coin_conf(conf, ticker)["block_time_class"];
+        if class.is_null() {return Ok(None)}
+        Ok(Some(try_s!(json::from_value(class.clone()))))
+    }
+
+    /// The class of the `ticker` coin, the well-known slow coins are slow even without the configuration.
+    /// The invalid classes are refused on the coin activation, cf. `check_coin_swap_conf`.
+    fn of_coin(conf: &Json, ticker: &str) -> BlockTimeClass {
+        match BlockTimeClass::from_coin_conf(conf, ticker) {
+            Ok(Some(class)) => return class,
+            Ok(None) => (),
+            Err(err) => log!("Invalid block_time_class of " (ticker) ": " (err)),
+        }
+        match ticker {
+            "BTC" => BlockTimeClass::VerySlow,
+            "BCH" | "BTG" | "SBTC" => BlockTimeClass::Slow,
+            _ => BlockTimeClass::Normal,
+        }
+    }
+
+    fn locktime_multiplier(self) -> u64 {
+        match self {
+            BlockTimeClass::Normal => 1,
+            BlockTimeClass::Slow => 4,
+            BlockTimeClass::VerySlow => 10,
+        }
     }
 }
 
+/// Checks the swap related fields of the `ticker` coin configuration, the coin isn't activated if they're invalid.
+pub fn check_coin_swap_conf(conf: &Json, ticker: &str) -> Result<(), String> {
+    try_s!(BlockTimeClass::from_coin_conf(conf, ticker));
+    Ok(())
+}
+
+/// We need to increase payment locktime accordingly when at least 1 side of swap uses "slow" coin.
+/// Both sides of the swap must have the same classes of the coins configured, cf. the negotiation.
+fn lp_atomic_locktime(conf: &Json, base: &str, rel: &str) -> u64 {
+    let multiplier = BlockTimeClass::of_coin(conf, base).locktime_multiplier()
+        .max(BlockTimeClass::of_coin(conf, rel).locktime_multiplier());
+    PAYMENT_LOCKTIME * multiplier
+}
+
 fn dex_fee_rate(base: &str, rel: &str) -> BigDecimal {
     if base == "KMD" || rel == "KMD" {
         // 1/777 - 10%
@@ -317,6 +427,31 @@ pub fn my_swap_file_path(ctx: &MmArc, uuid: &str) -> PathBuf {
     my_swaps_dir(ctx).join(format!("{}.json", uuid))
 }
 
+/// The "type" the swap event is saved with, e.g. "TakerFeeValidated".
+fn swap_event_type<E: serde::Serialize>(event: &E) -> String {
+    match json::to_value(event) {
+        Ok(event) => event["type"].as_str().unwrap_or("").to_owned(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Pushes the swap event to the `event_stream` subscribers.
+/// The `event` is serialized the same way as in the saved swap file: `{"type": ..., "data": ...}`.
+fn publish_swap_event<E: serde::Serialize>(ctx: &MmArc, uuid: &str, maker_or_taker: &str, event: &E) {
+    let event = match json::to_value(event) {
+        Ok(e) => e,
+        Err(e) => {
+            log!("!publish_swap_event(" (uuid) "): " (e));
+            return;
+        },
+    };
+    let event_type = event["type"].as_str().unwrap_or("").to_owned();
+    ctx.event_stream.publish(EventTopic::Swap, uuid, &event_type, json!({
+        "type": maker_or_taker,
+        "event": event,
+    }));
+}
+
 fn save_stats_swap(ctx: &MmArc, swap: &SavedSwap) -> Result<(), String> {
     let (path, content) = match &swap {
         SavedSwap::Maker(maker_swap) => (stats_maker_swap_file_path(ctx, &maker_swap.uuid), try_s!(json::to_vec(&maker_swap))),
@@ -381,28 +516,28 @@ impl SavedSwap {
         }
     }
 
-    fn recover_funds(self, ctx: MmArc) -> Result<RecoveredSwap, String> {
-        let maker_ticker = try_s!(self.maker_coin_ticker());
+    fn recover_funds(self, ctx: MmArc) -> Result<RecoveredSwap, RecoverFundsError> {
+        let maker_ticker = try_recover!(Unrecoverable, self.maker_coin_ticker());
         let maker_coin = match block_on(lp_coinfind(&ctx, &maker_ticker)) {
             Ok(Some(c)) => c,
-            Ok(None) => return ERR!("Coin {} is not activated", maker_ticker),
-            Err(e) => return ERR!("Error {} on {} coin find attempt", e, maker_ticker),
+            Ok(None) => return recover_err!(Temporary, "Coin {} is not activated", maker_ticker),
+            Err(e) => return recover_err!(Temporary, "Error {} on {} coin find attempt", e, maker_ticker),
         };
 
-        let taker_ticker = try_s!(self.taker_coin_ticker());
+        let taker_ticker = try_recover!(Unrecoverable, self.taker_coin_ticker());
         let taker_coin = match block_on(lp_coinfind(&ctx, &taker_ticker)) {
             Ok(Some(c)) => c,
-            Ok(None) => return ERR!("Coin {} is not activated", taker_ticker),
-            Err(e) => return ERR!("Error {} on {} coin find attempt", e, taker_ticker),
+            Ok(None) => return recover_err!(Temporary, "Coin {} is not activated", taker_ticker),
+            Err(e) => return recover_err!(Temporary, "Error {} on {} coin find attempt", e, taker_ticker),
         };
         match self {
             SavedSwap::Maker(saved) => {
-                let (maker_swap, _) = try_s!(MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
-                Ok(try_s!(maker_swap.recover_funds()))
+                let (maker_swap, _) = try_recover!(Unrecoverable, MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
+                maker_swap.recover_funds()
             },
             SavedSwap::Taker(saved) => {
-                let (taker_swap, _) = try_s!(TakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
-                Ok(try_s!(taker_swap.recover_funds()))
+                let (taker_swap, _) = try_recover!(Unrecoverable, TakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
+                taker_swap.recover_funds()
             },
         }
     }
@@ -418,6 +553,14 @@ impl SavedSwap {
         }
     }
 
+    /// The UNIX timestamp in seconds from which the funds of the recoverable swap can be recovered.
+    fn recover_funds_after(&self) -> Option<u64> {
+        match self {
+            SavedSwap::Maker(saved) => saved.recover_funds_after(),
+            SavedSwap::Taker(saved) => saved.recover_funds_after(),
+        }
+    }
+
     fn save_to_db(&self, ctx: &MmArc) -> Result<(), String> {
         let path = my_swap_file_path(ctx, self.uuid());
         if path.exists() {
@@ -462,13 +605,11 @@ impl<'a> From<&'a SavedSwap> for MySwapStatusResponse<'a> {
 
 /// Returns the status of swap performed on `my` node
 pub fn my_swap_status(ctx: MmArc, req: Json) -> HyRes {
-    let uuid = try_h!(req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
+    let uuid = try_th!(InvalidRequest, req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
     let path = my_swap_file_path(&ctx, uuid);
     let content = slurp(&path);
     if content.is_empty() {
-        return rpc_response(404, json!({
-            "error": "swap data is not found"
-        }).to_string());
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::SwapNotFound, "swap data is not found".into()));
     }
     let status: SavedSwap = try_h!(json::from_slice(&content));
 
@@ -479,7 +620,7 @@ pub fn my_swap_status(ctx: MmArc, req: Json) -> HyRes {
 
 /// Returns the status of requested swap, typically performed by other nodes and saved by `save_stats_swap_status`
 pub fn stats_swap_status(ctx: MmArc, req: Json) -> HyRes {
-    let uuid = try_h!(req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
+    let uuid = try_th!(InvalidRequest, req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
     let maker_path = stats_maker_swap_file_path(&ctx, uuid);
     let taker_path = stats_taker_swap_file_path(&ctx, uuid);
     let maker_content = slurp(&maker_path);
@@ -497,9 +638,7 @@ pub fn stats_swap_status(ctx: MmArc, req: Json) -> HyRes {
     };
 
     if maker_status.is_none() && taker_status.is_none() {
-        return rpc_response(404, json!({
-            "error": "swap data is not found"
-        }).to_string());
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::SwapNotFound, "swap data is not found".into()));
     }
 
     rpc_response(200, json!({
@@ -716,14 +855,11 @@ pub async fn coins_needed_for_kick_start(ctx: MmArc) -> Result<Response<Vec<u8>>
 }
 
 pub async fn recover_funds_of_swap(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
-    let uuid = try_s!(req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
+    let uuid = try_tr!(InvalidRequest, req["params"]["uuid"].as_str().ok_or("uuid parameter is not set or is not string"));
     let path = my_swap_file_path(&ctx, uuid);
-    let content = slurp(&path);
-    if content.is_empty() { return ERR!("swap data is not found") }
+    if slurp(&path).is_empty() { return RpcError::new(RpcErrorCode::SwapNotFound, "swap data is not found".into()).into_response() }
 
-    let swap: SavedSwap = try_s!(json::from_slice(&content));
-
-    let recover_data = try_s!(swap.recover_funds(ctx));
+    let recover_data = try_tr!(SwapIsNotRecoverable, recover_swap_funds(&ctx, uuid));
     let res = try_s!(json::to_vec(&json!({
         "result": {
             "action": recover_data.action,
@@ -789,6 +925,31 @@ mod lp_swap_tests {
         assert_eq!(expected_fee, actual_fee);
     }
 
+    #[test]
+    fn test_lp_atomic_locktime() {
+        let conf = json!({"coins": [
+            {"coin": "SLOW", "block_time_class": "slow"},
+            {"coin": "BTC", "block_time_class": "normal"},
+        ]});
+        assert_eq!(lp_atomic_locktime(&conf, "KMD", "ETH"), PAYMENT_LOCKTIME);
+        assert_eq!(lp_atomic_locktime(&conf, "KMD", "SLOW"), PAYMENT_LOCKTIME * 4);
+        // the configuration overrides the well-known classes
+        assert_eq!(lp_atomic_locktime(&conf, "BTC", "KMD"), PAYMENT_LOCKTIME);
+        assert_eq!(lp_atomic_locktime(&json!({}), "BTC", "KMD"), PAYMENT_LOCKTIME * 10);
+        assert_eq!(lp_atomic_locktime(&json!({}), "BCH", "BTC"), PAYMENT_LOCKTIME * 10);
+    }
+
+    #[test]
+    fn test_check_coin_swap_conf() {
+        let conf = json!({"coins": [
+            {"coin": "SLOW", "block_time_class": "slow"},
+            {"coin": "TYPO", "block_time_class": "sluggish"},
+        ]});
+        unwrap!(check_coin_swap_conf(&conf, "SLOW"));
+        unwrap!(check_coin_swap_conf(&conf, "KMD"));
+        assert!(check_coin_swap_conf(&conf, "TYPO").is_err());
+    }
+
     #[test]
     fn test_serde_swap_negotiation_data() {
         let data = SwapNegotiationData::default();
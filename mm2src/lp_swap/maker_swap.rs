@@ -7,8 +7,12 @@ use peers::FixedValidator;
 use rand::Rng;
 use super::*;
 
+pub fn stats_maker_swap_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("SWAPS").join("STATS").join("MAKER")
+}
+
 pub fn stats_maker_swap_file_path(ctx: &MmArc, uuid: &str) -> PathBuf {
-    ctx.dbdir().join("SWAPS").join("STATS").join("MAKER").join(format!("{}.json", uuid))
+    stats_maker_swap_dir(ctx).join(format!("{}.json", uuid))
 }
 
 fn save_my_maker_swap_event(ctx: &MmArc, uuid: &str, event: MakerSavedEvent) -> Result<(), String> {
@@ -43,6 +47,23 @@ fn save_my_maker_swap_event(ctx: &MmArc, uuid: &str, event: MakerSavedEvent) ->
     }
 }
 
+/// Records the maker payment refunded after the swap is finished (cf. `SavedSwap::recover_funds_and_save`).
+pub fn save_recovered_maker_swap(ctx: &MmArc, uuid: &str, action: &RecoveredSwapAction, tx_details: TransactionDetails)
+                                 -> Result<(), String> {
+    let recovered = match action {
+        RecoveredSwapAction::RefundedMyPayment => MakerSwapEvent::MakerPaymentRefunded(tx_details),
+        RecoveredSwapAction::SpentOtherPayment => return ERR!("The maker recovers the funds by the refund only"),
+    };
+    for event in vec![recovered, MakerSwapEvent::Finished] {
+        try_s!(save_my_maker_swap_event(ctx, uuid, MakerSavedEvent {
+            timestamp: now_ms(),
+            event: event.clone(),
+        }));
+        publish_swap_event(ctx, uuid, "Maker", &event);
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct TakerNegotiationData {
     pub taker_payment_locktime: u64,
@@ -90,10 +111,13 @@ pub struct MakerSwap {
     maker_payment_refund: Option<TransactionDetails>,
     errors: Vec<SwapError>,
     finished_at: u64,
+    /// The type of the last applied event.
+    state: String,
 }
 
 impl MakerSwap {
     fn apply_event(&mut self, event: MakerSwapEvent) -> Result<(), String> {
+        self.state = swap_event_type(&event);
         match event {
             MakerSwapEvent::Started(data) => self.data = data,
             MakerSwapEvent::StartFailed(err) => self.errors.push(err),
@@ -165,6 +189,7 @@ impl MakerSwap {
             errors: vec![],
             finished_at: 0,
             taker_payment_confirmed: false,
+            state: String::new(),
         }
     }
 
@@ -202,7 +227,7 @@ impl MakerSwap {
             ));
         };
 
-        let lock_duration = lp_atomic_locktime(self.maker_coin.ticker(), self.taker_coin.ticker());
+        let lock_duration = lp_atomic_locktime(&self.ctx.conf, self.maker_coin.ticker(), self.taker_coin.ticker());
         let mut rng = rand::thread_rng();
         let secret: [u8; 32] = rng.gen();
         let started_at = now_ms() / 1000;
@@ -286,9 +311,12 @@ impl MakerSwap {
 
         let expected_lock_time = taker_data.started_at + self.data.lock_duration;
         if taker_data.payment_locktime != expected_lock_time {
+            // the taker has other block time classes of the coins configured
+            let taker_lock_duration = taker_data.payment_locktime as i64 - taker_data.started_at as i64;
             return Ok((
                 Some(MakerSwapCommand::Finish),
-                vec![MakerSwapEvent::NegotiateFailed(ERRL!("taker_data.payment_locktime {} not equal to expected {}", taker_data.payment_locktime, expected_lock_time).into())]
+                vec![MakerSwapEvent::NegotiateFailed(ERRL!("taker_data.payment_locktime {} not equal to expected {}, the taker lock duration {} differs from {}",
+                    taker_data.payment_locktime, expected_lock_time, taker_lock_duration, self.data.lock_duration).into())]
             ))
         }
 
@@ -564,7 +592,7 @@ impl MakerSwap {
     fn refund_maker_payment(&self) -> Result<(Option<MakerSwapCommand>, Vec<MakerSwapEvent>), String> {
         // have to wait for 1 hour more due as some coins have BIP113 activated so these will reject transactions with locktime == present time
         // https://github.com/bitcoin/bitcoin/blob/master/doc/release-notes/release-notes-0.11.2.md#bip113-mempool-only-locktime-enforcement-using-getmediantimepast
-        while now_ms() / 1000 < self.data.maker_payment_lock + 3700 {
+        while now_ms() / 1000 < self.data.maker_payment_lock + REFUND_DELAY {
             std::thread::sleep(Duration::from_secs(10));
         }
 
@@ -639,17 +667,17 @@ impl MakerSwap {
         }
     }
 
-    pub fn recover_funds(&self) -> Result<RecoveredSwap, String> {
-        if self.finished_at == 0 { return ERR!("Swap must be finished before recover funds attempt"); }
+    pub fn recover_funds(&self) -> Result<RecoveredSwap, RecoverFundsError> {
+        if self.finished_at == 0 { return recover_err!(Temporary, "Swap must be finished before recover funds attempt"); }
 
-        if self.maker_payment_refund.is_some() { return ERR!("Maker payment is refunded, swap is not recoverable"); }
+        if self.maker_payment_refund.is_some() { return recover_err!(Unrecoverable, "Maker payment is refunded, swap is not recoverable"); }
 
-        if self.taker_payment_spend.is_some() { return ERR!("Taker payment is spent, swap is not recoverable"); }
+        if self.taker_payment_spend.is_some() { return recover_err!(Unrecoverable, "Taker payment is spent, swap is not recoverable"); }
 
         let maker_payment = match &self.maker_payment {
             Some(tx) => tx.tx_hex.0.clone(),
             None => {
-                let maybe_maker_payment = try_s!(self.maker_coin.check_if_my_payment_sent(
+                let maybe_maker_payment = try_recover!(Temporary, self.maker_coin.check_if_my_payment_sent(
                     self.data.maker_payment_lock as u32,
                     &*self.other_persistent_pub,
                     &*dhash160(&self.data.secret.0),
@@ -657,7 +685,7 @@ impl MakerSwap {
                 ));
                 match maybe_maker_payment {
                     Some(tx) => tx.tx_hex(),
-                    None => return ERR!("Maker payment transaction was not found"),
+                    None => return recover_err!(Unrecoverable, "Maker payment transaction was not found"),
                 }
             }
         };
@@ -669,16 +697,16 @@ impl MakerSwap {
             &maker_payment,
             self.data.maker_coin_start_block,
         ) {
-            Ok(Some(FoundSwapTxSpend::Spent(tx))) => return ERR!("Maker payment was already spent by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
-            Ok(Some(FoundSwapTxSpend::Refunded(tx))) => return ERR!("Maker payment was already refunded by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
-            Err(e) => return ERR!("Error {} when trying to find maker payment spend", e),
+            Ok(Some(FoundSwapTxSpend::Spent(tx))) => return recover_err!(Unrecoverable, "Maker payment was already spent by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
+            Ok(Some(FoundSwapTxSpend::Refunded(tx))) => return recover_err!(Unrecoverable, "Maker payment was already refunded by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
+            Err(e) => return recover_err!(Temporary, "Error {} when trying to find maker payment spend", e),
             Ok(None) => (), // payment is not spent, continue
         }
 
-        if now_ms() / 1000 < self.data.maker_payment_lock + 3700 {
-            return ERR!("Too early to refund, wait until {}", self.data.maker_payment_lock + 3700);
+        if now_ms() / 1000 < self.data.maker_payment_lock + REFUND_DELAY {
+            return recover_err!(Temporary, "Too early to refund, wait until {}", self.data.maker_payment_lock + REFUND_DELAY);
         }
-        let transaction = try_s!(self.maker_coin.send_maker_refunds_payment(
+        let transaction = try_recover!(Temporary, self.maker_coin.send_maker_refunds_payment(
             &maker_payment,
             self.data.maker_payment_lock as u32,
             &*self.other_persistent_pub,
@@ -714,6 +742,10 @@ impl AtomicSwap for MakerSwap {
     fn maker_coin(&self) -> &str { self.maker_coin.ticker() }
 
     fn taker_coin(&self) -> &str { self.taker_coin.ticker() }
+
+    fn swap_type(&self) -> &'static str { "Maker" }
+
+    fn state(&self) -> &str { &self.state }
 }
 
 pub enum MakerSwapCommand {
@@ -844,6 +876,14 @@ impl MakerSavedSwap {
         }
     }
 
+    /// Whether the maker has spent the taker payment, i.e. the coins are exchanged.
+    pub fn is_success(&self) -> bool {
+        self.events.iter().any(|event| match event.event {
+            MakerSwapEvent::TakerPaymentSpent(_) => true,
+            _ => false,
+        })
+    }
+
     pub fn get_my_info(&self) -> Option<MySwapInfo> {
         match self.events.first() {
             Some(event) => match &event.event {
@@ -872,6 +912,17 @@ impl MakerSavedSwap {
         }
     }
 
+    /// The UNIX timestamp in seconds from which the maker payment can be refunded.
+    pub fn recover_funds_after(&self) -> Option<u64> {
+        match self.events.first() {
+            Some(event) => match &event.event {
+                MakerSwapEvent::Started(data) => Some(data.maker_payment_lock + REFUND_DELAY),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
     pub fn is_recoverable(&self) -> bool {
         if !self.is_finished() { return false };
         for event in self.events.iter() {
@@ -913,11 +964,16 @@ pub fn run_maker_swap(swap: MakerSwap, initial_command: Option<MakerSwapCommand>
             };
             unwrap!(save_my_maker_swap_event(&ctx, &uuid, to_save));
             status.status(swap_tags, &event.status_str());
+            publish_swap_event(&ctx, &uuid, "Maker", &event);
             unwrap!(running_swap.write().unwrap().apply_event(event));
         }
         match res.0 {
             Some(c) => { command = c; },
             None => {
+                {
+                    let swap = unwrap!(running_swap.read());
+                    count_finished_swap(&ctx, &*swap, swap.errors.is_empty());
+                }
                 if let Err(e) = broadcast_my_swap_status(&uuid, &ctx) {
                     log!("!broadcast_my_swap_status(" (uuid) "): " (e));
                 }
@@ -1132,7 +1188,10 @@ mod maker_swap_tests {
         let maker_coin = MmCoinEnum::Test(TestCoin {});
         let taker_coin = MmCoinEnum::Test(TestCoin {});
         let (maker_swap, _) = unwrap!(MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, maker_saved_swap));
-        assert!(maker_swap.recover_funds().is_err());
+        match maker_swap.recover_funds() {
+            Err(RecoverFundsError::Unrecoverable(_)) => (),
+            other => panic!("Expected Unrecoverable, got {:?}", other),
+        }
     }
 
     #[test]
@@ -0,0 +1,365 @@
+//! Recovers the funds of my failed swaps in the background.
+//!
+//! The finished swaps having my payment locked (cf. `SavedSwap::is_recoverable`) are rescanned periodically.
+//! Once the payment can be refunded (or the other payment spent) the funds are recovered
+//! and the recovery transaction is recorded to the swap events.
+//!
+//!     {"method": "pending_recoveries"}
+//!
+//! lists the swaps waiting for the recovery along with the time their funds are unlocked at.
+
+use common::json_dir_entries;
+use super::*;
+
+/// How often the saved swaps are rescanned, in seconds.
+const SCAN_INTERVAL: u64 = 60;
+/// The delay before retrying a failed recovery, in seconds, doubled with every next failure.
+const RETRY_DELAY: u64 = 60;
+const MAX_RETRY_DELAY: u64 = 3600;
+/// How many times the details of the recovery transaction are requested before the recording is postponed.
+const TX_DETAILS_ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PendingRecovery {
+    pub uuid: String,
+    /// "Maker" or "Taker".
+    #[serde(rename = "type")]
+    pub swap_type: &'static str,
+    pub maker_coin: String,
+    pub taker_coin: String,
+    /// The UNIX timestamp in seconds from which the funds can be recovered.
+    pub unlock_time: u64,
+    /// The number of the failed recovery attempts.
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// The UNIX timestamp in seconds of the next recovery attempt.
+    pub next_attempt_at: u64,
+}
+
+/// The state of the swap recoveries shared by the `swap_recovery_loop` and the RPCs.
+#[derive(Default)]
+pub struct SwapRecoveries {
+    /// The recoverable swaps by uuid.
+    pending: HashMap<String, PendingRecovery>,
+    /// The swaps being recovered right now.
+    in_progress: HashSet<String>,
+    /// The swaps having nothing to recover (cf. `RecoverFundsError::Unrecoverable`), not retried until the restart.
+    unrecoverable: HashSet<String>,
+    /// The recovered swaps by uuid whose recovery transaction isn't recorded to the swap events yet.
+    unsaved: HashMap<String, UnsavedRecovery>,
+}
+
+#[derive(Clone)]
+struct UnsavedRecovery {
+    is_maker: bool,
+    recovered: RecoveredSwap,
+}
+
+/// Holds the swap in the `SwapRecoveries::in_progress` until dropped.
+struct InProgressGuard {
+    swap_ctx: Arc<SwapsContext>,
+    uuid: String,
+}
+
+impl Drop for InProgressGuard {
+    fn drop(&mut self) {
+        if let Ok(mut recoveries) = self.swap_ctx.recoveries.lock() {
+            recoveries.in_progress.remove(&self.uuid);
+        }
+    }
+}
+
+/// The delay before the next recovery attempt after the number of the failed `attempts`.
+fn retry_delay(attempts: u32) -> u64 {
+    (RETRY_DELAY << attempts.saturating_sub(1).min(6)).min(MAX_RETRY_DELAY)
+}
+
+fn my_recoverable_swaps(ctx: &MmArc) -> Result<Vec<SavedSwap>, String> {
+    let entries = try_s!(json_dir_entries(&my_swaps_dir(ctx)));
+    Ok(entries.iter().filter_map(|entry| match json::from_slice::<SavedSwap>(&slurp(&entry.path())) {
+        Ok(swap) => if swap.is_recoverable() {Some(swap)} else {None},
+        Err(e) => {
+            log!("Error " (e) " parsing JSON from " (entry.path().display()));
+            None
+        },
+    }).collect())
+}
+
+/// Replaces the pending recoveries with the recoverable `swaps`, keeping the failed attempts of the known ones.
+/// The swaps recovered already or having nothing to recover are skipped.
+fn update_pending_recoveries(recoveries: &mut SwapRecoveries, swaps: &[SavedSwap]) {
+    let mut updated = HashMap::new();
+    for swap in swaps {
+        if recoveries.unrecoverable.contains(swap.uuid()) || recoveries.unsaved.contains_key(swap.uuid()) {continue}
+        let unlock_time = match swap.recover_funds_after() {
+            Some(t) => t,
+            None => continue,
+        };
+        let (maker_coin, taker_coin) = match (swap.maker_coin_ticker(), swap.taker_coin_ticker()) {
+            (Ok(maker_coin), Ok(taker_coin)) => (maker_coin, taker_coin),
+            _ => continue,
+        };
+        let recovery = match recoveries.pending.remove(swap.uuid()) {
+            Some(recovery) => recovery,
+            None => PendingRecovery {
+                uuid: swap.uuid().to_owned(),
+                swap_type: match swap {
+                    SavedSwap::Maker(_) => "Maker",
+                    SavedSwap::Taker(_) => "Taker",
+                },
+                maker_coin,
+                taker_coin,
+                unlock_time,
+                attempts: 0,
+                last_error: None,
+                next_attempt_at: unlock_time,
+            },
+        };
+        updated.insert(recovery.uuid.clone(), recovery);
+    }
+    recoveries.pending = updated;
+}
+
+/// Gets the details of the recovery transaction and records it to the swap events, followed by the `Finished` event,
+/// so that the swap is no longer recoverable.
+fn save_recovery(ctx: &MmArc, uuid: &str, recovery: &UnsavedRecovery) -> Result<(), String> {
+    let ticker = &recovery.recovered.coin;
+    let coin = match block_on(lp_coinfind(ctx, ticker)) {
+        Ok(Some(c)) => c,
+        Ok(None) => return ERR!("Coin {} is not activated", ticker),
+        Err(e) => return ERR!("Error {} on {} coin find attempt", e, ticker),
+    };
+    // the transaction was just sent so only transport errors are expected to happen
+    let hash = recovery.recovered.transaction.tx_hash();
+    let mut attempts = 0;
+    let tx_details = loop {
+        match coin.tx_details_by_hash(&hash) {
+            Ok(details) => break details,
+            Err(e) => {
+                attempts += 1;
+                if attempts >= TX_DETAILS_ATTEMPTS || ctx.is_stopping() {
+                    return ERR!("Error {} getting tx details of {:02x}", e, hash);
+                }
+                thread::sleep(Duration::from_secs(10));
+            }
+        }
+    };
+    let action = &recovery.recovered.action;
+    if recovery.is_maker {
+        try_s!(save_recovered_maker_swap(ctx, uuid, action, tx_details));
+    } else {
+        try_s!(save_recovered_taker_swap(ctx, uuid, action, tx_details));
+    }
+    if let Err(e) = broadcast_my_swap_status(uuid, ctx) {
+        log!("!broadcast_my_swap_status(" (uuid) "): " (e));
+    }
+    Ok(())
+}
+
+/// Recovers the funds of my swap and records the recovery transaction to the swap events.
+/// The swap is recovered by one caller at a time, the others get the `RecoverFundsError::Temporary`.
+pub fn recover_swap_funds(ctx: &MmArc, uuid: &str) -> Result<RecoveredSwap, RecoverFundsError> {
+    let swap_ctx = try_recover!(Temporary, SwapsContext::from_ctx(ctx));
+    let _guard = {
+        let mut recoveries = try_recover!(Temporary, swap_ctx.recoveries.lock());
+        if !recoveries.in_progress.insert(uuid.to_owned()) {
+            return recover_err!(Temporary, "The funds of the swap {} are being recovered already", uuid);
+        }
+        InProgressGuard {swap_ctx: swap_ctx.clone(), uuid: uuid.to_owned()}
+    };
+
+    // loading the swap under the guard as it might have been recovered in the meantime
+    let content = slurp(&my_swap_file_path(ctx, uuid));
+    if content.is_empty() {return recover_err!(Unrecoverable, "Swap {} is not found", uuid)}
+    let swap: SavedSwap = try_recover!(Unrecoverable, json::from_slice(&content));
+    let is_maker = match swap {
+        SavedSwap::Maker(_) => true,
+        SavedSwap::Taker(_) => false,
+    };
+    let recovered = swap.recover_funds(ctx.clone())?;
+
+    let recovery = UnsavedRecovery {is_maker, recovered: recovered.clone()};
+    if let Err(e) = save_recovery(ctx, uuid, &recovery) {
+        // the funds are recovered already, the `swap_recovery_loop` retries to record the transaction
+        log!("Error " (e) " saving the recovery of the swap " (uuid));
+        try_recover!(Temporary, swap_ctx.recoveries.lock()).unsaved.insert(uuid.to_owned(), recovery);
+    }
+    Ok(recovered)
+}
+
+/// Recovers the funds of the swaps which are unlocked and not waiting for a retry.
+fn recover_unlocked_swaps(ctx: &MmArc) -> Result<(), String> {
+    let swap_ctx = try_s!(SwapsContext::from_ctx(ctx));
+    let unsaved: Vec<(String, UnsavedRecovery)> = try_s!(swap_ctx.recoveries.lock()).unsaved.iter()
+        .map(|(uuid, recovery)| (uuid.clone(), recovery.clone())).collect();
+    for (uuid, recovery) in unsaved {
+        match save_recovery(ctx, &uuid, &recovery) {
+            Ok(()) => {try_s!(swap_ctx.recoveries.lock()).unsaved.remove(&uuid);},
+            Err(e) => log!("Error " (e) " saving the recovery of the swap " (uuid)),
+        }
+    }
+
+    let swaps = try_s!(my_recoverable_swaps(ctx));
+    let now = now_ms() / 1000;
+    let due: Vec<PendingRecovery> = {
+        let mut recoveries = try_s!(swap_ctx.recoveries.lock());
+        update_pending_recoveries(&mut recoveries, &swaps);
+        recoveries.pending.values().filter(|recovery| recovery.next_attempt_at <= now).cloned().collect()
+    };
+
+    for recovery in due {
+        if ctx.is_stopping() { break }
+        // not counting the attempt, the swap is retried on the next scan after the coins are enabled
+        let inactive_coin = [&recovery.maker_coin, &recovery.taker_coin].iter().find(|ticker|
+            match block_on(lp_coinfind(ctx, ticker)) {
+                Ok(Some(_)) => false,
+                _ => true,
+            }
+        ).cloned();
+        if let Some(ticker) = inactive_coin {
+            if let Some(r) = try_s!(swap_ctx.recoveries.lock()).pending.get_mut(&recovery.uuid) {
+                r.last_error = Some(ERRL!("Coin {} is not activated", ticker));
+            }
+            continue;
+        }
+
+        log!("Recovering the funds of the swap " (recovery.uuid));
+        let res = recover_swap_funds(ctx, &recovery.uuid);
+        let mut recoveries = try_s!(swap_ctx.recoveries.lock());
+        match res {
+            Ok(recovered) => {
+                log!({"Recovered the funds of the swap {}: {:?} {} tx {:02x}",
+                    recovery.uuid, recovered.action, recovered.coin, recovered.transaction.tx_hash()});
+                recoveries.pending.remove(&recovery.uuid);
+            },
+            Err(RecoverFundsError::Unrecoverable(e)) => {
+                log!("Giving up the recovery of the swap " (recovery.uuid) ": " (e));
+                recoveries.pending.remove(&recovery.uuid);
+                recoveries.unrecoverable.insert(recovery.uuid.clone());
+            },
+            Err(RecoverFundsError::Temporary(e)) => {
+                log!("Error " (e) " recovering the funds of the swap " (recovery.uuid));
+                if let Some(r) = recoveries.pending.get_mut(&recovery.uuid) {
+                    r.attempts += 1;
+                    r.last_error = Some(e);
+                    r.next_attempt_at = now_ms() / 1000 + retry_delay(r.attempts);
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Recovers the funds of my failed swaps as soon as they are unlocked, until the MM is stopped.
+pub fn swap_recovery_loop(ctx: MmArc) {
+    let mut next_scan = 0;
+    loop {
+        if ctx.is_stopping() { break }
+        if now_ms() / 1000 >= next_scan {
+            if let Err(e) = recover_unlocked_swaps(&ctx) {
+                log!("!recover_unlocked_swaps: " (e));
+            }
+            next_scan = now_ms() / 1000 + SCAN_INTERVAL;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+pub fn pending_recoveries(ctx: MmArc) -> HyRes {
+    let swaps = try_h!(my_recoverable_swaps(&ctx));
+    let swap_ctx = try_h!(SwapsContext::from_ctx(&ctx));
+    let mut recoveries = try_h!(swap_ctx.recoveries.lock());
+    // the swaps are rescanned in case the recovery loop is disabled or hasn't caught up yet
+    update_pending_recoveries(&mut recoveries, &swaps);
+    let mut result: Vec<PendingRecovery> = recoveries.pending.values().cloned().collect();
+    drop(recoveries);
+    result.sort_by(|a, b| a.unlock_time.cmp(&b.unlock_time).then_with(|| a.uuid.cmp(&b.uuid)));
+    rpc_response(200, json!({
+        "result": result
+    }).to_string())
+}
+
+#[cfg(test)]
+mod swap_recovery_tests {
+    use super::*;
+
+    fn taker_swap_failed_after_payment(uuid: &str, taker_payment_spent: bool) -> SavedSwap {
+        let mut events = vec![
+            json!({"timestamp": 1563743937741u64, "event": {"type": "Started", "data": {
+                "lock_duration": 7800,
+                "maker": "1bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8",
+                "maker_amount": "3.54932734",
+                "maker_coin": "KMD",
+                "maker_coin_start_block": 1452970,
+                "maker_payment_confirmations": 1,
+                "maker_payment_wait": 1563746537,
+                "my_persistent_pub": "03101ace6b08605b9424b0582b5cce044b70a3c8d8d10cb2965e039b0967ae92b9",
+                "started_at": 1563743937,
+                "taker_amount": "0.02",
+                "taker_coin": "ETH",
+                "taker_coin_start_block": 8196380,
+                "taker_payment_confirmations": 1,
+                "taker_payment_lock": 1563751737,
+                "uuid": uuid,
+            }}}),
+        ];
+        if taker_payment_spent {
+            events.push(json!({"timestamp": 1563744500000u64, "event": {"type": "TakerPaymentSpent", "data": {
+                "transaction": unwrap!(json::from_str::<Json>(r#"{"block_height":0,"coin":"ETH","fee_details":null,"from":[],"internal_id":"00","my_balance_change":0,"received_by_me":0,"spent_by_me":0,"timestamp":0,"to":[],"total_amount":0,"tx_hash":"00","tx_hex":"00"}"#)),
+                "secret": "1b8886b8a2cdb62505699400b694ac20f04d7bd4abd80e1ab154aa8d861fc093",
+            }}}));
+            events.push(json!({"timestamp": 1563744500001u64, "event": {"type": "MakerPaymentSpendFailed", "data": {"error": "error"}}}));
+        } else {
+            events.push(json!({"timestamp": 1563744118577u64, "event": {"type": "TakerPaymentWaitForSpendFailed", "data": {"error": "error"}}}));
+        }
+        events.push(json!({"timestamp": 1563744500002u64, "event": {"type": "Finished"}}));
+        unwrap!(json::from_value(json!({
+            "type": "Taker",
+            "uuid": uuid,
+            "events": events,
+            "success_events": [],
+            "error_events": [],
+        })))
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), RETRY_DELAY);
+        assert_eq!(retry_delay(2), RETRY_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_DELAY * 4);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_update_pending_recoveries() {
+        let refund = taker_swap_failed_after_payment("3447b727-fe93-4357-8e5a-8cf2699b7e86", false);
+        let spend = taker_swap_failed_after_payment("41383f43-46a5-478c-9386-3b2cce0aca20", true);
+        assert!(refund.is_recoverable());
+        assert!(spend.is_recoverable());
+        // the refund waits for the locktime, the maker payment can be spent right after the taker payment is spent
+        assert_eq!(refund.recover_funds_after(), Some(1563751737 + REFUND_DELAY));
+        assert_eq!(spend.recover_funds_after(), Some(1563744500));
+
+        let mut recoveries = SwapRecoveries::default();
+        update_pending_recoveries(&mut recoveries, &[refund, spend]);
+        assert_eq!(recoveries.pending.len(), 2);
+        let recovery = &recoveries.pending["3447b727-fe93-4357-8e5a-8cf2699b7e86"];
+        assert_eq!(recovery.swap_type, "Taker");
+        assert_eq!(recovery.maker_coin, "KMD");
+        assert_eq!(recovery.taker_coin, "ETH");
+        assert_eq!(recovery.next_attempt_at, recovery.unlock_time);
+
+        // the failed attempts are kept while the swap is recoverable, the recovered swaps are dropped
+        unwrap!(recoveries.pending.get_mut("3447b727-fe93-4357-8e5a-8cf2699b7e86")).attempts = 2;
+        let refund = taker_swap_failed_after_payment("3447b727-fe93-4357-8e5a-8cf2699b7e86", false);
+        update_pending_recoveries(&mut recoveries, &[refund]);
+        assert_eq!(recoveries.pending.len(), 1);
+        assert_eq!(recoveries.pending["3447b727-fe93-4357-8e5a-8cf2699b7e86"].attempts, 2);
+
+        // the swaps having nothing to recover are not retried
+        recoveries.unrecoverable.insert("3447b727-fe93-4357-8e5a-8cf2699b7e86".into());
+        let refund = taker_swap_failed_after_payment("3447b727-fe93-4357-8e5a-8cf2699b7e86", false);
+        update_pending_recoveries(&mut recoveries, &[refund]);
+        assert!(recoveries.pending.is_empty());
+    }
+}
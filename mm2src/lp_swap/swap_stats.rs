@@ -0,0 +1,304 @@
+//! The market statistics of the pairs, aggregated from the swaps observed in the network
+//! (the `stats_swap_status` data saved by `save_stats_swap`).
+//!
+//! Only the swaps which have exchanged the coins are counted, the time of the trade is the start of the swap.
+//! A swap seen from both the maker and the taker sides is counted once.
+
+use common::json_dir_entries;
+use common::mm_number::{from_dec_to_ratio, from_ratio_to_dec};
+use num_rational::BigRational;
+use num_traits::identities::Zero;
+use std::path::Path;
+use super::*;
+
+/// A swap between the `base` and the `rel` coins of a pair.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairTrade {
+    pub uuid: String,
+    /// In the `rel` coin per the `base` coin.
+    pub price: BigRational,
+    /// In the `base` coin.
+    pub volume: BigRational,
+    /// In the `rel` coin.
+    pub quote_volume: BigRational,
+    /// UNIX timestamp in seconds.
+    pub timestamp: u64,
+}
+
+impl PairTrade {
+    /// The trade of the `base`/`rel` pair, `None` if the swap is of another pair.
+    pub fn from_swap_info(uuid: &str, info: &MySwapInfo, base: &str, rel: &str) -> Option<PairTrade> {
+        let (volume, quote_volume) = if info.my_coin == base && info.other_coin == rel {
+            (&info.my_amount, &info.other_amount)
+        } else if info.my_coin == rel && info.other_coin == base {
+            (&info.other_amount, &info.my_amount)
+        } else {
+            return None
+        };
+        let volume = from_dec_to_ratio(volume.clone());
+        if volume <= BigRational::zero() {return None}
+        let quote_volume = from_dec_to_ratio(quote_volume.clone());
+        Some(PairTrade {
+            uuid: uuid.to_owned(),
+            price: &quote_volume / &volume,
+            volume,
+            quote_volume,
+            timestamp: info.started_at,
+        })
+    }
+}
+
+fn stats_files(dir: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>, String> {
+    let entries = try_s!(json_dir_entries(&dir));
+    Ok(entries.into_iter().map(|entry| {
+        let content = slurp(&entry.path());
+        (entry.path(), content)
+    }).collect())
+}
+
+/// The successful swaps of the `base`/`rel` pair observed in the network, the oldest first.
+fn load_pair_trades(ctx: &MmArc, base: &str, rel: &str) -> Result<Vec<PairTrade>, String> {
+    let mut trades: HashMap<String, PairTrade> = HashMap::new();
+    let mut add = |uuid: &str, info: Option<MySwapInfo>| {
+        if let Some(trade) = info.and_then(|info| PairTrade::from_swap_info(uuid, &info, base, rel)) {
+            trades.entry(trade.uuid.clone()).or_insert(trade);
+        }
+    };
+    for (path, content) in try_s!(stats_files(&stats_maker_swap_dir(ctx))) {
+        match json::from_slice::<MakerSavedSwap>(&content) {
+            Ok(swap) => if swap.is_success() {add(&swap.uuid, swap.get_my_info())},
+            Err(e) => log!("Error " (e) " parsing JSON from " (path.display())),
+        }
+    }
+    for (path, content) in try_s!(stats_files(&stats_taker_swap_dir(ctx))) {
+        match json::from_slice::<TakerSavedSwap>(&content) {
+            Ok(swap) => if swap.is_success() {add(&swap.uuid, swap.get_my_info())},
+            Err(e) => log!("Error " (e) " parsing JSON from " (path.display())),
+        }
+    }
+    let mut trades: Vec<PairTrade> = trades.into_iter().map(|(_, trade)| trade).collect();
+    trades.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.uuid.cmp(&b.uuid)));
+    Ok(trades)
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PairTicker {
+    pub base: String,
+    pub rel: String,
+    /// The price of the latest swap, in the `rel` coin per the `base` coin.
+    pub last_price: Option<BigDecimal>,
+    /// The start of the latest swap, UNIX timestamp in seconds.
+    pub last_swap_at: Option<u64>,
+    pub high_24h: Option<BigDecimal>,
+    pub low_24h: Option<BigDecimal>,
+    /// In the `base` coin.
+    pub volume_24h: BigDecimal,
+    /// In the `rel` coin.
+    pub quote_volume_24h: BigDecimal,
+    pub swaps_24h: usize,
+}
+
+/// The statistics of the `trades` sorted by the time, `now` is a UNIX timestamp in seconds.
+pub fn pair_ticker(base: &str, rel: &str, trades: &[PairTrade], now: u64) -> PairTicker {
+    let day: Vec<&PairTrade> = trades.iter().filter(|trade| trade.timestamp + 24 * 3600 > now).collect();
+    let sum = |f: fn(&PairTrade) -> &BigRational| day.iter().fold(BigRational::zero(), |sum, trade| sum + f(trade));
+    PairTicker {
+        base: base.to_owned(),
+        rel: rel.to_owned(),
+        last_price: trades.last().map(|trade| from_ratio_to_dec(&trade.price)),
+        last_swap_at: trades.last().map(|trade| trade.timestamp),
+        high_24h: day.iter().map(|trade| &trade.price).max().map(from_ratio_to_dec),
+        low_24h: day.iter().map(|trade| &trade.price).min().map(from_ratio_to_dec),
+        volume_24h: from_ratio_to_dec(&sum(|trade| &trade.volume)),
+        quote_volume_24h: from_ratio_to_dec(&sum(|trade| &trade.quote_volume)),
+        swaps_24h: day.len(),
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Candle {
+    /// The start of the interval, UNIX timestamp in seconds.
+    pub timestamp: u64,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    /// In the `base` coin.
+    pub volume: BigDecimal,
+    /// In the `rel` coin.
+    pub quote_volume: BigDecimal,
+    pub swaps: usize,
+}
+
+/// The candles of the `interval` seconds for the `trades` sorted by the time, within the `from`..`to` UNIX timestamps.
+/// The intervals without the trades are skipped.
+pub fn ohlc_candles(trades: &[PairTrade], interval: u64, from: u64, to: u64) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = Vec::new();
+    let mut volumes: Vec<(BigRational, BigRational)> = Vec::new();
+    for trade in trades.iter().filter(|trade| trade.timestamp >= from && trade.timestamp < to) {
+        let timestamp = trade.timestamp - trade.timestamp % interval;
+        let price = from_ratio_to_dec(&trade.price);
+        match candles.last_mut() {
+            Some(ref mut candle) if candle.timestamp == timestamp => {
+                if price > candle.high {candle.high = price.clone()}
+                if price < candle.low {candle.low = price.clone()}
+                candle.close = price;
+                candle.swaps += 1;
+                let (volume, quote_volume) = unwrap!(volumes.last_mut());
+                *volume = &*volume + &trade.volume;
+                *quote_volume = &*quote_volume + &trade.quote_volume;
+            },
+            _ => {
+                candles.push(Candle {
+                    timestamp,
+                    open: price.clone(),
+                    high: price.clone(),
+                    low: price.clone(),
+                    close: price,
+                    volume: 0.into(),
+                    quote_volume: 0.into(),
+                    swaps: 1,
+                });
+                volumes.push((trade.volume.clone(), trade.quote_volume.clone()));
+            },
+        }
+    }
+    for (candle, (volume, quote_volume)) in candles.iter_mut().zip(volumes) {
+        candle.volume = from_ratio_to_dec(&volume);
+        candle.quote_volume = from_ratio_to_dec(&quote_volume);
+    }
+    candles
+}
+
+#[derive(Deserialize)]
+struct TickerReq {
+    base: String,
+    rel: String,
+}
+
+pub fn ticker(ctx: MmArc, req: Json) -> HyRes {
+    let req: TickerReq = try_th!(InvalidRequest, json::from_value(req));
+    if req.base == req.rel {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")));
+    }
+    let trades = try_h!(load_pair_trades(&ctx, &req.base, &req.rel));
+    rpc_response(200, json!({
+        "result": pair_ticker(&req.base, &req.rel, &trades, now_ms() / 1000)
+    }).to_string())
+}
+
+/// The shortest candle interval, in seconds.
+const MIN_OHLC_INTERVAL: u64 = 60;
+
+fn default_ohlc_interval() -> u64 { 3600 }
+
+#[derive(Deserialize)]
+struct OhlcReq {
+    base: String,
+    rel: String,
+    /// The candle interval, in seconds.
+    #[serde(default = "default_ohlc_interval")]
+    interval: u64,
+    /// UNIX timestamp in seconds.
+    from: Option<u64>,
+    /// UNIX timestamp in seconds.
+    to: Option<u64>,
+}
+
+pub fn ohlc(ctx: MmArc, req: Json) -> HyRes {
+    let req: OhlcReq = try_th!(InvalidRequest, json::from_value(req));
+    if req.base == req.rel {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest, ERRL!("Base and rel must be different coins")));
+    }
+    if req.interval < MIN_OHLC_INTERVAL {
+        return rpc_typed_err_response(RpcError::new(RpcErrorCode::InvalidRequest,
+            ERRL!("The interval must be at least {} seconds", MIN_OHLC_INTERVAL)));
+    }
+    let trades = try_h!(load_pair_trades(&ctx, &req.base, &req.rel));
+    let candles = ohlc_candles(&trades, req.interval, req.from.unwrap_or(0), req.to.unwrap_or(u64::max_value()));
+    rpc_response(200, json!({
+        "result": {
+            "base": req.base,
+            "rel": req.rel,
+            "interval": req.interval,
+            "candles": candles,
+        }
+    }).to_string())
+}
+
+#[cfg(test)]
+mod swap_stats_tests {
+    use super::*;
+
+    fn trade(uuid: &str, volume: i64, quote_volume: i64, timestamp: u64) -> PairTrade {
+        let volume = BigRational::from_integer(volume.into());
+        let quote_volume = BigRational::from_integer(quote_volume.into());
+        PairTrade {uuid: uuid.into(), price: &quote_volume / &volume, volume, quote_volume, timestamp}
+    }
+
+    #[test]
+    fn test_pair_trade_from_swap_info() {
+        let info = MySwapInfo {
+            my_coin: "BTC".into(),
+            other_coin: "KMD".into(),
+            my_amount: 1.into(),
+            other_amount: 1000.into(),
+            started_at: 100,
+        };
+        let trade = unwrap!(PairTrade::from_swap_info("1", &info, "KMD", "BTC"));
+        assert_eq!(trade.price, BigRational::new(1.into(), 1000.into()));
+        assert_eq!(trade.volume, BigRational::from_integer(1000.into()));
+        let trade = unwrap!(PairTrade::from_swap_info("1", &info, "BTC", "KMD"));
+        assert_eq!(trade.price, BigRational::from_integer(1000.into()));
+        assert_eq!(trade.timestamp, 100);
+        assert!(PairTrade::from_swap_info("1", &info, "BTC", "ETH").is_none());
+    }
+
+    #[test]
+    fn test_pair_ticker() {
+        let now = 100000;
+        let trades = vec![
+            trade("1", 1, 10, now - 25 * 3600),
+            trade("2", 2, 6, now - 3600),
+            trade("3", 1, 5, now - 60),
+            trade("4", 4, 8, now - 30),
+        ];
+        let ticker = pair_ticker("BASE", "REL", &trades, now);
+        assert_eq!(ticker.last_price, Some(2.into()));
+        assert_eq!(ticker.last_swap_at, Some(now - 30));
+        // the first trade is older than 24h
+        assert_eq!(ticker.high_24h, Some(5.into()));
+        assert_eq!(ticker.low_24h, Some(2.into()));
+        assert_eq!(ticker.volume_24h, 7.into());
+        assert_eq!(ticker.quote_volume_24h, 19.into());
+        assert_eq!(ticker.swaps_24h, 3);
+
+        let ticker = pair_ticker("BASE", "REL", &[], now);
+        assert_eq!(ticker.last_price, None);
+        assert_eq!(ticker.volume_24h, 0.into());
+        assert_eq!(ticker.swaps_24h, 0);
+    }
+
+    #[test]
+    fn test_ohlc_candles() {
+        let trades = vec![
+            trade("1", 1, 3, 3600 + 10),
+            trade("2", 1, 5, 3600 + 20),
+            trade("3", 1, 1, 3600 + 30),
+            trade("4", 2, 4, 3600 + 40),
+            trade("5", 1, 7, 3 * 3600 + 10),
+        ];
+        let candles = ohlc_candles(&trades, 3600, 0, u64::max_value());
+        assert_eq!(candles, vec![
+            Candle {timestamp: 3600, open: 3.into(), high: 5.into(), low: 1.into(), close: 2.into(),
+                volume: 5.into(), quote_volume: 13.into(), swaps: 4},
+            Candle {timestamp: 3 * 3600, open: 7.into(), high: 7.into(), low: 7.into(), close: 7.into(),
+                volume: 1.into(), quote_volume: 7.into(), swaps: 1},
+        ]);
+
+        let candles = ohlc_candles(&trades, 3600, 3600 + 30, 2 * 3600);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].open, 1.into());
+        assert_eq!(candles[0].swaps, 2);
+    }
+}
@@ -5,8 +5,12 @@ use crc::crc32;
 use peers::FixedValidator;
 use super::*;
 
+pub fn stats_taker_swap_dir(ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join("SWAPS").join("STATS").join("TAKER")
+}
+
 pub fn stats_taker_swap_file_path(ctx: &MmArc, uuid: &str) -> PathBuf {
-    ctx.dbdir().join("SWAPS").join("STATS").join("TAKER").join(format!("{}.json", uuid))
+    stats_taker_swap_dir(ctx).join(format!("{}.json", uuid))
 }
 
 fn save_my_taker_swap_event(ctx: &MmArc, uuid: &str, event: TakerSavedEvent) -> Result<(), String> {
@@ -42,6 +46,24 @@ fn save_my_taker_swap_event(ctx: &MmArc, uuid: &str, event: TakerSavedEvent) ->
     }
 }
 
+/// Records the taker payment refunded or the maker payment spent after the swap is finished
+/// (cf. `SavedSwap::recover_funds_and_save`).
+pub fn save_recovered_taker_swap(ctx: &MmArc, uuid: &str, action: &RecoveredSwapAction, tx_details: TransactionDetails)
+                                 -> Result<(), String> {
+    let recovered = match action {
+        RecoveredSwapAction::RefundedMyPayment => TakerSwapEvent::TakerPaymentRefunded(tx_details),
+        RecoveredSwapAction::SpentOtherPayment => TakerSwapEvent::MakerPaymentSpent(tx_details),
+    };
+    for event in vec![recovered, TakerSwapEvent::Finished] {
+        try_s!(save_my_taker_swap_event(ctx, uuid, TakerSavedEvent {
+            timestamp: now_ms(),
+            event: event.clone(),
+        }));
+        publish_swap_event(ctx, uuid, "Taker", &event);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TakerSavedEvent {
     timestamp: u64,
@@ -112,6 +134,14 @@ impl TakerSavedSwap {
         }
     }
 
+    /// Whether the taker has spent the maker payment, i.e. the coins are exchanged.
+    pub fn is_success(&self) -> bool {
+        self.events.iter().any(|event| match event.event {
+            TakerSwapEvent::MakerPaymentSpent(_) => true,
+            _ => false,
+        })
+    }
+
     pub fn get_my_info(&self) -> Option<MySwapInfo> {
         match self.events.first() {
             Some(event) => match &event.event {
@@ -130,6 +160,27 @@ impl TakerSavedSwap {
         }
     }
 
+    /// The UNIX timestamp in seconds from which the funds can be recovered:
+    /// the maker payment can be spent as soon as the maker has spent the taker payment revealing the secret,
+    /// otherwise the taker payment is refunded after its locktime.
+    pub fn recover_funds_after(&self) -> Option<u64> {
+        let data = match self.events.first() {
+            Some(event) => match &event.event {
+                TakerSwapEvent::Started(data) => data,
+                _ => return None,
+            },
+            None => return None,
+        };
+        let taker_payment_spent = self.events.iter().find(|event| match event.event {
+            TakerSwapEvent::TakerPaymentSpent(_) => true,
+            _ => false,
+        });
+        match taker_payment_spent {
+            Some(event) => Some(event.timestamp / 1000),
+            None => Some(data.taker_payment_lock + REFUND_DELAY),
+        }
+    }
+
     pub fn is_recoverable(&self) -> bool {
         if !self.is_finished() { return false };
         for event in self.events.iter() {
@@ -171,11 +222,16 @@ pub fn run_taker_swap(swap: TakerSwap, initial_command: Option<TakerSwapCommand>
             };
             unwrap!(save_my_taker_swap_event(&ctx, &uuid, to_save));
             status.status(swap_tags, &event.status_str());
+            publish_swap_event(&ctx, &uuid, "Taker", &event);
             unwrap!(unwrap!(running_swap.write()).apply_event(event));
         }
         match res.0 {
             Some(c) => { command = c; },
             None => {
+                {
+                    let swap = unwrap!(running_swap.read());
+                    count_finished_swap(&ctx, &*swap, swap.errors.is_empty());
+                }
                 if let Err(e) = broadcast_my_swap_status(&uuid, &ctx) {
                     log!("!broadcast_my_swap_status(" (uuid) "): " (e));
                 }
@@ -228,6 +284,8 @@ pub struct TakerSwap {
     finished_at: u64,
     secret_hash: H160Json,
     secret: H256Json,
+    /// The type of the last applied event.
+    state: String,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -310,6 +368,7 @@ pub enum TakerSwapCommand {
 
 impl TakerSwap {
     fn apply_event(&mut self, event: TakerSwapEvent) -> Result<(), String> {
+        self.state = swap_event_type(&event);
         match event {
             TakerSwapEvent::Started(data) => self.data = data,
             TakerSwapEvent::StartFailed(err) => self.errors.push(err),
@@ -391,6 +450,7 @@ impl TakerSwap {
             errors: vec![],
             secret_hash: H160Json::default(),
             secret: H256Json::default(),
+            state: String::new(),
         }
     }
 
@@ -429,7 +489,7 @@ impl TakerSwap {
             ));
         };
 
-        let lock_duration = lp_atomic_locktime(self.maker_coin.ticker(), self.taker_coin.ticker());
+        let lock_duration = lp_atomic_locktime(&self.ctx.conf, self.maker_coin.ticker(), self.taker_coin.ticker());
         let started_at = now_ms() / 1000;
 
         let maker_coin_start_block = match self.maker_coin.current_block().wait() {
@@ -495,9 +555,12 @@ impl TakerSwap {
 
         let expected_lock_time = maker_data.started_at + self.data.lock_duration * 2;
         if maker_data.payment_locktime != expected_lock_time {
+            // the maker has other block time classes of the coins configured
+            let maker_lock_duration = (maker_data.payment_locktime as i64 - maker_data.started_at as i64) / 2;
             return Ok((
                 Some(TakerSwapCommand::Finish),
-                vec![TakerSwapEvent::NegotiateFailed(ERRL!("maker_data.payment_locktime {} not equal to expected {}", maker_data.payment_locktime, expected_lock_time).into())]
+                vec![TakerSwapEvent::NegotiateFailed(ERRL!("maker_data.payment_locktime {} not equal to expected {}, the maker lock duration {} differs from {}",
+                    maker_data.payment_locktime, expected_lock_time, maker_lock_duration, self.data.lock_duration).into())]
             ))
         }
 
@@ -823,7 +886,7 @@ impl TakerSwap {
         loop {
             // have to wait for 1 hour more because some coins have BIP113 activated so these will reject transactions with locktime == present time
             // https://github.com/bitcoin/bitcoin/blob/master/doc/release-notes/release-notes-0.11.2.md#bip113-mempool-only-locktime-enforcement-using-getmediantimepast
-            if now_ms() / 1000 > self.data.taker_payment_lock + 3700 {
+            if now_ms() / 1000 > self.data.taker_payment_lock + REFUND_DELAY {
                 break;
             }
             std::thread::sleep(Duration::from_secs(10));
@@ -899,16 +962,16 @@ impl TakerSwap {
         }
     }
 
-    pub fn recover_funds(&self) -> Result<RecoveredSwap, String> {
-        if self.finished_at == 0 { return ERR!("Swap must be finished before recover funds attempt"); }
+    pub fn recover_funds(&self) -> Result<RecoveredSwap, RecoverFundsError> {
+        if self.finished_at == 0 { return recover_err!(Temporary, "Swap must be finished before recover funds attempt"); }
 
-        if self.taker_payment_refund.is_some() { return ERR!("Taker payment is refunded, swap is not recoverable"); }
+        if self.taker_payment_refund.is_some() { return recover_err!(Unrecoverable, "Taker payment is refunded, swap is not recoverable"); }
 
-        if self.maker_payment_spend.is_some() { return ERR!("Maker payment is spent, swap is not recoverable"); }
+        if self.maker_payment_spend.is_some() { return recover_err!(Unrecoverable, "Maker payment is spent, swap is not recoverable"); }
 
         let maker_payment = match &self.maker_payment {
             Some(tx) => tx.tx_hex.0.clone(),
-            None => return ERR!("No info about maker payment, swap is not recoverable"),
+            None => return recover_err!(Unrecoverable, "No info about maker payment, swap is not recoverable"),
         };
 
         macro_rules! check_maker_payment_is_not_spent {
@@ -921,9 +984,9 @@ impl TakerSwap {
                     &maker_payment,
                     self.data.maker_coin_start_block,
                 ) {
-                    Ok(Some(FoundSwapTxSpend::Spent(tx))) => return ERR!("Maker payment was already spent by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
-                    Ok(Some(FoundSwapTxSpend::Refunded(tx))) => return ERR!("Maker payment was already refunded by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
-                    Err(e) => return ERR!("Error {} when trying to find maker payment spend", e),
+                    Ok(Some(FoundSwapTxSpend::Spent(tx))) => return recover_err!(Unrecoverable, "Maker payment was already spent by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
+                    Ok(Some(FoundSwapTxSpend::Refunded(tx))) => return recover_err!(Unrecoverable, "Maker payment was already refunded by {} tx {:02x}", self.maker_coin.ticker(), tx.tx_hash()),
+                    Err(e) => return recover_err!(Temporary, "Error {} when trying to find maker payment spend", e),
                     Ok(None) => (), // payment is not spent, continue
                 }
             }
@@ -932,7 +995,7 @@ impl TakerSwap {
         let taker_payment = match &self.taker_payment {
             Some(tx) => tx.tx_hex.0.clone(),
             None => {
-                let maybe_sent = try_s!(self.taker_coin.check_if_my_payment_sent(
+                let maybe_sent = try_recover!(Temporary, self.taker_coin.check_if_my_payment_sent(
                     self.data.taker_payment_lock as u32,
                     &*self.other_persistent_pub,
                     &self.secret_hash.0,
@@ -940,14 +1003,14 @@ impl TakerSwap {
                 ));
                 match maybe_sent {
                     Some(tx) => tx.tx_hex(),
-                    None => return ERR!("Taker payment is not found, swap is not recoverable"),
+                    None => return recover_err!(Unrecoverable, "Taker payment is not found, swap is not recoverable"),
                 }
             }
         };
 
         if self.taker_payment_spend.is_some() {
             check_maker_payment_is_not_spent!();
-            let transaction = try_s!(self.maker_coin.send_taker_spends_maker_payment(
+            let transaction = try_recover!(Temporary, self.maker_coin.send_taker_spends_maker_payment(
                 &maker_payment,
                 self.maker_payment_lock as u32,
                 &*self.other_persistent_pub,
@@ -961,7 +1024,7 @@ impl TakerSwap {
             });
         }
 
-        let taker_payment_spend = try_s!(self.taker_coin.search_for_swap_tx_spend_my(
+        let taker_payment_spend = try_recover!(Temporary, self.taker_coin.search_for_swap_tx_spend_my(
             self.data.taker_payment_lock as u32,
             &*self.other_persistent_pub,
             &self.secret_hash.0,
@@ -973,8 +1036,8 @@ impl TakerSwap {
             Some(spend) => match spend {
                 FoundSwapTxSpend::Spent(tx) => {
                     check_maker_payment_is_not_spent!();
-                    let secret = try_s!(tx.extract_secret());
-                    let transaction = try_s!(self.maker_coin.send_taker_spends_maker_payment(
+                    let secret = try_recover!(Unrecoverable, tx.extract_secret());
+                    let transaction = try_recover!(Temporary, self.maker_coin.send_taker_spends_maker_payment(
                         &maker_payment,
                         self.maker_payment_lock as u32,
                         &*self.other_persistent_pub,
@@ -988,15 +1051,15 @@ impl TakerSwap {
                     })
                 },
                 FoundSwapTxSpend::Refunded(tx) => {
-                   ERR!("Taker payment has been refunded already by transaction {:02x}", tx.tx_hash())
+                   recover_err!(Unrecoverable, "Taker payment has been refunded already by transaction {:02x}", tx.tx_hash())
                 },
             },
             None => {
-                if now_ms() / 1000 < self.data.taker_payment_lock + 3700 {
-                    return ERR!("Too early to refund, wait until {}", self.data.taker_payment_lock + 3700);
+                if now_ms() / 1000 < self.data.taker_payment_lock + REFUND_DELAY {
+                    return recover_err!(Temporary, "Too early to refund, wait until {}", self.data.taker_payment_lock + REFUND_DELAY);
                 }
 
-                let transaction = try_s!(self.taker_coin.send_taker_refunds_payment(
+                let transaction = try_recover!(Temporary, self.taker_coin.send_taker_refunds_payment(
                     &taker_payment,
                     self.data.taker_payment_lock as u32,
                     &*self.other_persistent_pub,
@@ -1005,7 +1068,7 @@ impl TakerSwap {
 
                 Ok(RecoveredSwap {
                     action: RecoveredSwapAction::RefundedMyPayment,
-                    coin: self.maker_coin.ticker().to_string(),
+                    coin: self.taker_coin.ticker().to_string(),
                     transaction,
                 })
             },
@@ -1039,6 +1102,10 @@ impl AtomicSwap for TakerSwap {
     fn maker_coin(&self) -> &str { self.maker_coin.ticker() }
 
     fn taker_coin(&self) -> &str { self.taker_coin.ticker() }
+
+    fn swap_type(&self) -> &'static str { "Taker" }
+
+    fn state(&self) -> &str { &self.state }
 }
 
 #[cfg(test)]
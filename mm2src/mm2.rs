@@ -54,6 +54,7 @@ pub mod lp_ordermatch;
 pub mod lp_swap;
 #[path = "rpc.rs"]
 pub mod rpc;
+use self::rpc::api_keys::validate_api_keys_conf;
 
 #[cfg(any(test, not(feature = "native")))]
 #[path = "mm2_tests.rs"]
@@ -70,6 +71,7 @@ fn lp_main (conf: Json, ctx_cb: &dyn Fn (u32)) -> Result<(), String> {
             return ERR!("rpc_password must not be empty");
         }
     }
+    try_s!(validate_api_keys_conf(&conf));
 
     if conf["passphrase"].is_string() {
         let netid = conf["netid"].as_u64().unwrap_or (0) as u16;
@@ -99,42 +101,68 @@ fn help() {
         "\n"
         "Some (but not all) of the JSON configuration parameters (* - required):\n"
         "\n"
+        "  accept_legacy_price_pings .. Whether to accept the price pings signed with the legacy signature only,\n"
+        "                     which doesn't cover the volume and the uuid of the order. Defaults to true.\n"
+        "  auto_recover_swaps .. Refund (or spend the other payment of) my failed swaps as soon as their locktime passes,\n"
+        "                     see the \"pending_recoveries\" RPC. Defaults to true.\n"
         "  canbind        ..  If > 1000 and < 65536, initializes the `LP_fixed_pairport`.\n"
         // We don't want to break the existing RPC API,
         // so the "refrel=coinmarketcap" designator will act as autoselect,
         // using the CoinGecko behind the scenes unless the "cmc_key" is given.
         // In the future, when MM2 is more widely used and thus we're working more tighly with the GUIs (BarterDEX, HyperDEX, dICO),
         // we might add the "refrel=cmc" and "refrel=coingecko" RPC options.
-/* we're not using the `portfolio` crate at present
-        "  cmc_key        ..  CoinMarketCap Professional API Key. Switches from CoinGecko to CoinMarketCap.\n"
-        "                     The Key can be obtained from 'https://pro.coinmarketcap.com/account'.\n"
-*/
+        "  cmc_key        ..  CoinMarketCap Professional API Key. Switches the external prices of the conditional orders\n"
+        "                     from CoinGecko to CoinMarketCap. The Key can be obtained from 'https://pro.coinmarketcap.com/account'.\n"
         "                     NB: The 'coins' command-line configuration must have the lowercased coin names in the 'name' field,\n"
       r#"                     {"coins": [{"name": "dash", "coin": "DASH", ...}, ...], ...}."# "\n"
         // cf. https://github.com/atomiclabs/hyperdex/blob/1d4ed3234b482e769124725c7e979eef5cd72d24/app/marketmaker/supported-currencies.js#L12
         "  coins          ..  Information about the currencies: their ticker symbols, names, ports, addresses, etc.\n"
         "                     If the field isn't present on the command line then we try loading it from the 'coins' file.\n"
+        "                     The trading fields of a coin: \"block_time_class\" (\"normal\", \"slow\" or \"very_slow\") multiplies\n"
+        "                     the swap payment locktime by 1, 4 or 10 and must be the same on both sides of the swap,\n"
+        "                     \"min_order_volume\" cancels my orders having less than it available (0.00777 by default),\n"
+        "                     \"dust\" is the minimal UTXO output value in satoshis (1000 by default).\n"
         "  crash          ..  Simulate a crash to check how the crash handling works.\n"
         "  dbdir          ..  MM database path. 'DB' by default.\n"
         "  myipaddr       ..  IP address to bind to for P2P networking.\n"
         "  netid          ..  Subnetwork. Affects ports and keys.\n"
+        "  orderbook_limits .. The limits on the orders of the other nodes in the orderbook, the excess ones are dropped:\n"
+      r#"                     {"max_orders_per_pubkey": 100, "max_orders_per_pair": 10, "max_pairs_per_pubkey": 30}."# "\n"
+        "                     The \"min_order_volume\" of the coin configuration drops the orders trading less of the coin,\n"
+        "                     its \"min_price\" and \"max_price\" drop the orders pricing the coin out of the bounds,\n"
+      r#"                     {"coin": "KMD", "min_price": {"BTC": "0.00001"}, "max_price": {"BTC": "0.001"}}."# "\n"
+        "  ordermatch_timeout_ms .. The time the taker order is matched for before it's converted to a maker order\n"
+        "                     (or cancelled), and the maker waits for the taker to connect. Defaults to 30000, at least 10000.\n"
         "  passphrase *   ..  Wallet seed.\n"
         "                     Compressed WIFs and hexadecimal ECDSA keys (prefixed with 0x) are also accepted.\n"
         "  panic          ..  Simulate a panic to see if backtrace works.\n"
+        "  reserved_window_ms .. The time the taker collects the makers' replies for, connecting to the best priced one.\n"
+        "                     Defaults to 1000, capped at 10000. `0` connects to the first matching reply.\n"
         // cf. https://github.com/atomiclabs/hyperdex/pull/563/commits/6d17c0c994693b768e30130855c679a7849a2b27
         "  rpccors        ..  Access-Control-Allow-Origin header value to be used in all the RPC responses.\n"
         "                     Default is currently 'http://localhost:3000'\n"
         "  rpcip          ..  IP address to bind to for RPC server. Overrides the 127.0.0.1 default\n"
         "  rpc_password   ..  RPC password used to authorize non-public RPC calls\n"
         "                     MM generates password from passphrase if this field is not set\n"
+        "  rpc_api_keys   ..  Named RPC keys limited to a set of methods, passed in the \"userpass\" field:\n"
+        "                     [{\"name\": \"dashboard\", \"key\": \"...\", \"methods\": [\"my_balance\", \"orderbook\"]}].\n"
+        "                     \"*\" allows all methods. A key can be revoked at runtime with the \"revoke_api_key\" RPC.\n"
         "  rpc_local_only ..  MM forbids some RPC requests from not loopback (localhost) IPs as additional security measure.\n"
         "                     Defaults to `true`, set `false` to disable. `Use with caution`.\n"
         "  rpcport        ..  If > 1000 overrides the 7783 default.\n"
+        "  rpc_unix_socket .. Serve the RPC on the given Unix domain socket path instead of the TCP port.\n"
+        "                     The socket is only accessible to the owner unless `rpc_unix_socket_mode` (octal, \"600\") is set.\n"
+        "  rpc_tls        ..  Serve the RPC over TLS: {\"cert\": \"cert.pem\", \"key\": \"key.pem\", \"client_ca\": \"ca.pem\"}.\n"
+        "                     With the optional `client_ca` only the clients having a certificate signed by it are accepted.\n"
+        "                     No self-signed certificate is generated, create one with OpenSSL:\n"
+        "                     openssl req -x509 -newkey rsa:2048 -nodes -keyout key.pem -out cert.pem -days 365 -subj /CN=localhost\n"
         "  i_am_seed      ..  Activate the seed node mode (acting as a relay for mm2 clients).\n"
         "                     Defaults to `false`.\n"
         "  seednodes      ..  Seednode IPs that node will use.\n"
         "                     At least one seed IP must be present if the node is not a seed itself.\n"
         "  stderr         ..  Print a message to stderr and exit.\n"
+        "  taker_only     ..  The default of the \"taker_only\" buy and sell parameter: the unmatched taker orders are cancelled\n"
+        "                     instead of being converted to the maker orders. Defaults to false.\n"
         "  userhome       ..  System home directory of a user ('/root' by default).\n"
         "  wif            ..  `1` to add WIFs to the information we provide about a coin.\n"
         "\n"
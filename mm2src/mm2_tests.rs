@@ -126,12 +126,61 @@ fn test_rpc() {
     }))));
     assert_eq!(help.0, StatusCode::OK);
     assert_eq!((help.2)[ACCESS_CONTROL_ALLOW_ORIGIN], "http://localhost:4000");
+    assert!(help.1.contains("setprice(base, rel, price"), "{}", help.1);
+
+    let schema = unwrap! (block_on (mm.rpc (json! ({
+        "method": "rpc_schema",
+    }))));
+    assert_eq!(schema.0, StatusCode::OK, "{}", schema.1);
+    let schema: Json = unwrap!(json::from_str(&schema.1));
+    assert_eq!(schema["result"]["methods"]["withdraw"]["request"]["required"], json!(["method", "userpass", "coin", "to"]));
 
     unwrap! (block_on (mm.stop()));
     // unwrap! (mm.wait_for_log (9., &|log| log.contains ("on_stop] firing shutdown_tx!")));
     // TODO (workaround libtorrent hanging in delete) // unwrap! (mm.wait_for_log (9., &|log| log.contains ("LogState] Bye!")));
 }
 
+#[test]
+fn test_jsonrpc() {
+    let (_, mut mm, _dump_log, _dump_dashboard) = mm_spat (local_start(), &identity);
+    unwrap! (block_on (mm.wait_for_log (19., |log| log.contains (">>>>>>>>> DEX stats "))));
+
+    let version = unwrap! (block_on (mm.rpc (json! ({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "version",
+        "userpass": mm.userpass,
+    }))));
+    assert_eq! (version.0, StatusCode::OK, "JSON-RPC «version» failed with status «{}», response «{}»", version.0, version.1);
+    let version: Json = unwrap! (json::from_str (&version.1));
+    assert_eq! (version["id"], 1);
+    assert! (version["result"].is_string(), "Unexpected response {}", version);
+
+    let batch = unwrap! (block_on (mm.rpc (json! ([
+        {"jsonrpc": "2.0", "id": "a", "method": "get_enabled_coins", "params": {"userpass": mm.userpass}},
+        {"jsonrpc": "2.0", "id": "b", "method": "unknown_method", "userpass": mm.userpass},
+        {"jsonrpc": "2.0", "id": "c", "method": "get_enabled_coins", "userpass": "invalid"},
+        {"jsonrpc": "2.0", "method": "version", "userpass": mm.userpass},
+        {"jsonrpc": "1.0", "id": "d", "method": "version"},
+    ]))));
+    assert_eq! (batch.0, StatusCode::OK, "JSON-RPC batch failed with status «{}», response «{}»", batch.0, batch.1);
+    let batch: Vec<Json> = unwrap! (json::from_str (&batch.1));
+    // The notification (request without an "id") has no response.
+    assert_eq! (batch.len(), 4);
+    assert_eq! (batch[0]["id"], "a");
+    assert! (batch[0]["result"].is_array(), "Unexpected response {}", batch[0]);
+    assert_eq! (batch[1]["error"]["code"], -32601);
+    assert_eq! (batch[2]["error"]["code"], -32001);
+    assert_eq! (batch[3]["error"]["code"], -32600);
+
+    let not_json = unwrap! (mm.rpc_str (r#"{"jsonrpc": "2.0", "method": "#));
+    assert_eq! (not_json.0, StatusCode::OK);
+    let not_json: Json = unwrap! (json::from_str (&not_json.1));
+    assert_eq! (not_json["error"]["code"], -32700);
+
+    unwrap! (block_on (mm.stop()));
+}
+
 /// This is not a separate test but a helper used by `MarketMakerIt` to run the MarketMaker from the test binary.
 #[test]
 fn test_mm_start() {
@@ -625,6 +674,167 @@ fn test_rpc_password_from_json_no_userpass() {
     assert! (electrum.0.is_server_error(), "RPC «electrum» should have failed with server error, but got «{}», response «{}»", electrum.0, electrum.1);
 }
 
+#[test]
+fn test_rpc_api_keys() {
+    let coins = json!([
+        {"coin":"BEER","asset":"BEER","rpcport":8923,"txversion":4},
+    ]);
+
+    let mut mm = unwrap! (MarketMakerIt::start (
+        json! ({
+            "gui": "nogui",
+            "netid": 9998,
+            "passphrase": "bob passphrase",
+            "coins": coins,
+            "rpc_password": "password",
+            "rpc_api_keys": [
+                {"name": "dashboard", "key": "dashboard_key", "methods": ["get_enabled_coins", "my_orders"]},
+            ],
+            "i_am_seed": true,
+        }),
+        "password".into(),
+        local_start! ("bob")
+    ));
+    let (_dump_log, _dump_dashboard) = mm_dump (&mm.log_path);
+    log!({"Log path: {}", mm.log_path.display()});
+    unwrap! (block_on (mm.wait_for_log (22., |log| log.contains (">>>>>>>>> DEX stats "))));
+
+    let enabled = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": "dashboard_key",
+        "method": "get_enabled_coins",
+    }))));
+    assert_eq! (enabled.0, StatusCode::OK, "RPC «get_enabled_coins» failed with status «{}», response «{}»", enabled.0, enabled.1);
+
+    // the key is not allowed to call the methods outside of its list
+    let cancel = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": "dashboard_key",
+        "method": "cancel_all_orders",
+        "cancel_by": {"type": "All"},
+    }))));
+    assert! (cancel.0.is_server_error(), "RPC «cancel_all_orders» should have failed with server error, but got «{}», response «{}»", cancel.0, cancel.1);
+    assert! (cancel.1.contains ("is not allowed to call"), "Unexpected response «{}»", cancel.1);
+
+    let list = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": mm.userpass,
+        "method": "list_api_keys",
+    }))));
+    assert_eq! (list.0, StatusCode::OK, "RPC «list_api_keys» failed with status «{}», response «{}»", list.0, list.1);
+    assert! (!list.1.contains ("dashboard_key"), "The key must not be displayed, response «{}»", list.1);
+
+    let revoke = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": mm.userpass,
+        "method": "revoke_api_key",
+        "name": "dashboard",
+    }))));
+    assert_eq! (revoke.0, StatusCode::OK, "RPC «revoke_api_key» failed with status «{}», response «{}»", revoke.0, revoke.1);
+
+    let enabled = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": "dashboard_key",
+        "method": "get_enabled_coins",
+    }))));
+    assert! (enabled.0.is_server_error(), "RPC «get_enabled_coins» should have failed with server error, but got «{}», response «{}»", enabled.0, enabled.1);
+
+    unwrap! (block_on (mm.stop()));
+}
+
+#[test]
+#[cfg(feature = "native")]
+fn test_metrics() {
+    use common::wio::slurp_req;
+    use futures01::Future;
+    use http::Request;
+
+    let mut mm = unwrap! (MarketMakerIt::start (
+        json! ({
+            "gui": "nogui",
+            "netid": 9998,
+            "passphrase": "bob passphrase",
+            "coins": [],
+            "rpc_password": "password",
+            "i_am_seed": true,
+        }),
+        "password".into(),
+        local_start! ("bob")
+    ));
+    let (_dump_log, _dump_dashboard) = mm_dump (&mm.log_path);
+    log!({"Log path: {}", mm.log_path.display()});
+    unwrap! (block_on (mm.wait_for_log (22., |log| log.contains (">>>>>>>>> DEX stats "))));
+
+    let orders = unwrap! (block_on (mm.rpc (json! ({
+        "userpass": mm.userpass,
+        "method": "my_orders",
+    }))));
+    assert_eq! (orders.0, StatusCode::OK, "RPC «my_orders» failed with status «{}», response «{}»", orders.0, orders.1);
+
+    let metrics = |token: &str| {
+        let request = unwrap! (Request::builder().method ("GET") .uri (fomat! ("http://" (mm.ip) ":7783/metrics"))
+            .header ("Authorization", fomat! ("Bearer " (token))) .body (Vec::new()));
+        let (status, _headers, body) = unwrap! (slurp_req (request) .wait());
+        (status, unwrap! (String::from_utf8 (body)))
+    };
+
+    let (status, body) = metrics (&mm.userpass);
+    assert_eq! (status, StatusCode::OK, "/metrics failed with status «{}», response «{}»", status, body);
+    assert! (body.contains ("# TYPE mm2_swaps_active gauge"), "Unexpected /metrics response «{}»", body);
+    assert! (body.contains ("mm2_rpc_request_duration_seconds_count{method=\"my_orders\"} 1"), "Unexpected /metrics response «{}»", body);
+
+    let (status, body) = metrics ("wrong password");
+    assert! (status.is_server_error(), "/metrics should have failed with the wrong password, but got «{}», response «{}»", status, body);
+
+    unwrap! (block_on (mm.stop()));
+}
+
+#[test]
+#[cfg(all(feature = "native", unix))]
+fn test_rpc_unix_socket() {
+    use std::io::{Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+
+    // NB: The socket path is limited to about 100 bytes, hence the short name in the temporary folder.
+    let socket_path = std::env::temp_dir().join (format! ("mm2_{}.sock", common::now_ms()));
+    let mm = unwrap! (MarketMakerIt::start (
+        json! ({
+            "gui": "nogui",
+            "netid": 9998,
+            "passphrase": "bob passphrase",
+            "coins": [],
+            "rpc_password": "password",
+            "rpc_unix_socket": socket_path,
+            "i_am_seed": true,
+        }),
+        "password".into(),
+        local_start! ("bob")
+    ));
+    let (_dump_log, _dump_dashboard) = mm_dump (&mm.log_path);
+    log!({"Log path: {}", mm.log_path.display()});
+    unwrap! (block_on (mm.wait_for_log (22., |log| log.contains ("RPC is served over the Unix socket"))));
+
+    let mode = unwrap! (std::fs::metadata (&socket_path)) .permissions().mode();
+    assert_eq! (mode & 0o777, 0o600);
+
+    let rpc = |body: Json| {
+        let body = body.to_string();
+        let mut stream = unwrap! (UnixStream::connect (&socket_path));
+        unwrap! (write! (stream, "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+        let mut res = String::new();
+        unwrap! (stream.read_to_string (&mut res));
+        res
+    };
+
+    let version = rpc (json! ({"method": "version"}));
+    assert! (version.starts_with ("HTTP/1.1 200"), "Unexpected response «{}»", version);
+
+    // The private methods are available to the socket clients, the userpass is still required.
+    let orders = rpc (json! ({"userpass": mm.userpass, "method": "my_orders"}));
+    assert! (orders.starts_with ("HTTP/1.1 200"), "Unexpected response «{}»", orders);
+    let orders = rpc (json! ({"userpass": "wrong", "method": "my_orders"}));
+    assert! (orders.starts_with ("HTTP/1.1 500"), "Unexpected response «{}»", orders);
+
+    let stop = rpc (json! ({"userpass": mm.userpass, "method": "stop"}));
+    assert! (stop.starts_with ("HTTP/1.1 200"), "Unexpected response «{}»", stop);
+}
+
 /// Helper function requesting my swap status and checking it's events
 async fn check_my_swap_status(
     mm: &MarketMakerIt,
@@ -1248,6 +1458,9 @@ fn test_swap_status() {
     }))));
 
     assert_eq! (my_swap.0, StatusCode::NOT_FOUND, "!not found status code: {}", my_swap.1);
+    let err: Json = unwrap! (json::from_str (&my_swap.1));
+    assert_eq! (err["error_code"], "SwapNotFound");
+    assert_eq! (err["error_category"], "swap");
 
     let stats_swap = unwrap! (block_on (mm.rpc (json! ({
         "userpass": mm.userpass,
@@ -1296,6 +1509,8 @@ fn test_order_errors_when_base_equal_rel() {
         "price": 0.9
     }))));
     assert! (rc.0.is_server_error(), "setprice should have failed, but got {:?}", rc);
+    let err: Json = unwrap! (json::from_str (&rc.1));
+    assert_eq! (err["error_code"], "InvalidRequest");
 
     let rc = unwrap! (block_on (mm.rpc (json! ({
         "userpass": mm.userpass,
@@ -1306,6 +1521,8 @@ fn test_order_errors_when_base_equal_rel() {
         "relvolume": 0.1,
     }))));
     assert! (rc.0.is_server_error(), "buy should have failed, but got {:?}", rc);
+    let err: Json = unwrap! (json::from_str (&rc.1));
+    assert_eq! (err["error_code"], "InvalidRequest");
 
     let rc = unwrap! (block_on (mm.rpc (json! ({
         "userpass": mm.userpass,
@@ -1316,6 +1533,8 @@ fn test_order_errors_when_base_equal_rel() {
         "basevolume": 0.1,
     }))));
     assert! (rc.0.is_server_error(), "sell should have failed, but got {:?}", rc);
+    let err: Json = unwrap! (json::from_str (&rc.1));
+    assert_eq! (err["error_code"], "InvalidRequest");
 }
 
 fn startup_passphrase(passphrase: &str, expected_address: &str) {
@@ -1556,6 +1775,20 @@ fn test_cancel_order() {
     }))));
     assert!(cancel_rc.0.is_success(), "!cancel_order: {}", rc.1);
 
+    // the cancelled order is kept in the history
+    let rc = unwrap! (block_on (mm_bob.rpc (json! ({
+        "userpass": mm_bob.userpass,
+        "method": "orders_history",
+        "base": "BEER",
+        "status": "Cancelled",
+    }))));
+    assert!(rc.0.is_success(), "!orders_history: {}", rc.1);
+    let history: Json = unwrap!(json::from_str(&rc.1));
+    let orders = unwrap!(history["result"]["orders"].as_array());
+    assert_eq!(orders.len(), 1, "{}", rc.1);
+    assert_eq!(orders[0]["uuid"], setprice_json["result"]["uuid"]);
+    assert_eq!(orders[0]["cancellation_reason"], "Cancelled");
+
     thread::sleep(Duration::from_secs(11));
 
     // Bob orderbook must show no orders
@@ -1636,6 +1869,137 @@ fn test_electrum_enable_conn_errors() {
     ]));
 }
 
+#[test]
+fn test_update_maker_order() {
+    let coins = json!([
+        {"coin":"RICK","asset":"RICK"},
+        {"coin":"MORTY","asset":"MORTY"},
+    ]);
+
+    // start bob and immediately place the order
+    let mut mm_bob = unwrap! (MarketMakerIt::start (
+        json! ({
+            "gui": "nogui",
+            "netid": 9998,
+            "dht": "on",  // Enable DHT without delay.
+            "myipaddr": env::var ("BOB_TRADE_IP") .ok(),
+            "rpcip": env::var ("BOB_TRADE_IP") .ok(),
+            "canbind": env::var ("BOB_TRADE_PORT") .ok().map (|s| unwrap! (s.parse::<i64>())),
+            "passphrase": "bob passphrase",
+            "coins": coins,
+            "i_am_seed": true,
+            "rpc_password": "pass",
+        }),
+        "pass".into(),
+        match var ("LOCAL_THREAD_MM") {Ok (ref e) if e == "bob" => Some (local_start()), _ => None}
+    ));
+    let (_bob_dump_log, _bob_dump_dashboard) = mm_dump (&mm_bob.log_path);
+    log!({"Bob log path: {}", mm_bob.log_path.display()});
+    unwrap! (block_on (mm_bob.wait_for_log (22., |log| log.contains (">>>>>>>>> DEX stats "))));
+
+    log!("Bob enable RICK " [block_on(enable_electrum(&mm_bob, "RICK", vec![
+        "electrum3.cipig.net:10017",
+        "electrum2.cipig.net:10017",
+        "electrum1.cipig.net:10017",
+    ]))]);
+
+    log!("Bob enable MORTY " [block_on(enable_electrum(&mm_bob, "MORTY", vec![
+        "electrum3.cipig.net:10018",
+        "electrum2.cipig.net:10018",
+        "electrum1.cipig.net:10018",
+    ]))]);
+
+    let mut mm_alice = unwrap! (MarketMakerIt::start (
+        json! ({
+            "gui": "nogui",
+            "netid": 9998,
+            "myipaddr": env::var ("ALICE_TRADE_IP") .ok(),
+            "rpcip": env::var ("ALICE_TRADE_IP") .ok(),
+            "passphrase": "alice passphrase",
+            "coins": coins,
+            "seednodes": [fomat!((mm_bob.ip))],
+            "rpc_password": "pass",
+        }),
+        "pass".into(),
+        match var ("LOCAL_THREAD_MM") {Ok (ref e) if e == "alice" => Some (local_start()), _ => None}
+    ));
+
+    let (_alice_dump_log, _alice_dump_dashboard) = mm_dump (&mm_alice.log_path);
+    log!({"Alice log path: {}", mm_alice.log_path.display()});
+
+    unwrap! (block_on (mm_alice.wait_for_log (22., |log| log.contains (">>>>>>>>> DEX stats "))));
+
+    log!("Alice enable RICK " [block_on(enable_electrum(&mm_alice, "RICK", vec![
+        "electrum3.cipig.net:10017",
+        "electrum2.cipig.net:10017",
+        "electrum1.cipig.net:10017",
+    ]))]);
+
+    log!("Alice enable MORTY " [block_on(enable_electrum(&mm_alice, "MORTY", vec![
+        "electrum3.cipig.net:10018",
+        "electrum2.cipig.net:10018",
+        "electrum1.cipig.net:10018",
+    ]))]);
+
+    log!("Issue bob sell request");
+    let rc = unwrap! (block_on (mm_bob.rpc (json! ({
+        "userpass": mm_bob.userpass,
+        "method": "setprice",
+        "base": "RICK",
+        "rel": "MORTY",
+        "price": 0.9,
+        "volume": "0.9",
+    }))));
+    assert! (rc.0.is_success(), "!setprice: {}", rc.1);
+    let setprice: Json = unwrap!(json::from_str(&rc.1));
+    let uuid = setprice["result"]["uuid"].clone();
+
+    thread::sleep(Duration::from_secs(12));
+
+    log!("Update the bob order");
+    let rc = unwrap! (block_on (mm_bob.rpc (json! ({
+        "userpass": mm_bob.userpass,
+        "method": "update_maker_order",
+        "uuid": uuid,
+        "price": "1.1",
+        "min_volume": "0.5",
+    }))));
+    assert! (rc.0.is_success(), "!update_maker_order: {}", rc.1);
+    let updated: Json = unwrap!(json::from_str(&rc.1));
+    assert_eq!(updated["result"]["uuid"], uuid);
+
+    let rc = unwrap! (block_on (mm_bob.rpc (json! ({
+        "userpass": mm_bob.userpass,
+        "method": "update_maker_order",
+        "uuid": uuid,
+        "min_volume": "1",
+    }))));
+    assert! (rc.0.is_server_error(), "update_maker_order should have failed, but got {:?}", rc);
+    let err: Json = unwrap! (json::from_str (&rc.1));
+    assert_eq! (err["error_code"], "InvalidRequest");
+
+    // the update is broadcasted right away, not waiting for the next 10 seconds tick
+    thread::sleep(Duration::from_secs(3));
+
+    let rc = unwrap! (block_on (mm_alice.rpc (json! ({
+            "userpass": mm_alice.userpass,
+            "method": "orderbook",
+            "base": "RICK",
+            "rel": "MORTY",
+        }))));
+    assert!(rc.0.is_success(), "!orderbook: {}", rc.1);
+
+    let alice_orderbook: Json = unwrap!(json::from_str(&rc.1));
+    log!("Alice orderbook " [alice_orderbook]);
+    let asks = unwrap!(alice_orderbook["asks"].as_array());
+    assert_eq!(asks.len(), 1, "Alice RICK/MORTY orderbook must have exactly 1 ask");
+    assert_eq!(asks[0]["price"], Json::from("1.1"));
+    assert_eq!(asks[0]["min_volume"], Json::from("0.5"));
+
+    unwrap! (block_on (mm_bob.stop()));
+    unwrap! (block_on (mm_alice.stop()));
+}
+
 #[test]
 fn test_order_should_not_be_displayed_when_node_is_down() {
     let coins = json!([
@@ -1,4 +1,5 @@
 use common::mm_ctx::{MmArc, MmCtxBuilder};
+use common::privkey::key_pair_from_seed;
 use mocktopus::mocking::*;
 use super::*;
 
@@ -16,7 +17,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -31,6 +34,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 20.into(),
         rel_amount_rat: Some(BigRational::from_integer(20.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -49,7 +53,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -64,6 +70,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 20.into(),
         rel_amount_rat: Some(BigRational::from_integer(20.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -82,7 +89,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -97,6 +106,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -115,7 +125,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -130,6 +142,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -148,7 +161,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::new(1.into(), 2.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -163,6 +178,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -181,7 +197,9 @@ fn test_match_maker_order_and_taker_request() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
 
     let request = TakerRequest {
@@ -196,6 +214,7 @@ fn test_match_maker_order_and_taker_request() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let actual = match_order_and_request(&maker, &request);
@@ -217,7 +236,9 @@ fn test_maker_order_available_amount() {
         price_rat: BigRational::from_integer(1.into()),
         matches: HashMap::new(),
         started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
         uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
     };
     maker.matches.insert(Uuid::new_v4(), MakerMatch {
         request: TakerRequest {
@@ -232,6 +253,7 @@ fn test_maker_order_available_amount() {
             dest_pub_key: H256Json::default(),
             method: "request".into(),
             action: TakerAction::Buy,
+            partial_fill: false,
         },
         reserved: MakerReserved {
             method: "reserved".into(),
@@ -263,6 +285,7 @@ fn test_maker_order_available_amount() {
             dest_pub_key: H256Json::default(),
             method: "request".into(),
             action: TakerAction::Buy,
+            partial_fill: false,
         },
         reserved: MakerReserved {
             method: "reserved".into(),
@@ -303,11 +326,15 @@ fn test_taker_match_reserved() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -340,11 +367,15 @@ fn test_taker_match_reserved() {
         rel_amount: 10.into(),
         rel_amount_rat: Some(BigRational::from_integer(10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -376,11 +407,15 @@ fn test_taker_match_reserved() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -412,11 +447,15 @@ fn test_taker_match_reserved() {
         rel_amount: "0.9".parse().unwrap(),
         rel_amount_rat: Some(BigRational::new(9.into(), 10.into())),
         action: TakerAction::Sell,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -448,11 +487,15 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -484,11 +527,15 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: None,
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -520,11 +567,15 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -556,11 +607,15 @@ fn test_taker_match_reserved() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -590,12 +645,16 @@ fn test_taker_match_reserved() {
             rel_amount: 1.into(),
             rel_amount_rat: Some(BigRational::from_integer(1.into())),
             action: TakerAction::Buy,
+            partial_fill: false,
             uuid,
             method: "request".into(),
             sender_pubkey: H256Json::default(),
             dest_pub_key: H256Json::default(),
         },
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
     };
 
     let reserved = MakerReserved {
@@ -629,11 +688,15 @@ fn test_taker_order_cancellable() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -651,11 +714,15 @@ fn test_taker_order_cancellable() {
         rel_amount: 2.into(),
         rel_amount_rat: Some(BigRational::from_integer(2.into())),
         action: TakerAction::Buy,
+        partial_fill: false,
     };
 
     let mut order = TakerOrder {
         request,
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms()
     };
 
@@ -682,6 +749,7 @@ fn test_taker_order_cancellable() {
                 dest_pub_key: H256Json::default(),
                 maker_order_uuid: Uuid::new_v4(),
                 taker_order_uuid: Uuid::new_v4(),
+                swap_uuid: None,
             },
             connected: None,
         }
@@ -708,6 +776,8 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
     });
     maker_orders.insert(Uuid::from_bytes([1; 16]), MakerOrder {
         uuid: Uuid::from_bytes([1; 16]),
@@ -722,6 +792,8 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
     });
     maker_orders.insert(Uuid::from_bytes([2; 16]), MakerOrder {
         uuid: Uuid::from_bytes([2; 16]),
@@ -736,15 +808,21 @@ fn prepare_for_cancel_by(ctx: &MmArc) {
         price: 0.into(),
         price_rat: BigRational::from_integer(0.into()),
         started_swaps: vec![],
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
     });
     taker_orders.insert(Uuid::from_bytes([3; 16]), TakerOrder {
         matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
         created_at: now_ms(),
         request: TakerRequest {
             base: "ETOMIC".into(),
             rel: "BEER".into(),
             uuid: Uuid::from_bytes([3; 16]),
             action: TakerAction::Buy,
+            partial_fill: false,
             base_amount: 0.into(),
             base_amount_rat: Some(BigRational::from_integer(0.into())),
             rel_amount: 0.into(),
@@ -815,3 +893,990 @@ fn test_cancel_by_all() {
     assert!(cancelled.contains(&Uuid::from_bytes([2; 16])));
     assert!(cancelled.contains(&Uuid::from_bytes([3; 16])));
 }
+
+#[test]
+fn test_time_in_force() {
+    let gtt = TimeInForce::GoodTillTime { expires_at: 1000 };
+    assert!(!gtt.is_expired(999));
+    assert!(gtt.is_expired(1000));
+    assert!(!TimeInForce::GoodTillCancelled.is_expired(u64::max_value()));
+    assert!(TimeInForce::ImmediateOrCancel.is_immediate());
+    assert!(TimeInForce::FillOrKill.is_immediate());
+    assert!(!gtt.is_immediate());
+
+    // the expiry in the past is rejected, the immediate policies are available to the takers only
+    assert!(gtt.check(true).is_err());
+    let future = TimeInForce::GoodTillTime { expires_at: now_ms() / 1000 + 3600 };
+    assert!(future.check(false).is_ok());
+    assert!(TimeInForce::ImmediateOrCancel.check(true).is_ok());
+    let err = unwrap_err!(TimeInForce::FillOrKill.check(false));
+    assert_eq!(err.code, RpcErrorCode::InvalidRequest);
+
+    let parsed: TimeInForce = unwrap!(json::from_value(json!({"type": "GoodTillTime", "expires_at": 1000})));
+    assert_eq!(parsed, gtt);
+}
+
+#[test]
+fn test_time_in_force_persisted() {
+    let order = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: Uuid::new_v4(),
+        time_in_force: TimeInForce::GoodTillTime { expires_at: 1000 },
+        allowed_pubkeys: Vec::new(),
+    };
+    let mut saved = unwrap!(json::to_value(&order));
+    let restored: MakerOrder = unwrap!(json::from_value(saved.clone()));
+    assert_eq!(restored.time_in_force, order.time_in_force);
+
+    // the orders saved by the previous versions are good till cancelled
+    unwrap!(saved.as_object_mut()).remove("time_in_force");
+    let restored: MakerOrder = unwrap!(json::from_value(saved));
+    assert_eq!(restored.time_in_force, TimeInForce::GoodTillCancelled);
+
+    // the taker order converted to maker keeps the expiry
+    let taker = TakerOrder {
+        created_at: now_ms(),
+        matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillTime { expires_at: 1000 },
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
+        request: TakerRequest {
+            base: "BASE".into(),
+            rel: "REL".into(),
+            uuid: Uuid::new_v4(),
+            action: TakerAction::Sell,
+            partial_fill: false,
+            base_amount: 10.into(),
+            base_amount_rat: Some(BigRational::from_integer(10.into())),
+            rel_amount: 10.into(),
+            rel_amount_rat: Some(BigRational::from_integer(10.into())),
+            dest_pub_key: H256Json::default(),
+            method: "request".into(),
+            sender_pubkey: H256Json::default(),
+        },
+    };
+    let maker: MakerOrder = taker.into();
+    assert_eq!(maker.time_in_force, TimeInForce::GoodTillTime { expires_at: 1000 });
+}
+
+fn price_ping(price: BigRational, balance: BigRational, min_volume: Option<BigRational>) -> PricePingRequest {
+    PricePingRequest {
+        method: "postprice".into(),
+        pubkey: "".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        price: from_ratio_to_dec(&price),
+        price_rat: Some(price),
+        price64: "0".into(),
+        timestamp: now_ms() / 1000,
+        pubsecp: "".into(),
+        sig: "".into(),
+        sig_v2: None,
+        balance: from_ratio_to_dec(&balance),
+        balance_rat: Some(balance),
+        uuid: Some(Uuid::new_v4()),
+        min_volume: min_volume.as_ref().map(from_ratio_to_dec),
+        min_volume_rat: min_volume,
+        first_seen: 0,
+    }
+}
+
+/// The `price_ping` of the `pubkey` maker order selling the `balance` of the `base` for the `rel` at the `price`.
+fn price_ping_for(pubkey: &str, base: &str, rel: &str, price: BigRational, balance: BigRational) -> PricePingRequest {
+    let mut ping = price_ping(price, balance, None);
+    ping.pubkey = pubkey.into();
+    ping.base = base.into();
+    ping.rel = rel.into();
+    ping
+}
+
+fn add_to_orderbook(orderbook: &mut HashMap<(String, String), HashMap<Uuid, PricePingRequest>>, ping: PricePingRequest) {
+    orderbook.entry((ping.base.clone(), ping.rel.clone())).or_insert_with(HashMap::new).insert(unwrap!(ping.uuid), ping);
+}
+
+fn orderbook_of(pings: Vec<PricePingRequest>) -> HashMap<(String, String), HashMap<Uuid, PricePingRequest>> {
+    let mut orderbook = HashMap::new();
+    for ping in pings {add_to_orderbook(&mut orderbook, ping)}
+    orderbook
+}
+
+fn int(i: i64) -> BigRational { BigRational::from_integer(i.into()) }
+
+#[test]
+fn test_check_min_volume_of_matching_orders() {
+    let mut orders = HashMap::new();
+    orders.insert(Uuid::new_v4(), price_ping(int(1), int(100), Some(int(10))));
+    orders.insert(Uuid::new_v4(), price_ping(int(2), int(100), Some(int(5))));
+    // the order of the node not supporting the min_volume
+    orders.insert(Uuid::new_v4(), price_ping(int(3), int(100), None));
+
+    assert!(check_min_volume_of_matching_orders(None, &MmNumber::from(1), &MmNumber::from(1)).is_ok());
+    assert!(check_min_volume_of_matching_orders(Some(&orders), &MmNumber::from(1), &MmNumber::from(10)).is_ok());
+    assert!(check_min_volume_of_matching_orders(Some(&orders), &MmNumber::from(2), &MmNumber::from(5)).is_ok());
+    assert!(check_min_volume_of_matching_orders(Some(&orders), &MmNumber::from(3), &MmNumber::from(1)).is_ok());
+    // no orders matching the price, the request might still be matched by an order we don't know about
+    assert!(check_min_volume_of_matching_orders(Some(&orders), &BigRational::new(1.into(), 2.into()).into(), &MmNumber::from(1)).is_ok());
+
+    let err = unwrap_err!(check_min_volume_of_matching_orders(Some(&orders), &MmNumber::from(2), &MmNumber::from(4)));
+    assert_eq!(err.code, RpcErrorCode::InvalidRequest);
+    let min_volume: BigDecimal = unwrap!(json::from_value(unwrap!(err.details)["min_volume"].clone()));
+    assert_eq!(min_volume, 5.into());
+}
+
+#[test]
+fn test_match_maker_order_below_min_volume() {
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 5.into(),
+        min_base_vol_rat: BigRational::from_integer(5.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        uuid: Uuid::new_v4(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
+    };
+
+    let mut request = TakerRequest {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        uuid: Uuid::new_v4(),
+        method: "request".into(),
+        dest_pub_key: H256Json::default(),
+        sender_pubkey: H256Json::default(),
+        base_amount: 4.into(),
+        base_amount_rat: Some(BigRational::from_integer(4.into())),
+        rel_amount: 4.into(),
+        rel_amount_rat: Some(BigRational::from_integer(4.into())),
+        action: TakerAction::Buy,
+        partial_fill: false,
+    };
+    assert_eq!(below_min_volume(&maker, &request), Some(MmNumber::from(5)));
+    assert_eq!(match_order_and_request(&maker, &request), OrderMatchResult::NotMatched);
+
+    request.base_amount = 5.into();
+    request.base_amount_rat = Some(BigRational::from_integer(5.into()));
+    request.rel_amount = 5.into();
+    request.rel_amount_rat = Some(BigRational::from_integer(5.into()));
+    assert_eq!(below_min_volume(&maker, &request), None);
+    assert_eq!(match_order_and_request(&maker, &request), OrderMatchResult::Matched((5.into(), 5.into())));
+
+    // the taker sells the REL, the volume is in the REL
+    request.base = "REL".into();
+    request.rel = "BASE".into();
+    request.action = TakerAction::Sell;
+    request.rel_amount = 3.into();
+    request.rel_amount_rat = Some(BigRational::from_integer(3.into()));
+    assert_eq!(below_min_volume(&maker, &request), Some(MmNumber::from(5)));
+}
+
+fn orderbook_entry(price: BigRational, volume: BigRational, pubkey: &str) -> OrderbookEntry {
+    OrderbookEntry {
+        coin: "BASE".into(),
+        address: "".into(),
+        price: from_ratio_to_dec(&price),
+        price_rat: price,
+        max_volume: unwrap!(from_ratio_to_dec(&volume).to_f64()),
+        max_volume_rat: volume.clone(),
+        base_max_volume: from_ratio_to_dec(&volume),
+        base_max_volume_rat: volume,
+        min_volume: 0.into(),
+        min_volume_rat: BigRational::zero(),
+        depth: 0.into(),
+        depth_rat: BigRational::zero(),
+        pubkey: pubkey.into(),
+        age: 0,
+    }
+}
+
+#[test]
+fn test_orderbook_side() {
+    let rat = |n: i64, d: i64| BigRational::new(n.into(), d.into());
+    let entries = vec![
+        orderbook_entry(rat(12, 10), rat(1, 1), "c"),
+        orderbook_entry(rat(1, 1), rat(2, 1), "a"),
+        orderbook_entry(rat(11, 10), rat(3, 1), "b"),
+        orderbook_entry(rat(1, 1), rat(4, 1), "d"),
+    ];
+
+    let (asks, levels) = orderbook_side(entries.clone(), true, None, None);
+    let prices: Vec<_> = asks.iter().map(|e| (e.price_rat.clone(), e.pubkey.as_str())).collect();
+    assert_eq!(prices, vec![(rat(1, 1), "a"), (rat(1, 1), "d"), (rat(11, 10), "b"), (rat(12, 10), "c")]);
+    let depths: Vec<_> = asks.iter().map(|e| e.depth_rat.clone()).collect();
+    assert_eq!(depths, vec![rat(2, 1), rat(6, 1), rat(9, 1), rat(10, 1)]);
+    assert_eq!(levels.len(), 3);
+    assert_eq!(levels[0].orders, 2);
+    assert_eq!(levels[0].volume_rat, rat(6, 1));
+    assert_eq!(levels[2].depth_rat, rat(10, 1));
+
+    // the bids are sorted by the descending price, the depth limit is applied to the price levels
+    let (bids, levels) = orderbook_side(entries.clone(), false, None, Some(2));
+    let prices: Vec<_> = bids.iter().map(|e| e.price_rat.clone()).collect();
+    assert_eq!(prices, vec![rat(12, 10), rat(11, 10)]);
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[1].depth_rat, rat(4, 1));
+
+    // the ask prices are rounded up to the step, the bid prices are rounded down
+    let step = rat(1, 2);
+    let (asks, levels) = orderbook_side(entries.clone(), true, Some(&step), Some(1));
+    assert_eq!(asks.len(), 2);
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels[0].price_rat, rat(1, 1));
+    let (_, levels) = orderbook_side(entries, false, Some(&step), None);
+    let prices: Vec<_> = levels.iter().map(|l| (l.price_rat.clone(), l.orders)).collect();
+    assert_eq!(prices, vec![(rat(1, 1), 4)]);
+
+    // the volumes are accumulated in the base coin, the bids' max_volume is in the rel coin
+    let mut bid = orderbook_entry(rat(2, 1), rat(4, 1), "e");
+    bid.base_max_volume_rat = rat(2, 1);
+    let (bids, levels) = orderbook_side(vec![bid], false, None, None);
+    assert_eq!(bids[0].max_volume_rat, rat(4, 1));
+    assert_eq!(bids[0].depth_rat, rat(2, 1));
+    assert_eq!(levels[0].volume_rat, rat(2, 1));
+}
+
+fn maker_reserved(base_amount: u64, rel_amount: u64) -> MakerReserved {
+    MakerReserved {
+        method: "reserved".into(),
+        base: "BASE".into(),
+        rel: "REL".into(),
+        base_amount: base_amount.into(),
+        base_amount_rat: Some(BigRational::from_integer(base_amount.into())),
+        rel_amount: rel_amount.into(),
+        rel_amount_rat: Some(BigRational::from_integer(rel_amount.into())),
+        sender_pubkey: H256Json::default(),
+        dest_pub_key: H256Json::default(),
+        maker_order_uuid: Uuid::new_v4(),
+        taker_order_uuid: Uuid::new_v4(),
+    }
+}
+
+#[test]
+fn test_reserved_by_price() {
+    let orderbook = HashMap::new();
+    assert!(reserved_by_price(vec![], &orderbook).is_empty());
+
+    let expensive = maker_reserved(10, 12);
+    let cheap = maker_reserved(10, 9);
+    let cheap_later = maker_reserved(20, 18);
+    // zero base amount can't be priced
+    let zero = maker_reserved(0, 1);
+    let sorted: Vec<Uuid> = reserved_by_price(vec![expensive.clone(), zero, cheap.clone(), cheap_later.clone()], &orderbook)
+        .into_iter().map(|r| r.maker_order_uuid).collect();
+    // the same price and the orders unknown to the orderbook, the earliest reply goes first
+    assert_eq!(sorted, vec![cheap.maker_order_uuid, cheap_later.maker_order_uuid, expensive.maker_order_uuid]);
+}
+
+#[test]
+fn test_reserved_by_price_order_age() {
+    let older = maker_reserved(10, 9);
+    let newer = maker_reserved(20, 18);
+    let unknown = maker_reserved(30, 27);
+    let expensive = maker_reserved(10, 12);
+    let ping = |first_seen: u64| {
+        let mut ping = price_ping(BigRational::from_integer(1.into()), BigRational::from_integer(10.into()), None);
+        ping.first_seen = first_seen;
+        ping
+    };
+    let mut pair_orders = HashMap::new();
+    pair_orders.insert(older.maker_order_uuid, ping(100));
+    pair_orders.insert(newer.maker_order_uuid, ping(200));
+    pair_orders.insert(expensive.maker_order_uuid, ping(50));
+    let mut orderbook = HashMap::new();
+    orderbook.insert(("BASE".to_string(), "REL".to_string()), pair_orders);
+
+    // the equal prices are ordered by the age of the orders regardless of the arrival of the replies
+    let sorted: Vec<Uuid> = reserved_by_price(vec![unknown.clone(), expensive.clone(), newer.clone(), older.clone()], &orderbook)
+        .into_iter().map(|r| r.maker_order_uuid).collect();
+    assert_eq!(sorted, vec![older.maker_order_uuid, newer.maker_order_uuid, unknown.maker_order_uuid, expensive.maker_order_uuid]);
+}
+
+fn buy_request(base_amount: u64, rel_amount: u64, partial_fill: bool) -> TakerRequest {
+    TakerRequest {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        uuid: Uuid::new_v4(),
+        method: "request".into(),
+        dest_pub_key: H256Json::default(),
+        sender_pubkey: H256Json::default(),
+        base_amount: base_amount.into(),
+        base_amount_rat: Some(BigRational::from_integer(base_amount.into())),
+        rel_amount: rel_amount.into(),
+        rel_amount_rat: Some(BigRational::from_integer(rel_amount.into())),
+        action: TakerAction::Buy,
+        partial_fill,
+    }
+}
+
+/// Matches the taker order with the `reserved` reply, the swap being started if `connected`.
+fn insert_taker_match(order: &mut TakerOrder, reserved: MakerReserved, connected: bool) {
+    let connect = TakerConnect {
+        method: "connect".into(),
+        sender_pubkey: H256Json::default(),
+        dest_pub_key: H256Json::default(),
+        maker_order_uuid: reserved.maker_order_uuid,
+        taker_order_uuid: order.request.uuid,
+        swap_uuid: Some(Uuid::new_v4()),
+    };
+    let connected = if connected {
+        Some(MakerConnected {
+            method: "connected".into(),
+            sender_pubkey: H256Json::default(),
+            dest_pub_key: H256Json::default(),
+            maker_order_uuid: reserved.maker_order_uuid,
+            taker_order_uuid: order.request.uuid,
+        })
+    } else {
+        None
+    };
+    order.matches.insert(reserved.maker_order_uuid, TakerMatch {reserved, connect, connected, last_updated: now_ms()});
+}
+
+#[test]
+fn test_match_order_and_partial_request() {
+    let maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 4.into(),
+        max_base_vol_rat: BigRational::from_integer(4.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: vec![],
+        uuid: Uuid::new_v4(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
+    };
+
+    let request = buy_request(10, 20, true);
+    assert_eq!(OrderMatchResult::Matched((4.into(), 4.into())), match_order_and_request(&maker, &request));
+
+    let request = buy_request(10, 20, false);
+    assert_eq!(OrderMatchResult::NotMatched, match_order_and_request(&maker, &request));
+
+    // the taker sells 10 REL for 5 BASE at least, getting 4 BASE for 4 REL
+    let mut request = buy_request(10, 5, true);
+    request.action = TakerAction::Sell;
+    request.base = "REL".into();
+    request.rel = "BASE".into();
+    assert_eq!(OrderMatchResult::Matched((4.into(), 4.into())), match_order_and_request(&maker, &request));
+    request.partial_fill = false;
+    assert_eq!(OrderMatchResult::NotMatched, match_order_and_request(&maker, &request));
+}
+
+#[test]
+fn test_taker_order_partial_fill() {
+    let mut order = TakerOrder {
+        request: buy_request(10, 20, true),
+        matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
+        created_at: now_ms(),
+    };
+
+    let first = maker_reserved(4, 6);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&first));
+    insert_taker_match(&mut order, first.clone(), true);
+    // the same maker order can't be matched twice
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&first));
+    assert_eq!(order.filled_base_amount(), MmNumber::from(4));
+    assert_eq!(order.unmatched_base_amount(), MmNumber::from(6));
+    assert!(!order.is_filled());
+    assert!(order.is_cancellable());
+
+    // more than the unmatched amount
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&maker_reserved(7, 7)));
+    // the price is higher than ours
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&maker_reserved(6, 13)));
+
+    let second = maker_reserved(6, 12);
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&second));
+    insert_taker_match(&mut order, second, false);
+    assert_eq!(order.filled_base_amount(), MmNumber::from(4));
+    assert_eq!(order.unmatched_base_amount(), MmNumber::from(0));
+    assert!(!order.is_cancellable());
+
+    let second = unwrap!(order.matches.values_mut().find(|m| m.connected.is_none()));
+    second.connected = Some(MakerConnected {
+        method: "connected".into(),
+        sender_pubkey: H256Json::default(),
+        dest_pub_key: H256Json::default(),
+        maker_order_uuid: second.reserved.maker_order_uuid,
+        taker_order_uuid: second.reserved.taker_order_uuid,
+    });
+    assert!(order.is_filled());
+
+    let rpc_order = unwrap!(json::to_value(TakerOrderForRpc::from(&order)));
+    assert_eq!(rpc_order["filled_amount_rat"], unwrap!(json::to_value(BigRational::from_integer(10.into()))));
+    assert_eq!(rpc_order["remaining_amount_rat"], unwrap!(json::to_value(BigRational::from_integer(0.into()))));
+
+    // the unmatched amount of the partially filled order is converted to a maker order
+    let mut order = TakerOrder {
+        request: buy_request(10, 20, true),
+        matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
+        created_at: now_ms(),
+    };
+    insert_taker_match(&mut order, maker_reserved(4, 6), true);
+    let maker: MakerOrder = order.into();
+    assert_eq!(maker.max_base_vol_rat, BigRational::from_integer(12.into()));
+
+    // FillOrKill orders aren't partially filled
+    let order = TakerOrder {
+        request: buy_request(10, 20, false),
+        matches: HashMap::new(),
+        time_in_force: TimeInForce::FillOrKill,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
+        created_at: now_ms(),
+    };
+    assert_eq!(MatchReservedResult::NotMatched, order.match_reserved(&maker_reserved(4, 6)));
+    assert_eq!(MatchReservedResult::Matched, order.match_reserved(&maker_reserved(10, 20)));
+}
+
+#[test]
+fn test_market_price() {
+    let rat = |n: i64, d: i64| BigRational::new(n.into(), d.into());
+    let mut asks = HashMap::new();
+    asks.insert(Uuid::new_v4(), price_ping(rat(3, 2), int(5), None));
+    asks.insert(Uuid::new_v4(), price_ping(int(1), int(5), None));
+    asks.insert(Uuid::new_v4(), price_ping(int(4), int(100), None));
+    // my own order is skipped
+    let mut mine = price_ping(int(1), int(100), None);
+    mine.pubkey = "mine".into();
+    asks.insert(Uuid::new_v4(), mine);
+
+    // 5 at 1 and 3 at 1.5
+    let price = unwrap!(market_price(Some(&asks), "mine", true, &MmNumber::from(8), &MmNumber::from(50)));
+    assert_eq!(price, MarketPrice {
+        best: MmNumber::from(1),
+        average: MmNumber::from(rat(19, 16)),
+        limit: MmNumber::from(rat(3, 2)),
+    });
+    // only 5 are available within the 49% from the best price
+    let err = unwrap!(market_price(Some(&asks), "mine", true, &MmNumber::from(8), &MmNumber::from(49)).err());
+    assert_eq!(err.code, RpcErrorCode::InvalidRequest);
+    assert!(err.message.contains("available 5"), "{}", err.message);
+    assert!(market_price(None, "mine", true, &MmNumber::from(1), &MmNumber::from(1)).is_err());
+    assert!(market_price(Some(&asks), "mine", true, &MmNumber::from(1), &MmNumber::from(100)).is_err());
+
+    // the bids selling the REL for the BASE: 5 REL at 2 BASE per REL, 10 REL at 4 BASE per REL,
+    // that's 10 BASE at 0.5 REL per BASE and 40 BASE at 0.25 REL per BASE
+    let mut bids = HashMap::new();
+    bids.insert(Uuid::new_v4(), price_ping(int(2), int(5), None));
+    bids.insert(Uuid::new_v4(), price_ping(int(4), int(10), None));
+    let price = unwrap!(market_price(Some(&bids), "mine", false, &MmNumber::from(10), &MmNumber::from(1)));
+    assert_eq!(price, MarketPrice {
+        best: MmNumber::from(rat(1, 2)),
+        average: MmNumber::from(rat(1, 2)),
+        limit: MmNumber::from(rat(99, 200)),
+    });
+    // 10 BASE at 0.5 and 10 BASE at 0.25
+    let price = unwrap!(market_price(Some(&bids), "mine", false, &MmNumber::from(20), &MmNumber::from(50)));
+    assert_eq!(price.average, MmNumber::from(rat(3, 8)));
+    assert!(market_price(Some(&bids), "mine", false, &MmNumber::from(20), &MmNumber::from(49)).is_err());
+}
+
+#[test]
+fn test_orders_history_record() {
+    use self::orders_history::{filter_orders_history, OrderHistoryRecord, OrderStatus, OrdersHistoryFilter};
+
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: 1000,
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: vec![],
+        uuid: Uuid::new_v4(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        allowed_pubkeys: Vec::new(),
+    };
+    assert_eq!(OrderHistoryRecord::from_maker_order(&maker, None).status, OrderStatus::Created);
+    assert_eq!(OrderHistoryRecord::from_maker_order(&maker, Some(OrderCancellationReason::InsufficientVolume)).status, OrderStatus::Cancelled);
+    assert_eq!(OrderHistoryRecord::from_maker_order(&maker, Some(OrderCancellationReason::Expired)).status, OrderStatus::Expired);
+    let swap_uuid = Uuid::new_v4();
+    maker.started_swaps.push(swap_uuid);
+    assert_eq!(OrderHistoryRecord::from_maker_order(&maker, None).status, OrderStatus::PartiallyFilled);
+    let drained = OrderHistoryRecord::from_maker_order(&maker, Some(OrderCancellationReason::InsufficientVolume));
+    assert_eq!(drained.status, OrderStatus::Filled);
+    assert_eq!(drained.cancellation_reason, Some(OrderCancellationReason::InsufficientVolume));
+
+    let mut taker = TakerOrder {
+        request: buy_request(10, 20, true),
+        matches: HashMap::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        started_swaps: Vec::new(),
+        rejections: HashMap::new(),
+        created_at: 2000,
+    };
+    let record = OrderHistoryRecord::from_taker_order(&taker, None);
+    assert_eq!(record.status, OrderStatus::Created);
+    assert_eq!(record.action, "Buy");
+    assert_eq!(record.price_rat, BigRational::from_integer(2.into()));
+    insert_taker_match(&mut taker, maker_reserved(4, 8), false);
+    assert_eq!(OrderHistoryRecord::from_taker_order(&taker, None).status, OrderStatus::Matched);
+    insert_taker_match(&mut taker, maker_reserved(6, 12), true);
+    taker.started_swaps.push(Uuid::new_v4());
+    assert_eq!(OrderHistoryRecord::from_taker_order(&taker, None).status, OrderStatus::PartiallyFilled);
+    assert_eq!(OrderHistoryRecord::from_taker_order(&taker, Some(OrderCancellationReason::NotMatched)).status, OrderStatus::Cancelled);
+
+    // the taker order converted to a maker order keeps the creation time and the swaps
+    let taker_record = OrderHistoryRecord::from_taker_order(&taker, None);
+    maker.uuid = taker_record.uuid;
+    let merged = OrderHistoryRecord::from_maker_order(&maker, None).merge(Some(taker_record.clone()));
+    assert_eq!(merged.order_type, "Maker");
+    assert_eq!(merged.created_at, 2000);
+    assert_eq!(merged.swaps, vec![taker.started_swaps[0], swap_uuid]);
+
+    maker.uuid = Uuid::new_v4();
+    let maker_record = OrderHistoryRecord::from_maker_order(&maker, None);
+    let records = vec![maker_record.clone(), taker_record.clone()];
+    let filter = |filter: Json| -> Vec<Uuid> {
+        let filter: OrdersHistoryFilter = unwrap!(json::from_value(filter));
+        filter_orders_history(records.clone(), &filter).into_iter().map(|r| r.uuid).collect()
+    };
+    // the most recently created first
+    assert_eq!(filter(json!({})), vec![taker_record.uuid, maker_record.uuid]);
+    assert_eq!(filter(json!({"type": "Maker"})), vec![maker_record.uuid]);
+    assert_eq!(filter(json!({"status": "PartiallyFilled", "base": "BASE", "rel": "REL"})), vec![taker_record.uuid, maker_record.uuid]);
+    assert_eq!(filter(json!({"status": "Created"})), Vec::<Uuid>::new());
+    assert_eq!(filter(json!({"from_timestamp": 2})), vec![taker_record.uuid]);
+    assert_eq!(filter(json!({"to_timestamp": 2})), vec![maker_record.uuid]);
+    assert_eq!(filter(json!({"rel": "BASE"})), Vec::<Uuid>::new());
+}
+
+#[test]
+fn test_price_ping_signature() {
+    let key_pair = unwrap!(key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid"));
+    let mut ping = price_ping(BigRational::from_integer(2.into()), BigRational::from_integer(10.into()), None);
+    ping.pubsecp = hex::encode(&**key_pair.public());
+    ping.pubkey = hex::encode(&[1u8; 32]);
+    ping.price64 = "200000000".into();
+    unwrap!(ping.sign(&key_pair));
+
+    let mut v2_signers = HashSet::new();
+    unwrap!(ping.verify(&mut v2_signers, false));
+    assert!(v2_signers.contains(&**key_pair.public()));
+
+    // the fields not covered by the legacy signature are covered by the version 2 one
+    let mut tampered = ping.clone();
+    tampered.balance_rat = Some(BigRational::from_integer(1000.into()));
+    assert!(tampered.verify(&mut HashSet::new(), true).is_err());
+    let mut tampered = ping.clone();
+    tampered.balance = 1000.into();
+    assert!(tampered.verify(&mut HashSet::new(), true).is_err());
+    let mut tampered = ping.clone();
+    tampered.uuid = Some(Uuid::new_v4());
+    assert!(tampered.verify(&mut HashSet::new(), true).is_err());
+    let mut tampered = ping.clone();
+    tampered.price_rat = Some(BigRational::from_integer(1.into()));
+    assert!(tampered.verify(&mut HashSet::new(), true).is_err());
+    let mut tampered = ping.clone();
+    tampered.min_volume = Some(5.into());
+    assert!(tampered.verify(&mut HashSet::new(), true).is_err());
+
+    // the legacy ping is accepted during the transition, unless its signer is known to sign the version 2
+    let mut legacy = ping.clone();
+    legacy.sig_v2 = None;
+    unwrap!(legacy.verify(&mut HashSet::new(), true));
+    assert!(legacy.verify(&mut HashSet::new(), false).is_err());
+    assert!(legacy.verify(&mut v2_signers, true).is_err());
+    legacy.price64 = "100000000".into();
+    assert!(legacy.verify(&mut HashSet::new(), true).is_err());
+
+    // the signature survives the JSON round trip
+    let relayed: PricePingRequest = unwrap!(json::from_value(unwrap!(json::to_value(&ping))));
+    unwrap!(relayed.verify(&mut HashSet::new(), false));
+}
+
+#[test]
+fn test_init_ordermatch_context() {
+    let ctx = MmCtxBuilder::new().with_conf(json!({"orderbook_limits": {"max_orders_per_pubkey": "many"}})).into_mm_arc();
+    let err = unwrap!(init_ordermatch_context(&ctx).err());
+    assert!(err.contains("Invalid orderbook_limits configuration"), "{}", err);
+
+    let ctx = MmCtxBuilder::new().with_conf(json!({"orderbook_limits": {"max_orders_per_pubkey": 5}})).into_mm_arc();
+    unwrap!(init_ordermatch_context(&ctx));
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&ctx));
+    assert_eq!(ordermatch_ctx.orderbook_limits.max_orders_per_pubkey, 5);
+}
+
+#[test]
+fn test_orderbook_limits() {
+    use self::orderbook_limits::remove_pubkey_orders;
+
+    let limits = OrderbookLimits {max_orders_per_pubkey: 3, max_orders_per_pair: 2, max_pairs_per_pubkey: 2};
+    let one = BigRational::from_integer(1.into());
+    let ping = |pubkey: &str, base: &str, rel: &str| price_ping_for(pubkey, base, rel, one.clone(), one.clone());
+    let mut orderbook = orderbook_of(vec![]);
+    let insert = |orderbook: &mut HashMap<(String, String), HashMap<Uuid, PricePingRequest>>, req: PricePingRequest| {
+        unwrap!(check_new_order_limits(&limits, orderbook, &req));
+        add_to_orderbook(orderbook, req);
+    };
+
+    insert(&mut orderbook, ping("aa", "BASE", "REL"));
+    insert(&mut orderbook, ping("aa", "BASE", "REL"));
+    // the pair limit
+    assert!(check_new_order_limits(&limits, &orderbook, &ping("aa", "BASE", "REL")).is_err());
+    insert(&mut orderbook, ping("aa", "REL", "BASE"));
+    // the orders limit
+    assert!(check_new_order_limits(&limits, &orderbook, &ping("aa", "REL", "BASE")).is_err());
+    // the limits are per pubkey
+    insert(&mut orderbook, ping("bb", "BASE", "REL"));
+    insert(&mut orderbook, ping("bb", "REL", "BASE"));
+    // the pairs limit
+    assert!(check_new_order_limits(&limits, &orderbook, &ping("bb", "BASE", "OTHER")).is_err());
+
+    assert_eq!(remove_pubkey_orders(&mut orderbook, "aa"), 3);
+    assert_eq!(orderbook.values().map(|orders| orders.len()).sum::<usize>(), 2);
+    assert_eq!(remove_pubkey_orders(&mut orderbook, "bb"), 2);
+    assert!(orderbook.is_empty());
+}
+
+#[test]
+fn test_check_min_order_volume() {
+    let conf = json!({"coins": [
+        {"coin": "BASE", "min_order_volume": "1"},
+        {"coin": "REL", "min_order_volume": 10},
+    ]});
+    // 2 BASE for 20 REL
+    let ping = price_ping(BigRational::from_integer(10.into()), BigRational::from_integer(2.into()), None);
+    unwrap!(check_min_order_volume(&conf, &ping));
+    // 0.5 BASE
+    let ping = price_ping(BigRational::from_integer(10.into()), BigRational::new(1.into(), 2.into()), None);
+    assert!(check_min_order_volume(&conf, &ping).is_err());
+    // 2 BASE for 2 REL
+    let ping = price_ping(BigRational::from_integer(1.into()), BigRational::from_integer(2.into()), None);
+    assert!(check_min_order_volume(&conf, &ping).is_err());
+    // no min_order_volume configured
+    unwrap!(check_min_order_volume(&json!({}), &ping));
+
+    assert_eq!(min_order_volume(&conf, "BASE"), MmNumber::from(1));
+    assert_eq!(min_order_volume(&conf, "OTHER"), MmNumber::from(BigRational::new(777.into(), 100000.into())));
+}
+
+#[test]
+fn test_check_price_bounds() {
+    use self::orderbook_limits::check_price_bounds;
+
+    let conf = json!({"coins": [
+        {"coin": "BASE", "min_price": {"REL": "2"}, "max_price": {"REL": 10}},
+        {"coin": "REL", "max_price": {"BASE": "0.25"}},
+    ]});
+    let ping_at = |price: BigRational| price_ping(price, int(1), None);
+    unwrap!(check_price_bounds(&conf, &ping_at(int(5))));
+    unwrap!(check_price_bounds(&conf, &ping_at(int(10))));
+    // below the BASE min_price
+    assert!(check_price_bounds(&conf, &ping_at(int(1))).is_err());
+    // above the BASE max_price
+    assert!(check_price_bounds(&conf, &ping_at(int(11))).is_err());
+    // the REL price of 1/3 BASE is above the REL max_price
+    assert!(check_price_bounds(&conf, &ping_at(int(3))).is_err());
+    // the bounds are keyed by the other coin of the pair
+    let mut other_pair = ping_at(int(1));
+    other_pair.rel = "OTHER".into();
+    unwrap!(check_price_bounds(&conf, &other_pair));
+    unwrap!(check_price_bounds(&json!({}), &ping_at(int(1))));
+}
+
+#[test]
+fn test_private_maker_order_is_allowed() {
+    let mut maker = MakerOrder {
+        base: "BASE".into(),
+        rel: "REL".into(),
+        created_at: now_ms(),
+        max_base_vol: 10.into(),
+        max_base_vol_rat: BigRational::from_integer(10.into()),
+        min_base_vol: 0.into(),
+        min_base_vol_rat: BigRational::from_integer(0.into()),
+        price: 1.into(),
+        price_rat: BigRational::from_integer(1.into()),
+        matches: HashMap::new(),
+        started_swaps: Vec::new(),
+        time_in_force: TimeInForce::GoodTillCancelled,
+        uuid: Uuid::new_v4(),
+        allowed_pubkeys: Vec::new(),
+    };
+    let allowed = H256Json::from([1; 32]);
+    let other = H256Json::from([2; 32]);
+    // public order
+    assert!(maker.is_allowed(&allowed));
+    assert!(maker.is_allowed(&other));
+
+    maker.allowed_pubkeys.push(unwrap!(normalize_pubkey(&hex::encode(&allowed.0).to_uppercase())));
+    assert!(maker.is_allowed(&allowed));
+    assert!(!maker.is_allowed(&other));
+}
+
+#[test]
+fn test_private_price_ping_send_recv() {
+    let seed_a = "spice describe gravity federal blast come thank unfair canal monkey style afraid";
+    let seed_b = "also shoot benefit prefer juice shell elder veteran woman mimic image kidney";
+    let ctx_of = |seed: &str| MmCtxBuilder::default().with_secp256k1_key_pair(unwrap!(key_pair_from_seed(seed))).into_mm_arc();
+    let ctx_a = ctx_of(seed_a);
+    let ctx_b = ctx_of(seed_b);
+    let taker_ctx = ctx_of("bob passphrase");
+    let maker_a = unwrap!(key_pair_from_seed(seed_a));
+    let maker_b = unwrap!(key_pair_from_seed(seed_b));
+    let taker_id = unwrap!(taker_ctx.public_id());
+    let sender_a = hex::encode(&unwrap!(ctx_a.public_id()).bytes);
+    let sender_b = hex::encode(&unwrap!(ctx_b.public_id()).bytes);
+
+    // the pings of the private orders are signed by their makers
+    let signed_ping = |key_pair: &KeyPair, pubkey: &str| {
+        let mut ping = price_ping(BigRational::from_integer(2.into()), BigRational::from_integer(10.into()), None);
+        ping.pubsecp = hex::encode(&**key_pair.public());
+        ping.pubkey = pubkey.into();
+        ping.price64 = "200000000".into();
+        unwrap!(ping.sign(key_pair));
+        ping
+    };
+    let ping_a = signed_ping(&maker_a, &sender_a);
+    let ping_a2 = signed_ping(&maker_a, &sender_a);
+    let ping_b = signed_ping(&maker_b, &sender_b);
+    let uuid_a = unwrap!(ping_a.uuid);
+    let uuid_a2 = unwrap!(ping_a2.uuid);
+    let uuid_b = unwrap!(ping_b.uuid);
+
+    // every private order is sent under a subject of its own, so the orders of a maker don't overwrite each other
+    assert_ne!(private_price_ping_subject(&sender_a, &uuid_a), private_price_ping_subject(&sender_a, &uuid_a2));
+    assert_ne!(private_price_ping_subject(&sender_a, &uuid_a), private_price_ping_subject(&sender_b, &uuid_a));
+
+    // the recipient learns the orders to poll from the notifies, the other nodes ignore them
+    for (ctx, uuid) in &[(&ctx_a, uuid_a), (&ctx_a, uuid_a2), (&ctx_b, uuid_b)] {
+        let notify = unwrap!(PrivatePriceNotify::new(ctx, *uuid, &[taker_id]));
+        assert_eq!(notify.recipients, vec![hex::encode(&*dhash160(&taker_id.bytes))]);
+        unwrap!(lp_private_price_notify_recv(&taker_ctx, unwrap!(json::to_value(&notify))).wait());
+        unwrap!(lp_private_price_notify_recv(ctx, unwrap!(json::to_value(&notify))).wait());
+    }
+    let mut orders = unwrap!(private_price_orders(&taker_ctx));
+    orders.sort();
+    let mut expected = vec![(sender_a.clone(), uuid_a), (sender_a.clone(), uuid_a2), (sender_b.clone(), uuid_b)];
+    expected.sort();
+    assert_eq!(orders, expected);
+    assert!(unwrap!(private_price_orders(&ctx_a)).is_empty());
+
+    // the pings received from the orders' subjects are added to the orderbook
+    unwrap!(block_on(process_private_price_ping(&taker_ctx, &sender_a, &uuid_a, &unwrap!(json::to_vec(&ping_a)))));
+    unwrap!(block_on(process_private_price_ping(&taker_ctx, &sender_a, &uuid_a2, &unwrap!(json::to_vec(&ping_a2)))));
+    unwrap!(block_on(process_private_price_ping(&taker_ctx, &sender_b, &uuid_b, &unwrap!(json::to_vec(&ping_b)))));
+
+    // anyone can put a value under the subject of an order, but only the maker's own ping of that order is accepted
+    let forged = signed_ping(&maker_b, &sender_a);
+    let forged_uuid = unwrap!(forged.uuid);
+    assert!(block_on(process_private_price_ping(&taker_ctx, &sender_a, &forged_uuid, &unwrap!(json::to_vec(&forged)))).is_err());
+    assert!(block_on(process_private_price_ping(&taker_ctx, &sender_a, &uuid_b, &unwrap!(json::to_vec(&ping_b)))).is_err());
+    assert!(block_on(process_private_price_ping(&taker_ctx, &sender_a, &uuid_a, &unwrap!(json::to_vec(&ping_a2)))).is_err());
+
+    let ordermatch_ctx = unwrap!(OrdermatchContext::from_ctx(&taker_ctx));
+    let orderbook = unwrap!(ordermatch_ctx.orderbook.lock());
+    let orders = &orderbook[&("BASE".to_string(), "REL".to_string())];
+    assert_eq!(orders.len(), 3);
+    assert_eq!(orders[&uuid_a].pubkey, sender_a);
+    assert_eq!(orders[&uuid_a2].pubkey, sender_a);
+    assert_eq!(orders[&uuid_b].pubkey, sender_b);
+}
+
+#[test]
+fn test_find_best_orders() {
+    use self::best_orders::{find_best_orders, BestOrdersAction};
+
+    let ping = |pubkey: &str, base: &str, rel: &str, price: i64, balance: i64, min_volume: i64| {
+        let mut ping = price_ping_for(pubkey, base, rel, int(price), int(balance));
+        ping.min_volume = Some(min_volume.into());
+        ping.min_volume_rat = Some(int(min_volume));
+        ping
+    };
+    let orderbook = orderbook_of(vec![
+        // selling KMD
+        ping("aa", "KMD", "BTC", 3, 10, 0),
+        ping("bb", "KMD", "BTC", 2, 10, 0),
+        ping("cc", "KMD", "BTC", 1, 4, 0),
+        ping("aa", "KMD", "ETH", 5, 10, 6),
+        ping("me", "KMD", "ETH", 1, 10, 0),
+        // buying KMD
+        ping("aa", "BTC", "KMD", 2, 10, 0),
+        ping("bb", "ETH", "KMD", 4, 10, 1),
+    ]);
+
+    let best = find_best_orders(&orderbook, "me", "KMD", BestOrdersAction::Buy, &MmNumber::from(5));
+    assert_eq!(best.len(), 1);
+    // the "cc" has not enough volume, the "aa" ETH order has a greater min_volume and "me" is skipped
+    let prices: Vec<BigRational> = best["BTC"].iter().map(|order| order.price_rat.clone()).collect();
+    assert_eq!(prices, vec![BigRational::from_integer(2.into()), BigRational::from_integer(3.into())]);
+    assert_eq!(best["BTC"][0].pubkey, "bb");
+
+    let best = find_best_orders(&orderbook, "me", "KMD", BestOrdersAction::Sell, &MmNumber::from(5));
+    // the "aa" buys up to 20 KMD for 10 BTC
+    assert_eq!(best["BTC"][0].price_rat, BigRational::new(1.into(), 2.into()));
+    assert_eq!(best["BTC"][0].max_volume_rat, BigRational::from_integer(20.into()));
+    // the "bb" buys 4 to 40 KMD
+    assert_eq!(best["ETH"][0].price_rat, BigRational::new(1.into(), 4.into()));
+    assert_eq!(best["ETH"][0].min_volume_rat, BigRational::from_integer(4.into()));
+    let best = find_best_orders(&orderbook, "me", "KMD", BestOrdersAction::Sell, &MmNumber::from(3));
+    assert!(!best.contains_key("ETH"));
+}
+
+#[test]
+fn test_conditional_order_trigger() {
+    use self::conditional_orders::{orderbook_depth_price, ConditionalOrderStatus, PriceSource, Trigger, TriggerRecord, TriggerType};
+
+    let orderbook = orderbook_of(vec![
+        // the asks, including a dust one at an extreme price
+        price_ping_for("aa", "KMD", "BTC", int(3), int(10)),
+        price_ping_for("bb", "KMD", "BTC", int(2), int(10)),
+        price_ping_for("cc", "KMD", "BTC", BigRational::new(1.into(), 100.into()), BigRational::new(1.into(), 1000.into())),
+        price_ping_for("me", "KMD", "BTC", int(1), int(10)),
+        // the bids, buying 40 KMD for 1/4 BTC and 50 KMD for 1/5 BTC
+        price_ping_for("aa", "BTC", "KMD", int(4), int(10)),
+        price_ping_for("bb", "BTC", "KMD", int(5), int(10)),
+    ]);
+    let depth_price = |is_buy: bool, depth: i64| orderbook_depth_price(&orderbook, "me", "KMD", "BTC", is_buy, &int(depth));
+    assert_eq!(depth_price(true, 0), Some(BigRational::new(1.into(), 100.into())));
+    // the dust ask is walked past
+    assert_eq!(depth_price(true, 1), Some(int(2)));
+    assert_eq!(depth_price(true, 15), Some(int(3)));
+    // my own order is skipped
+    assert_eq!(depth_price(true, 30), None);
+    assert_eq!(depth_price(false, 40), Some(BigRational::new(1.into(), 4.into())));
+    assert_eq!(depth_price(false, 50), Some(BigRational::new(1.into(), 5.into())));
+    assert_eq!(depth_price(false, 100), None);
+    assert_eq!(orderbook_depth_price(&orderbook, "me", "KMD", "ETH", true, &int(0)), None);
+
+    let order = |method: &str, trigger_type: TriggerType, price: BigRational| ConditionalOrder {
+        uuid: Uuid::new_v4(),
+        method: method.into(),
+        base: "KMD".into(),
+        rel: "BTC".into(),
+        trigger: Trigger {trigger_type, price: price.into(), min_depth: None},
+        price_source: PriceSource::Orderbook,
+        order: json!({}),
+        status: ConditionalOrderStatus::Pending,
+        history: Vec::new(),
+        created_at: now_ms(),
+        updated_at: now_ms(),
+    };
+    let quarter = BigRational::new(1.into(), 4.into());
+    let fifth = BigRational::new(1.into(), 5.into());
+    // selling when the bid falls to the stop price or rises to the target
+    assert!(order("sell", TriggerType::StopLoss, quarter.clone()).is_triggered(&quarter));
+    assert!(!order("sell", TriggerType::StopLoss, fifth.clone()).is_triggered(&quarter));
+    assert!(order("sell", TriggerType::TakeProfit, fifth.clone()).is_triggered(&quarter));
+    assert!(!order("sell", TriggerType::TakeProfit, int(1)).is_triggered(&quarter));
+    // buying when the ask rises to the stop price or falls to the target
+    assert!(order("buy", TriggerType::StopLoss, int(2)).is_triggered(&int(2)));
+    assert!(!order("buy", TriggerType::StopLoss, int(3)).is_triggered(&int(2)));
+    assert!(order("buy", TriggerType::TakeProfit, int(3)).is_triggered(&int(2)));
+    assert!(!order("buy", TriggerType::TakeProfit, int(1)).is_triggered(&int(2)));
+
+    // the failed request is retried on a later trigger
+    let mut failed = order("buy", TriggerType::TakeProfit, int(3));
+    assert!(failed.is_pending(now_ms()));
+    failed.history.push(TriggerRecord {timestamp: now_ms(), price: 2.into(), taker_uuid: None, error: Some("error".into())});
+    assert!(!failed.is_pending(now_ms()));
+    assert!(failed.is_pending(now_ms() + 60000));
+    failed.status = ConditionalOrderStatus::Failed;
+    assert!(!failed.is_pending(now_ms() + 60000));
+}
+
+#[test]
+fn test_external_prices() {
+    use self::external_prices::{parse_external_prices, PricingProvider};
+
+    let conf = json!({"coins": [
+        {"coin": "KMD", "name": "Komodo"},
+        {"coin": "BTC", "name": "bitcoin"},
+        {"coin": "RICK"},
+    ]});
+    assert_eq!(unwrap!(PricingProvider::from_conf(&conf)), PricingProvider::CoinGecko);
+    let cmc = unwrap!(PricingProvider::from_conf(&json!({"cmc_key": "key"})));
+    assert_eq!(cmc, PricingProvider::CoinMarketCap("key".into()));
+    assert!(PricingProvider::from_conf(&json!({"cmc_key": 1})).is_err());
+    assert_eq!(unwrap!(PricingProvider::CoinGecko.coin_id(&conf, "KMD")), "komodo");
+    assert!(PricingProvider::CoinGecko.coin_id(&conf, "RICK").is_err());
+    assert_eq!(unwrap!(cmc.coin_id(&conf, "RICK")), "RICK");
+
+    let mut ids = HashMap::new();
+    ids.insert("komodo".to_string(), "KMD".to_string());
+    ids.insert("bitcoin".to_string(), "BTC".to_string());
+    ids.insert("unknown".to_string(), "UNK".to_string());
+    let reply = json!({"komodo": {"btc": 0.0001}, "bitcoin": {"btc": 1}});
+    let prices = unwrap!(parse_external_prices(&PricingProvider::CoinGecko, &ids, &reply));
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices["KMD"], BigRational::new(1.into(), 10000.into()));
+
+    let mut ids = HashMap::new();
+    ids.insert("KMD".to_string(), "KMD".to_string());
+    ids.insert("ETH".to_string(), "ETH".to_string());
+    let reply = json!({"data": {
+        "KMD": {"slug": "komodo", "quote": {"BTC": {"price": 0.0001}}},
+        "ETH": {"slug": "ethereum", "quote": {"BTC": {"price": 0.02}}},
+    }});
+    let prices = unwrap!(parse_external_prices(&cmc, &ids, &reply));
+    let external = ExternalPrices {prices, at: now_ms()};
+    assert_eq!(external.price("ETH", "KMD", now_ms()), Some(int(200)));
+    assert_eq!(external.price("KMD", "BTC", now_ms()), None);
+    // the outdated prices don't trigger the orders
+    assert_eq!(external.price("ETH", "KMD", now_ms() + 300000), None);
+}
+
+#[test]
+fn test_resolve_taker_only() {
+    let input = |params: Json| -> AutoBuyInput {
+        let mut req = json!({"method": "buy", "base": "BASE", "rel": "REL", "price": "1", "volume": "1"});
+        for (key, value) in unwrap!(params.as_object()) {req[key] = value.clone()}
+        unwrap!(json::from_value(req))
+    };
+    let ctx = MmCtxBuilder::default().into_mm_arc();
+    let mut order = input(json!({}));
+    unwrap!(resolve_taker_only(&ctx, &mut order));
+    assert_eq!(order.time_in_force, TimeInForce::GoodTillCancelled);
+    let mut order = input(json!({"taker_only": true}));
+    unwrap!(resolve_taker_only(&ctx, &mut order));
+    assert_eq!(order.time_in_force, TimeInForce::ImmediateOrCancel);
+    let mut order = input(json!({"taker_only": true, "time_in_force": {"type": "FillOrKill"}}));
+    unwrap!(resolve_taker_only(&ctx, &mut order));
+    assert_eq!(order.time_in_force, TimeInForce::FillOrKill);
+    let mut order = input(json!({"taker_only": true, "time_in_force": {"type": "GoodTillTime", "expires_at": now_ms() / 1000 + 60}}));
+    assert!(resolve_taker_only(&ctx, &mut order).is_err());
+
+    // the node-wide default can be overridden by the request
+    let ctx = MmCtxBuilder::new().with_conf(json!({"taker_only": true, "ordermatch_timeout_ms": 60000})).into_mm_arc();
+    let mut order = input(json!({}));
+    unwrap!(resolve_taker_only(&ctx, &mut order));
+    assert_eq!(order.time_in_force, TimeInForce::ImmediateOrCancel);
+    let mut order = input(json!({"taker_only": false}));
+    unwrap!(resolve_taker_only(&ctx, &mut order));
+    assert_eq!(order.time_in_force, TimeInForce::GoodTillCancelled);
+
+    assert_eq!(ordermatch_timeout(&ctx), 60000);
+    let ctx = MmCtxBuilder::new().with_conf(json!({"ordermatch_timeout_ms": 1000})).into_mm_arc();
+    assert_eq!(ordermatch_timeout(&ctx), MIN_ORDERMATCH_TIMEOUT_MS);
+}
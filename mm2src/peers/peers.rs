@@ -1575,6 +1575,7 @@ pub async fn send (ctx: MmArc, peer: bits256, subject: Vec<u8>, fallback: u8, pa
 
     // Tell `peers_thread` to save the data.
     try_s! (pctx.cmd_tx.send (LtCommand::Put {seed: peer, salt, payload, send_handler, fallback}));
+    ctx.metrics.inc_counter ("mm2_p2p_messages_sent_total", &[("transport", "dht")]);
 
     Ok (send_handler_arc.into())
 }
@@ -1685,8 +1686,9 @@ pub async fn recv (ctx: MmArc, subject: Vec<u8>, fallback: u8, validator: FixedV
     // NB: There should be no zero bytes in the salt (due to `CStr::from_ptr` and the possibility of a similar problem abroad).
     let salt = format_radix (checksum_ecma (&subject), 36);
 
-    let rc = RecvFuture {pctx, seed, salt, validator, frid: None, fallback} .compat() .await;
-    Ok (try_s! (rc))
+    let rc = try_s! (RecvFuture {pctx, seed, salt, validator, frid: None, fallback} .compat() .await);
+    ctx.metrics.inc_counter ("mm2_p2p_messages_received_total", &[("transport", "dht")]);
+    Ok (rc)
 }
 
 /*
@@ -22,11 +22,12 @@
 
 use bytes::Bytes;
 use coins::{get_enabled_coins, get_trade_fee, send_raw_transaction, set_required_confirmations, withdraw, my_tx_history};
-use common::{err_to_rpc_json_string, HyRes};
+use common::{err_to_rpc_json_string, now_float, HyRes};
 #[cfg(feature = "native")]
 use common::wio::{slurp_reqʰ, CORE, CPUPOOL, HTTP};
 use common::lift_body::LiftBody;
 use common::mm_ctx::MmArc;
+use common::rpc_error::{RpcError, RpcErrorCode};
 #[cfg(feature = "native")]
 use common::mm_ctx::ctx2helpers;
 #[cfg(feature = "native")]
@@ -41,15 +42,26 @@ use http::header::{HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE, CONTE
 #[cfg(feature = "native")]
 use hyper::{self, service::Service};
 use serde_json::{self as json, Value as Json};
+use std::fmt;
 use std::future::{Future as Future03};
 use std::net::SocketAddr;
 #[cfg(feature = "native")]
 use tokio_core::net::TcpListener;
 
 use crate::mm2::lp_network;
-use crate::mm2::lp_ordermatch::{buy, cancel_all_orders, cancel_order, my_orders, order_status, orderbook, sell, set_price};
-use crate::mm2::lp_swap::{coins_needed_for_kick_start, import_swaps,  my_swap_status, my_recent_swaps,
-                          recover_funds_of_swap, stats_swap_status};
+use crate::mm2::lp_ordermatch::{ban_pubkey, best_orders, buy, cancel_all_orders, cancel_conditional_order, cancel_order,
+                                conditional_order, my_conditional_orders, my_orders, order_status, orderbook,
+                                orderbook_limits, orders_history, sell, set_price, unban_pubkey, update_maker_order};
+use crate::mm2::lp_swap::{coins_needed_for_kick_start, import_swaps,  my_swap_status, my_recent_swaps, ohlc,
+                          pending_recoveries, recover_funds_of_swap, stats_swap_status, ticker};
+
+#[path = "rpc/api_keys.rs"]
+pub mod api_keys;
+use self::api_keys::{check_api_key, list_api_keys, revoke_api_key};
+
+#[path = "rpc/jsonrpc.rs"]
+pub mod jsonrpc;
+use self::jsonrpc::{is_jsonrpc, jsonrpc_service, looks_like_jsonrpc, parse_error_response};
 
 #[path = "rpc/lp_commands.rs"]
 pub mod lp_commands;
@@ -58,6 +70,21 @@ use self::lp_commands::*;
 #[path = "rpc/lp_signatures.rs"]
 pub mod lp_signatures;
 
+#[path = "rpc/prometheus.rs"]
+pub mod prometheus;
+
+#[path = "rpc/rpc_schema.rs"]
+pub mod rpc_schema;
+use self::rpc_schema::{help, rpc_schema};
+
+#[cfg(feature = "native")]
+#[path = "rpc/rpc_tls.rs"]
+mod rpc_tls;
+
+#[cfg(feature = "native")]
+#[path = "rpc/ws_events.rs"]
+pub mod ws_events;
+
 /// Lists the RPC method not requiring the "userpass" authentication.  
 /// None is also public to skip auth and display proper error in case of method is missing
 const PUBLIC_METHODS: &[Option<&str>] = &[  // Sorted alphanumerically (on the first letter) for readability.
@@ -73,6 +100,7 @@ const PUBLIC_METHODS: &[Option<&str>] = &[  // Sorted alphanumerically (on the f
     Some("passphrase"),  // Manually checks the "passphrase".
     Some("pricearray"),
     Some("psock"),
+    Some("rpc_schema"),
     Some("statsdisp"),
     Some("stats_swap_status"),
     Some("tradesarray"),
@@ -80,6 +108,29 @@ const PUBLIC_METHODS: &[Option<&str>] = &[  // Sorted alphanumerically (on the f
     None
 ];
 
+/// The origin of an RPC request.
+#[derive(Clone, Copy, Debug)]
+pub enum RpcClient {
+    Tcp (SocketAddr),
+    /// Connected through the `rpc_unix_socket`, the access being controlled by the socket file permissions.
+    Unix
+}
+
+impl RpcClient {
+    /// True for the loopback TCP clients and for the Unix socket clients.
+    pub fn is_local (&self) -> bool {
+        match self {
+            RpcClient::Tcp (addr) => addr.ip().is_loopback(),
+            RpcClient::Unix => true
+}   }   }
+
+impl fmt::Display for RpcClient {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpcClient::Tcp (addr) => write! (f, "{}", addr),
+            RpcClient::Unix => write! (f, "unix socket")
+}   }   }
+
 #[allow(unused_macros)]
 macro_rules! unwrap_or_err_response {
     ($e:expr, $($args:tt)*) => {
@@ -99,12 +150,12 @@ macro_rules! unwrap_or_err_response {
 ///       -d 'd18:secp256k1_key_pair38:.0..Z......g)e.Q.@..d.sn<.v..>0.P....Ie'
 /// 
 #[cfg(feature = "native")]
-async fn helpers (ctx: MmArc, client: SocketAddr, req: Parts,
+async fn helpers (ctx: MmArc, client: RpcClient, req: Parts,
                   reqᵇ: Box<dyn Stream<Item=Bytes,Error=String>+Send>) -> Result<Response<Vec<u8>>, String> {
     let ct = try_s! (req.headers.get (CONTENT_TYPE) .ok_or ("No Content-Type"));
     if ct.as_bytes() != b"application/octet-stream" {return ERR! ("Unexpected Content-Type")}
 
-    if !client.ip().is_loopback() {return ERR! ("Not local")}
+    if !client.is_local() {return ERR! ("Not local")}
 
     let reqᵇ = try_s! (reqᵇ.concat2().compat().await);
     //log! ("helpers] " [=req] ", " (gstuff::binprint (&reqᵇ, b'.')));
@@ -154,22 +205,45 @@ struct RpcService {
     /// Allows us to get the `MmCtx` if it is still around.
     ctx_h: u32,
     /// The IP and port from whence the request is coming from.
-    client: SocketAddr,
+    client: RpcClient,
 }
 
-fn auth(json: &Json, ctx: &MmArc) -> Result<(), &'static str> {
+/// The `rpc_password` allows any method,
+/// whereas the `rpc_api_keys` are limited to their own lists of methods (cf. `api_keys`).
+fn auth(json: &Json, ctx: &MmArc) -> Result<(), String> {
     if !PUBLIC_METHODS.contains(&json["method"].as_str()) {
-        if !json["userpass"].is_string() {
-            return Err("Userpass is not set!");
-        }
+        let userpass = match json["userpass"].as_str() {
+            Some(userpass) => userpass,
+            None => return Err("Userpass is not set!".into()),
+        };
 
         if json["userpass"] != ctx.conf["rpc_password"] {
-            return Err("Userpass is invalid!");
+            return check_api_key(ctx, userpass, json["method"].as_str().unwrap_or(""));
         }
     }
     Ok(())
 }
 
+/// Checks whether the `client` may invoke the method of the request.
+/// Shared between the legacy and the JSON-RPC modes.
+fn check_access(ctx: &MmArc, req: &Json, client: RpcClient) -> Result<(), RpcError> {
+    // https://github.com/artemii235/SuperNET/issues/368
+    let local_only = ctx.conf["rpc_local_only"].as_bool().unwrap_or(true);
+    if local_only && !client.is_local() && !PUBLIC_METHODS.contains (&req["method"].as_str()) {
+        return Err (RpcError::new (RpcErrorCode::Unauthorized, ERRL! ("Selected method can be called from localhost only!")))
+    }
+    if let Err (err) = auth (req, ctx) {return Err (RpcError::new (RpcErrorCode::Unauthorized, ERRL! ("{}", err)))}
+    Ok(())
+}
+
+/// Runs the `handler` found by the `dispatcher`, recording its latency for the "/metrics".
+async fn run_handler (ctx: &MmArc, method: &str, handler: HyRes) -> Result<Response<Vec<u8>>, String> {
+    let started = now_float();
+    let res = handler.compat().await;
+    ctx.metrics.observe ("mm2_rpc_request_duration_seconds", &[("method", method)], now_float() - started);
+    res
+}
+
 /// Result of `fn dispatcher`.
 pub enum DispatcherRes {
     /// `fn dispatcher` has found a Rust handler for the RPC "method".
@@ -197,10 +271,14 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
     };
     DispatcherRes::Match (match &method[..] {  // Sorted alphanumerically (on the first latter) for readability.
         // "autoprice" => lp_autoprice (ctx, req),
-        "buy" => buy (ctx, req),
+        "ban_pubkey" => ban_pubkey (ctx, req),
+        "best_orders" => best_orders (ctx, req),
+        "buy" => hyres(buy(ctx, req)),
         "cancel_all_orders" => cancel_all_orders (ctx, req),
+        "cancel_conditional_order" => cancel_conditional_order (ctx, req),
         "cancel_order" => cancel_order (ctx, req),
         "coins_needed_for_kick_start" => hyres(coins_needed_for_kick_start(ctx)),
+        "conditional_order" => conditional_order (ctx, req),
         "disable_coin" => disable_coin(ctx, req),
         // TODO coin initialization performs blocking IO, i.e request.wait(), have to run it on CPUPOOL to avoid blocking shared CORE.
         //      at least until we refactor the functions like `utxo_coin_from_iguana_info` to async versions.
@@ -216,20 +294,28 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
             }
             #[cfg(not(feature = "native"))] {return DispatcherRes::NoMatch (req)}
         },
+        "list_api_keys" => list_api_keys(ctx),
         // "inventory" => inventory (ctx, req),
+        "my_conditional_orders" => my_conditional_orders (ctx),
         "my_orders" => my_orders (ctx),
         "my_balance" => my_balance (ctx, req),
         "my_tx_history" => my_tx_history(ctx, req),
         "notify" => lp_signatures::lp_notify_recv (ctx, req),  // Invoked usually from the `lp_command_q_loop`
+        "ohlc" => ohlc (ctx, req),
         "orderbook" => orderbook (ctx, req),
+        "orderbook_limits" => orderbook_limits (ctx),
         "order_status" => order_status (ctx, req),
+        "orders_history" => orders_history (ctx, req),
         // "passphrase" => passphrase (ctx, req),
+        "pending_recoveries" => pending_recoveries (ctx),
         "sell" => hyres(sell(ctx, req)),
         "send_raw_transaction" => send_raw_transaction (ctx, req),
-        "setprice" => set_price (ctx, req),
+        "setprice" => hyres(set_price(ctx, req)),
         "stop" => stop (ctx),
         "my_recent_swaps" => my_recent_swaps(ctx, req),
         "my_swap_status" => my_swap_status(ctx, req),
+        "revoke_api_key" => revoke_api_key(ctx, req),
+        "rpc_schema" => rpc_schema(),
         "recover_funds_of_swap" => {
             #[cfg(feature = "native")] {
                 Box::new(CPUPOOL.spawn_fn(move || { hyres(recover_funds_of_swap (ctx, req)) }))
@@ -238,6 +324,9 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
         },
         "set_required_confirmations" => hyres(set_required_confirmations(ctx, req)),
         "stats_swap_status" => stats_swap_status(ctx, req),
+        "ticker" => ticker (ctx, req),
+        "unban_pubkey" => unban_pubkey (ctx, req),
+        "update_maker_order" => hyres(update_maker_order(ctx, req)),
         "version" => version(),
         "withdraw" => withdraw(ctx, req),
         _ => return DispatcherRes::NoMatch (req)
@@ -247,7 +336,12 @@ pub fn dispatcher (req: Json, ctx: MmArc) -> DispatcherRes {
 type RpcRes = Box<dyn Future<Item=Response<LiftBody<Vec<u8>>>, Error=String> + Send>;
 
 async fn rpc_serviceʹ (ctx: MmArc, req: Parts, reqᵇ: Box<dyn Stream<Item=Bytes, Error=String> + Send>,
-                       client: SocketAddr) -> Result<Response<Vec<u8>>, String> {
+                       client: RpcClient) -> Result<Response<Vec<u8>>, String> {
+    if req.method == Method::GET && req.uri.path() == "/metrics" {
+        let userpass = prometheus::bearer_token (&req.headers);
+        if let Err (err) = check_access (&ctx, &json! ({"method": "metrics", "userpass": userpass}), client) {return err.into_response()}
+        return prometheus::metrics (ctx) .await
+    }
     if req.method != Method::POST {return ERR! ("Only POST requests are supported!")}
 
     #[cfg(feature = "native")] {
@@ -256,25 +350,27 @@ async fn rpc_serviceʹ (ctx: MmArc, req: Parts, reqᵇ: Box<dyn Stream<Item=Byte
     }
 
     let reqᵇ = try_s! (reqᵇ.concat2().compat().await);
-    let reqʲ: Json = try_s! (json::from_slice (&reqᵇ));
+    let reqʲ: Json = match json::from_slice (&reqᵇ) {
+        Ok (reqʲ) => reqʲ,
+        Err (err) if looks_like_jsonrpc (&reqᵇ) => return parse_error_response (&fomat! ((err))),
+        Err (err) => return RpcError::new (RpcErrorCode::InvalidRequest, ERRL! ("{}", err)) .into_response()
+    };
+    if is_jsonrpc (&reqʲ) {return jsonrpc_service (ctx, reqʲ, client) .await}
 
-    // https://github.com/artemii235/SuperNET/issues/368
-    let local_only = ctx.conf["rpc_local_only"].as_bool().unwrap_or(true);
-    if local_only && !client.ip().is_loopback() && !PUBLIC_METHODS.contains (&reqʲ["method"].as_str()) {
-        return ERR! ("Selected method can be called from localhost only!")
-    }
-    try_s! (auth (&reqʲ, &ctx));
+    if let Err (err) = check_access (&ctx, &reqʲ, client) {return err.into_response()}
 
+    let method = reqʲ["method"].as_str().unwrap_or ("") .to_owned();
     let handler = match dispatcher (reqʲ, ctx.clone()) {
         DispatcherRes::Match (handler) => handler,
-        DispatcherRes::NoMatch (req) => return ERR! ("No such method: {:?}", req["method"])
+        DispatcherRes::NoMatch (req) =>
+            return RpcError::new (RpcErrorCode::NoSuchMethod, ERRL! ("No such method: {:?}", req["method"])) .into_response()
     };
-    let res = try_s! (handler.compat().await);
+    let res = try_s! (run_handler (&ctx, &method, handler) .await);
     Ok (res)
 }
 
 #[cfg(feature = "native")]
-async fn rpc_service (req: Request<hyper::Body>, ctx_h: u32, client: SocketAddr) -> Response<LiftBody<Vec<u8>>> {
+async fn rpc_service (req: Request<hyper::Body>, ctx_h: u32, client: RpcClient) -> Response<LiftBody<Vec<u8>>> {
     macro_rules! try_sf {($value: expr) => {match $value {Ok (ok) => ok, Err (err) => {
         log! ("RPC error response: " (err));
         let ebody = err_to_rpc_json_string (&fomat! ((err)));
@@ -288,6 +384,12 @@ async fn rpc_service (req: Request<hyper::Body>, ctx_h: u32, client: SocketAddr)
         None => HeaderValue::from_static ("http://localhost:3000"),
     };
 
+    if ws_events::is_ws_upgrade (req.headers()) {
+        let mut res = try_sf! (ws_events::ws_upgrade (ctx, req, client));
+        res.headers_mut().insert (ACCESS_CONTROL_ALLOW_ORIGIN, rpc_cors);
+        return res
+    }
+
     // Convert the native Hyper stream into a portable stream of `Bytes`.
     let (req, reqᵇ) = req.into_parts();
     let reqᵇ = Box::new (reqᵇ.then (|chunk| -> Result<Bytes, String> {
@@ -323,24 +425,27 @@ impl Service for RpcService {
     type Future = RpcRes;
 
     fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
-        let f = rpc_service (req, self.ctx_h, self.client.clone());
+        let f = rpc_service (req, self.ctx_h, self.client);
         let f = Compat::new (Box::pin (f.map (|r|->Result<_,String>{Ok(r)})));
         Box::new (f)
     }
 }
 
 #[cfg(feature = "native")]
-pub extern fn spawn_rpc(ctx_h: u32) {
+type RpcServer = Box<dyn Future<Item=(), Error=()> + Send>;
+
+#[cfg(feature = "native")]
+fn tcp_server (ctx: &MmArc, ctx_h: u32) -> Result<RpcServer, String> {
     // NB: We need to manually handle the incoming connections in order to get the remote IP address,
     // cf. https://github.com/hyperium/hyper/issues/1410#issuecomment-419510220.
     // Although if the ability to access the remote IP address is solved by the Hyper in the future
     // then we might want to refactor into starting it ideomatically in order to benefit from a more graceful shutdown,
     // cf. https://github.com/hyperium/hyper/pull/1640.
 
-    let ctx = unwrap! (MmArc::from_ffi_handle (ctx_h), "No context");
-
-    let rpc_ip_port = unwrap! (ctx.rpc_ip_port());
-    let listener = unwrap! (TcpListener::bind2 (&rpc_ip_port), "Can't bind on {}", rpc_ip_port);
+    let rpc_ip_port = try_s! (ctx.rpc_ip_port());
+    let listener = try_s! (TcpListener::bind2 (&rpc_ip_port) .map_err (|err| ERRL! ("Can't bind on {}: {}", rpc_ip_port, err)));
+    let tls_acceptor = try_s! (rpc_tls::rpc_tls_acceptor (&ctx.conf) .map_err (|err| ERRL! ("Can't initialize the rpc_tls: {}", err)));
+    let scheme = if tls_acceptor.is_some() {"https"} else {"http"};
 
     let server = listener
         .incoming()
@@ -353,21 +458,118 @@ pub extern fn spawn_rpc(ctx_h: u32) {
                 }
             };
 
-            unwrap!(CORE.lock()).spawn(
-                HTTP.serve_connection(
-                    socket,
-                    RpcService {
-                        ctx_h,
-                        client
-                    },
-                )
-                .map(|_| ())
+            let service = RpcService {
+                ctx_h,
+                client: RpcClient::Tcp (client)
+            };
+            match tls_acceptor {
+                Some (ref tls_acceptor) => {
+                    unwrap!(CORE.lock()).spawn(
+                        tls_acceptor.accept(socket)
+                        .map_err (move |err| log! ({"spawn_rpc] TLS handshake with {} failed: {}", client, err}))
+                        .and_then (move |stream| HTTP.serve_connection(stream, service)
+                            // Allows the `ws_events` to take over the connection.
+                            .with_upgrades()
+                            .map(|_| ())
+                            .map_err (|err| log! ({"spawn_rpc] HTTP error: {}", err})))
+                    );
+                },
+                None => {
+                    unwrap!(CORE.lock()).spawn(
+                        HTTP.serve_connection(socket, service)
+                        // Allows the `ws_events` to take over the connection.
+                        .with_upgrades()
+                        .map(|_| ())
+                        .map_err (|err| log! ({"spawn_rpc] HTTP error: {}", err}))
+                    );
+                },
+            }
+            Ok(())
+        })
+        .map_err (|err| log! ({"spawn_rpc] accept error: {}", err}));
+
+    log!(">>>>>>>>>> DEX stats " (rpc_ip_port.ip())":"(rpc_ip_port.port()) " \
+            DEX stats API enabled at unixtime." (gstuff::now_ms() / 1000) " <<<<<<<<<");
+    log!("RPC is served over " (scheme));
+    Ok (Box::new (server))
+}
+
+/// Serves the RPC on the "rpc_unix_socket" instead of the TCP port.
+///
+/// The socket file is created with the "rpc_unix_socket_mode" permissions ("600" by default, octal),
+/// only the users able to write into the socket file can talk to the node.
+/// The socket is bound in a private directory and moved to the `path` after its permissions are set,
+/// so that it's never accessible with the umask default permissions.
+#[cfg(all(feature = "native", unix))]
+fn unix_socket_server (ctx: &MmArc, ctx_h: u32, path: &str) -> Result<RpcServer, String> {
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use std::path::Path;
+    use tokio_uds::UnixListener;
+
+    // Remove the socket left by the previous run.
+    if let Ok (meta) = fs::symlink_metadata (path) {
+        if !meta.file_type().is_socket() {return ERR! ("rpc_unix_socket {} exists and is not a socket", path)}
+        try_s! (fs::remove_file (path));
+    }
+    let mode = match ctx.conf["rpc_unix_socket_mode"].as_str() {
+        Some (mode) => try_s! (u32::from_str_radix (mode, 8)),
+        None => 0o600
+    };
+    let dir = match Path::new (path) .parent() {
+        Some (parent) if parent != Path::new ("") => parent.to_owned(),
+        _ => Path::new (".") .to_owned()
+    };
+    let private_dir = dir.join (fomat! (".rpc_unix_socket." (std::process::id())));
+    try_s! (fs::DirBuilder::new().mode (0o700) .create (&private_dir)
+        .map_err (|err| ERRL! ("Can't create {}: {}", private_dir.display(), err)));
+    let private_path = private_dir.join ("rpc.sock");
+    let listener = (|| -> Result<UnixListener, String> {
+        let listener = try_s! (UnixListener::bind (&private_path) .map_err (|err| ERRL! ("Can't bind on {}: {}", path, err)));
+        try_s! (fs::set_permissions (&private_path, fs::Permissions::from_mode (mode)));
+        try_s! (fs::rename (&private_path, path));
+        Ok (listener)
+    })();
+    if let Err (err) = fs::remove_dir_all (&private_dir) {log! ("spawn_rpc] Can't remove " (private_dir.display()) ": " (err))}
+    let listener = try_s! (listener);
+
+    let server = listener
+        .incoming()
+        .for_each (move |socket| {
+            let service = RpcService {ctx_h, client: RpcClient::Unix};
+            unwrap! (CORE.lock()) .spawn (
+                HTTP.serve_connection (socket, service)
+                .with_upgrades()
+                .map (|_| ())
                 .map_err (|err| log! ({"spawn_rpc] HTTP error: {}", err}))
             );
             Ok(())
         })
         .map_err (|err| log! ({"spawn_rpc] accept error: {}", err}));
 
+    let path = path.to_owned();
+    log!(">>>>>>>>>> DEX stats " (path) " DEX stats API enabled at unixtime." (gstuff::now_ms() / 1000) " <<<<<<<<<");
+    log!("RPC is served over the Unix socket");
+    Ok (Box::new (server.then (move |_| {
+        if let Err (err) = std::fs::remove_file (&path) {log! ("spawn_rpc] Can't remove " (path) ": " (err))}
+        Ok(())
+    })))
+}
+
+#[cfg(all(feature = "native", not(unix)))]
+fn unix_socket_server (_ctx: &MmArc, _ctx_h: u32, _path: &str) -> Result<RpcServer, String> {
+    ERR! ("rpc_unix_socket is not supported on this platform")
+}
+
+#[cfg(feature = "native")]
+pub extern fn spawn_rpc(ctx_h: u32) {
+    let ctx = unwrap! (MmArc::from_ffi_handle (ctx_h), "No context");
+
+    let server = match ctx.conf["rpc_unix_socket"].as_str() {
+        Some (path) => unwrap! (unix_socket_server (&ctx, ctx_h, path)),
+        None => unwrap! (tcp_server (&ctx, ctx_h))
+    };
+
     // Finish the server `Future` when `shutdown_rx` fires.
 
     let (shutdown_tx, shutdown_rx) = futures01::sync::oneshot::channel::<()>();
@@ -381,10 +583,7 @@ pub extern fn spawn_rpc(ctx_h: u32) {
         } else {ERR! ("on_stop callback called twice!")}
     }));
 
-    let rpc_ip_port = unwrap! (ctx.rpc_ip_port());
     unwrap! (CORE.lock()) .spawn ({
-        log!(">>>>>>>>>> DEX stats " (rpc_ip_port.ip())":"(rpc_ip_port.port()) " \
-                DEX stats API enabled at unixtime." (gstuff::now_ms() / 1000) " <<<<<<<<<");
         let _ = ctx.rpc_started.pin (true);
         server
     });
@@ -401,6 +600,6 @@ pub fn init_header_slots() {
     fn rpc_service_fn (
         ctx: MmArc, req: Parts, reqᵇ: Box<dyn Stream<Item=Bytes, Error=String> + Send>, client: SocketAddr)
         -> Pin<Box<dyn Future03<Output=Result<Response<Vec<u8>>, String>> + Send>> {
-            Box::pin (rpc_serviceʹ (ctx, req, reqᵇ, client))}
+            Box::pin (rpc_serviceʹ (ctx, req, reqᵇ, RpcClient::Tcp (client)))}
     let _ = RPC_SERVICE.pin (rpc_service_fn);
 }
@@ -0,0 +1,144 @@
+//! Named RPC keys limited to a set of methods.
+//!
+//! The `rpc_password` keeps the full access, whereas the keys from the "rpc_api_keys" configuration
+//! can be handed to the dashboards and bots which should only invoke a few methods:
+//!
+//!     "rpc_api_keys": [
+//!         {"name": "dashboard", "key": "...", "methods": ["my_balance", "orderbook", "my_recent_swaps"]},
+//!         {"name": "bot", "key": "...", "methods": ["setprice", "buy", "sell", "cancel_order", "my_orders"]}
+//!     ]
+//!
+//! "*" among the "methods" allows any method.
+//! The key is passed in the "userpass" field, same as the `rpc_password`.
+//!
+//! A key revoked with the "revoke_api_key" RPC stays revoked across restarts
+//! (we remember the hash of the key in the database) until it is replaced in the configuration.
+
+use bitcrypto::sha256;
+use common::{rpc_err_response, rpc_response, slurp, write, HyRes};
+use common::mm_ctx::{from_ctx, MmArc};
+use serde_json::{self as json, Value as Json};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApiKey {
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key: String,
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+impl ApiKey {
+    fn allows (&self, method: &str) -> bool {
+        self.methods.iter().any (|m| m == "*" || m == method)
+    }
+
+    fn key_hash (&self) -> String {
+        hex::encode (&*sha256 (self.key.as_bytes()))
+    }
+}
+
+pub struct RpcContext {
+    pub api_keys: Mutex<Vec<ApiKey>>,
+}
+
+impl RpcContext {
+    /// Obtains a reference to this crate context, creating it if necessary.
+    pub fn from_ctx (ctx: &MmArc) -> Result<Arc<RpcContext>, String> {
+        Ok (try_s! (from_ctx (&ctx.rpc_ctx, move || {
+            let mut api_keys = try_s! (api_keys_from_conf (&ctx.conf));
+            let revoked = try_s! (load_revoked_key_hashes (ctx));
+            for key in api_keys.iter_mut() {
+                if revoked.contains (&key.key_hash()) {key.revoked = true}
+            }
+            Ok (RpcContext {
+                api_keys: Mutex::new (api_keys),
+            })
+        })))
+    }
+}
+
+fn api_keys_from_conf (conf: &Json) -> Result<Vec<ApiKey>, String> {
+    if conf["rpc_api_keys"].is_null() {return Ok (Vec::new())}
+    let api_keys: Vec<ApiKey> = try_s! (json::from_value (conf["rpc_api_keys"].clone()));
+    let mut names = HashSet::new();
+    let mut keys = HashSet::new();
+    for api_key in api_keys.iter() {
+        if api_key.name.is_empty() {return ERR! ("rpc_api_keys: the key name must not be empty")}
+        if api_key.key.is_empty() {return ERR! ("rpc_api_keys: the key '{}' must not be empty", api_key.name)}
+        if api_key.methods.is_empty() {return ERR! ("rpc_api_keys: the key '{}' has no methods", api_key.name)}
+        if conf["rpc_password"].as_str() == Some (&api_key.key[..]) {
+            return ERR! ("rpc_api_keys: the key '{}' matches the rpc_password", api_key.name)
+        }
+        if !names.insert (&api_key.name) {return ERR! ("rpc_api_keys: duplicate name '{}'", api_key.name)}
+        if !keys.insert (&api_key.key) {return ERR! ("rpc_api_keys: the key '{}' is used twice", api_key.name)}
+    }
+    Ok (api_keys)
+}
+
+/// Invoked from `lp_main` in order to report the configuration errors early.
+pub fn validate_api_keys_conf (conf: &Json) -> Result<(), String> {
+    try_s! (api_keys_from_conf (conf));
+    Ok(())
+}
+
+fn revoked_api_keys_path (ctx: &MmArc) -> PathBuf {
+    ctx.dbdir().join ("REVOKED_API_KEYS.json")
+}
+
+fn load_revoked_key_hashes (ctx: &MmArc) -> Result<HashSet<String>, String> {
+    let content = slurp (&revoked_api_keys_path (ctx));
+    if content.is_empty() {return Ok (HashSet::new())}
+    Ok (try_s! (json::from_slice (&content)))
+}
+
+/// Checks that the `userpass` is one of the active API keys and that it allows the `method`.
+pub fn check_api_key (ctx: &MmArc, userpass: &str, method: &str) -> Result<(), String> {
+    let rpc_ctx = try_s! (RpcContext::from_ctx (ctx));
+    let api_keys = try_s! (rpc_ctx.api_keys.lock());
+    let api_key = match api_keys.iter().find (|k| k.key == userpass) {
+        Some (k) if !k.revoked => k,
+        _ => return Err ("Userpass is invalid!".into())
+    };
+    if !api_key.allows (method) {
+        return Err (fomat! ("API key '" (api_key.name) "' is not allowed to call '" (method) "'"))
+    }
+    Ok(())
+}
+
+pub fn list_api_keys (ctx: MmArc) -> HyRes {
+    let rpc_ctx = try_h! (RpcContext::from_ctx (&ctx));
+    let api_keys = try_h! (rpc_ctx.api_keys.lock());
+    rpc_response (200, json! ({
+        "result": *api_keys
+    }).to_string())
+}
+
+#[derive(Deserialize)]
+struct RevokeApiKeyReq {
+    name: String,
+}
+
+pub fn revoke_api_key (ctx: MmArc, req: Json) -> HyRes {
+    let req: RevokeApiKeyReq = try_h! (json::from_value (req));
+    let rpc_ctx = try_h! (RpcContext::from_ctx (&ctx));
+    let mut api_keys = try_h! (rpc_ctx.api_keys.lock());
+    match api_keys.iter_mut().find (|k| k.name == req.name) {
+        Some (api_key) => api_key.revoked = true,
+        None => return rpc_err_response (404, &fomat! ("API key '" (req.name) "' is not found"))
+    }
+
+    let mut revoked = try_h! (load_revoked_key_hashes (&ctx));
+    for api_key in api_keys.iter().filter (|k| k.revoked) {revoked.insert (api_key.key_hash());}
+    let content = try_h! (json::to_vec (&revoked));
+    try_h! (write (&revoked_api_keys_path (&ctx), &content));
+
+    log! ("API key '" (req.name) "' is revoked");
+    rpc_response (200, json! ({
+        "result": "success"
+    }).to_string())
+}
@@ -0,0 +1,144 @@
+//! JSON-RPC 2.0 mode of the RPC endpoint (https://www.jsonrpc.org/specification).
+//!
+//! Selected when the request has the `"jsonrpc": "2.0"` field or is a batch array:
+//!
+//!     [{"jsonrpc": "2.0", "id": 1, "method": "my_balance", "params": {"coin": "KMD"}, "userpass": "..."},
+//!      {"jsonrpc": "2.0", "id": 2, "method": "my_balance", "params": {"coin": "BTC"}, "userpass": "..."}]
+//!
+//! Only the by-name (object) `params` are supported, they are passed to the usual handlers as the top-level fields
+//! (and as the "params" field for the handlers reading it, like `my_swap_status`).
+//! The "userpass" can be given either at the top level or among the `params`.
+//! The legacy `{"result": ...}` response is unwrapped into the JSON-RPC "result",
+//! the legacy `{"error": "..."}` response is turned into the JSON-RPC error object with the `HANDLER_ERROR` code
+//! (or a standard JSON-RPC code matching the "error_code", the legacy response being passed in the error "data").
+
+use common::mm_ctx::MmArc;
+use futures::future::join_all;
+use http::Response;
+use http::header::CONTENT_TYPE;
+use serde_json::{self as json, Value as Json};
+
+use super::{check_access, dispatcher, run_handler, DispatcherRes, RpcClient};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The handler returned an error.
+pub const HANDLER_ERROR: i64 = -32000;
+/// Authentication failed or the method is not available to the client.
+pub const UNAUTHORIZED: i64 = -32001;
+
+/// True if the request should be processed in the JSON-RPC 2.0 mode.
+pub fn is_jsonrpc (req: &Json) -> bool {
+    req.is_array() || req["jsonrpc"].is_string()
+}
+
+/// The request body that failed to parse as JSON is only treated as JSON-RPC if it mentions the "jsonrpc".
+pub fn looks_like_jsonrpc (body: &[u8]) -> bool {
+    body.windows (9) .any (|w| w == b"\"jsonrpc\"")
+}
+
+fn error_response (id: Json, code: i64, message: &str, data: Option<Json>) -> Json {
+    let mut error = json! ({"code": code, "message": message});
+    if let Some (data) = data {error["data"] = data}
+    json! ({"jsonrpc": "2.0", "id": id, "error": error})
+}
+
+/// Converts the legacy handler response into the JSON-RPC result or error.
+fn handler_response (id: Json, res: Response<Vec<u8>>) -> Json {
+    let (parts, body) = res.into_parts();
+    let body: Json = match json::from_slice (&body) {
+        Ok (body) => body,
+        Err (_) => Json::String (String::from_utf8_lossy (&body) .into())
+    };
+    if !parts.status.is_success() || !body["error"].is_null() {
+        let message = match body["error"].as_str() {
+            Some (err) => err.to_owned(),
+            None => fomat! ("HTTP status " (parts.status))
+        };
+        // cf. `RpcErrorCode`
+        let code = match body["error_code"].as_str() {
+            Some ("InvalidRequest") => INVALID_PARAMS,
+            Some ("NoSuchMethod") => METHOD_NOT_FOUND,
+            Some ("Unauthorized") => UNAUTHORIZED,
+            _ => HANDLER_ERROR
+        };
+        return error_response (id, code, &message, Some (body))
+    }
+    let is_wrapped = body.as_object().map (|map| map.len() == 1 && map.contains_key ("result")) .unwrap_or (false);
+    let result = if is_wrapped {body["result"].clone()} else {body};
+    json! ({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+/// Processes a single JSON-RPC request, returning `None` for the notifications (requests without an "id").
+async fn jsonrpc_call (ctx: MmArc, req: Json, client: RpcClient) -> Option<Json> {
+    let id = req["id"].clone();
+    let is_notification = req.is_object() && req.get ("id") .is_none();
+    macro_rules! respond {($res: expr) => {return if is_notification {None} else {Some ($res)}}}
+
+    if req["jsonrpc"].as_str() != Some ("2.0") {
+        respond! (error_response (Json::Null, INVALID_REQUEST, "The \"jsonrpc\" field must be \"2.0\"", None))
+    }
+    if !(id.is_null() || id.is_string() || id.is_number()) {
+        respond! (error_response (Json::Null, INVALID_REQUEST, "The \"id\" must be a string or a number", None))
+    }
+    let method = match req["method"].as_str() {
+        Some (method) => method.to_owned(),
+        None => respond! (error_response (id, INVALID_REQUEST, "The \"method\" must be a string", None))
+    };
+
+    let mut legacy = match req["params"] {
+        Json::Object (ref params) => params.clone(),
+        Json::Null => json::Map::new(),
+        _ => respond! (error_response (id, INVALID_PARAMS, "Only the by-name (object) params are supported", None))
+    };
+    if !req["userpass"].is_null() && !legacy.contains_key ("userpass") {
+        legacy.insert ("userpass".into(), req["userpass"].clone());
+    }
+    if !req["params"].is_null() {legacy.insert ("params".into(), req["params"].clone());}
+    legacy.insert ("method".into(), Json::String (method.clone()));
+    let legacy = Json::Object (legacy);
+
+    if let Err (err) = check_access (&ctx, &legacy, client) {
+        respond! (error_response (id, UNAUTHORIZED, &err.message, Some (err.to_json())))
+    }
+    let handler = match dispatcher (legacy, ctx) {
+        DispatcherRes::Match (handler) => handler,
+        DispatcherRes::NoMatch (req) => respond! (error_response (id, METHOD_NOT_FOUND, &fomat! ("No such method: " (req["method"])), None))
+    };
+    match run_handler (&ctx, &method, handler) .await {
+        Ok (res) => respond! (handler_response (id, res)),
+        Err (err) => respond! (error_response (id, HANDLER_ERROR, &err, None))
+    }
+}
+
+fn json_response (body: &Json) -> Result<Response<Vec<u8>>, String> {
+    Ok (try_s! (Response::builder()
+        .header (CONTENT_TYPE, "application/json")
+        .body (try_s! (json::to_vec (body)))))
+}
+
+/// Handles the parsed JSON-RPC request or batch.
+pub async fn jsonrpc_service (ctx: MmArc, req: Json, client: RpcClient) -> Result<Response<Vec<u8>>, String> {
+    match req {
+        Json::Array (batch) => {
+            if batch.is_empty() {return json_response (&error_response (Json::Null, INVALID_REQUEST, "Empty batch", None))}
+            let responses: Vec<Json> = join_all (batch.into_iter().map (|req| jsonrpc_call (ctx.clone(), req, client))) .await
+                .into_iter().filter_map (|res| res) .collect();
+            // "If there are no Response objects contained within the Response array as it is to be sent to the client,
+            // the server MUST NOT return an empty Array and should return nothing at all."
+            if responses.is_empty() {return Ok (try_s! (Response::builder().status (204) .body (Vec::new())))}
+            json_response (&Json::Array (responses))
+        },
+        req => match jsonrpc_call (ctx, req, client) .await {
+            Some (res) => json_response (&res),
+            None => Ok (try_s! (Response::builder().status (204) .body (Vec::new())))
+        }
+    }
+}
+
+/// The response to a request body which isn't a valid JSON.
+pub fn parse_error_response (err: &str) -> Result<Response<Vec<u8>>, String> {
+    json_response (&error_response (Json::Null, PARSE_ERROR, err, None))
+}
@@ -24,6 +24,7 @@
 use coins::{disable_coin as disable_coin_impl, lp_coinfind, lp_coininit, MmCoinEnum};
 use common::{rpc_err_response, rpc_response, HyRes, MM_VERSION};
 use common::executor::{spawn, Timer};
+use common::rpc_error::{rpc_typed_err_response, RpcError, RpcErrorCode};
 use common::mm_ctx::MmArc;
 use futures01::Future;
 use futures::compat::Future01CompatExt;
@@ -32,32 +33,37 @@ use http::Response;
 use serde_json::{self as json, Value as Json};
 
 use crate::mm2::lp_ordermatch::{CancelBy, cancel_orders_by};
-use crate::mm2::lp_swap::{get_locked_amount, active_swaps_using_coin};
+use crate::mm2::lp_swap::{check_coin_swap_conf, get_locked_amount, active_swaps_using_coin};
 
 /// Attempts to disable the coin
 pub fn disable_coin (ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h!(req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th!(InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let _coin = match block_on (lp_coinfind (&ctx, &ticker)) {
         Ok (Some (t)) => t,
-        Ok (None) => return rpc_err_response (500, &fomat! ("No such coin: " (ticker))),
+        Ok (None) => return rpc_typed_err_response (RpcError::new (RpcErrorCode::CoinNotEnabled, fomat! ("No such coin: " (ticker)))),
         Err (err) => return rpc_err_response (500, &fomat! ("!lp_coinfind(" (ticker) "): " (err)))
     };
     let swaps = try_h!(active_swaps_using_coin(&ctx, &ticker));
     if !swaps.is_empty() {
-        return rpc_response (500, json!({
-            "error": fomat! ("There're active swaps using " (ticker)),
-            "swaps": swaps,
-        }).to_string());
+        let err = RpcError::new (RpcErrorCode::CoinIsInUse, fomat! ("There're active swaps using " (ticker)))
+            .with_details (json!({"swaps": swaps}));
+        let mut res = err.to_json();
+        // The "swaps" field is kept at the top level for the backward compatibility.
+        res["swaps"] = json!(swaps);
+        return rpc_response (500, res.to_string());
     }
     let (cancelled, still_matching) = try_h!(cancel_orders_by(&ctx, CancelBy::Coin{ ticker: ticker.clone() }));
     if !still_matching.is_empty() {
-        return rpc_response (500, json!({
-            "error": fomat! ("There're currently matching orders using " (ticker)),
-            "orders": {
-                "matching": still_matching,
-                "cancelled": cancelled,
-            }
-        }).to_string());
+        let orders = json!({
+            "matching": still_matching,
+            "cancelled": cancelled,
+        });
+        let err = RpcError::new (RpcErrorCode::CoinIsInUse, fomat! ("There're currently matching orders using " (ticker)))
+            .with_details (json!({"orders": orders}));
+        let mut res = err.to_json();
+        // The "orders" field is kept at the top level for the backward compatibility.
+        res["orders"] = orders;
+        return rpc_response (500, res.to_string());
     }
 
     try_h!(disable_coin_impl(&ctx, &ticker));
@@ -71,9 +77,13 @@ pub fn disable_coin (ctx: MmArc, req: Json) -> HyRes {
 
 /// Enable a coin in the Electrum mode.
 pub async fn electrum (ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
-    let ticker = try_s! (req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
-    let coin: MmCoinEnum = try_s! (lp_coininit (&ctx, &ticker, &req) .await);
-    let balance = try_s! (coin.my_balance().compat().await);
+    let ticker = try_tr! (InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    if try_s! (lp_coinfind (&ctx, &ticker) .await) .is_some() {
+        return RpcError::new (RpcErrorCode::CoinAlreadyEnabled, ERRL! ("Coin {} already initialized", ticker)) .into_response()
+    }
+    try_tr! (CoinConfigError, check_coin_swap_conf (&ctx.conf, &ticker));
+    let coin: MmCoinEnum = try_tr! (CoinConfigError, lp_coininit (&ctx, &ticker, &req) .await);
+    let balance = try_tr! (TransportError, coin.my_balance().compat().await);
     let res = json! ({
         "result": "success",
         "address": coin.my_address(),
@@ -88,9 +98,13 @@ pub async fn electrum (ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, Strin
 
 /// Enable a coin in the local wallet mode.
 pub async fn enable (ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
-    let ticker = try_s! (req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
-    let coin: MmCoinEnum = try_s! (lp_coininit (&ctx, &ticker, &req) .await);
-    let balance = try_s! (coin.my_balance().compat().await);
+    let ticker = try_tr! (InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    if try_s! (lp_coinfind (&ctx, &ticker) .await) .is_some() {
+        return RpcError::new (RpcErrorCode::CoinAlreadyEnabled, ERRL! ("Coin {} already initialized", ticker)) .into_response()
+    }
+    try_tr! (CoinConfigError, check_coin_swap_conf (&ctx.conf, &ticker));
+    let coin: MmCoinEnum = try_tr! (CoinConfigError, lp_coininit (&ctx, &ticker, &req) .await);
+    let balance = try_tr! (TransportError, coin.my_balance().compat().await);
     let res = json! ({
         "result": "success",
         "address": coin.my_address(),
@@ -103,38 +117,23 @@ pub async fn enable (ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String>
     Ok (try_s! (Response::builder().body (res)))
 }
 
-pub fn help() -> HyRes {
-    rpc_response(200, "
-        buy(base, rel, price, relvolume, timeout=10, duration=3600)
-        electrum(coin, urls)
-        enable(coin, urls, swap_contract_address)
-        myprice(base, rel)
-        my_balance(coin)
-        my_swap_status(params/uuid)
-        orderbook(base, rel, duration=3600)
-        sell(base, rel, price, basevolume, timeout=10, duration=3600)
-        send_raw_transaction(coin, tx_hex)
-        setprice(base, rel, price, broadcast=1)
-        stop()
-        version
-        withdraw(coin, amount, to)
-    ")
-}
-
 /// Get my_balance of a coin
 pub fn my_balance (ctx: MmArc, req: Json) -> HyRes {
-    let ticker = try_h! (req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
+    let ticker = try_th! (InvalidRequest, req["coin"].as_str().ok_or ("No 'coin' field")).to_owned();
     let coin = match block_on (lp_coinfind (&ctx, &ticker)) {
         Ok (Some (t)) => t,
-        Ok (None) => return rpc_err_response (500, &fomat! ("No such coin: " (ticker))),
+        Ok (None) => return rpc_typed_err_response (RpcError::new (RpcErrorCode::CoinNotEnabled, fomat! ("No such coin: " (ticker)))),
         Err (err) => return rpc_err_response (500, &fomat! ("!lp_coinfind(" (ticker) "): " (err)))
     };
-    Box::new(coin.my_balance().and_then(move |balance| rpc_response(200, json!({
-        "coin": ticker,
-        "balance": balance,
-        "locked_by_swaps": get_locked_amount(&ctx, &ticker),
-        "address": coin.my_address(),
-    }).to_string())))
+    Box::new(coin.my_balance().then(move |balance| {
+        let balance = try_th! (TransportError, balance);
+        rpc_response(200, json!({
+            "coin": ticker,
+            "balance": balance,
+            "locked_by_swaps": get_locked_amount(&ctx, &ticker),
+            "address": coin.my_address(),
+        }).to_string())
+    }))
 }
 
 /*
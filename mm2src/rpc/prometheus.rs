@@ -0,0 +1,79 @@
+//! The "/metrics" endpoint, exposing the node state in the Prometheus text format.
+//!
+//! The endpoint is authenticated with the `rpc_password` (or an API key allowing the "metrics" method)
+//! passed as a bearer token, which is what the Prometheus `bearer_token` scrape option does:
+//!
+//!     curl -H "Authorization: Bearer $userpass" http://127.0.0.1:7783/metrics
+//!
+//! The counters are accumulated in the `MmCtx::metrics`, the gauges are sampled from the contexts on every scrape.
+
+use coins::coins_metrics;
+use common::metrics::{write_family, Sample};
+use common::mm_ctx::MmArc;
+use http::Response;
+use http::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE};
+use std::collections::HashMap;
+
+use crate::mm2::lp_ordermatch::ordermatch_metrics;
+use crate::mm2::lp_swap::active_swaps_by_state;
+
+/// The userpass from the "Authorization: Bearer ..." header.
+pub fn bearer_token (headers: &HeaderMap) -> Option<String> {
+    let value = headers.get (AUTHORIZATION) ?.to_str().ok()?;
+    if value.starts_with ("Bearer ") {Some (value[7..].trim().to_owned())} else {None}
+}
+
+fn pair_samples (name: &str, labels: &[(&'static str, &str)], pairs: &HashMap<(String, String), usize>) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = pairs.iter().map (|((base, rel), count)| {
+        let mut pair_labels: Vec<(&'static str, String)> = labels.iter().map (|&(k, v)| (k, v.to_owned())) .collect();
+        pair_labels.push (("base", base.clone()));
+        pair_labels.push (("rel", rel.clone()));
+        Sample::new (name, pair_labels, *count as f64)
+    }) .collect();
+    samples.sort_by (|a, b| a.labels.cmp (&b.labels));
+    samples
+}
+
+pub async fn metrics (ctx: MmArc) -> Result<Response<Vec<u8>>, String> {
+    let mut out = String::new();
+
+    let mut swaps: Vec<Sample> = try_s! (active_swaps_by_state (&ctx)) .into_iter().map (|((swap_type, state), count)|
+        Sample::new ("mm2_swaps_active", vec! [("type", swap_type.into()), ("state", state)], count as f64)) .collect();
+    swaps.sort_by (|a, b| a.labels.cmp (&b.labels));
+    write_family (&mut out, "mm2_swaps_active", "gauge", "The number of the running swaps by the type and the last event.", &swaps);
+    write_family (&mut out, "mm2_swaps_finished_total", "counter", "The number of the swaps finished without errors.",
+        &ctx.metrics.samples ("mm2_swaps_finished_total"));
+    write_family (&mut out, "mm2_swaps_failed_total", "counter", "The number of the swaps finished with errors.",
+        &ctx.metrics.samples ("mm2_swaps_failed_total"));
+
+    let ordermatch = try_s! (ordermatch_metrics (&ctx));
+    let mut orders = pair_samples ("mm2_orders", &[("type", "maker")], &ordermatch.maker_orders);
+    orders.extend (pair_samples ("mm2_orders", &[("type", "taker")], &ordermatch.taker_orders));
+    write_family (&mut out, "mm2_orders", "gauge", "The number of my orders by the type and the pair.", &orders);
+    write_family (&mut out, "mm2_orderbook_orders", "gauge", "The number of the orderbook entries by the pair.",
+        &pair_samples ("mm2_orderbook_orders", &[], &ordermatch.orderbook));
+
+    let coins = try_s! (coins_metrics (&ctx) .await);
+    let mut electrum = Vec::new();
+    let mut history = Vec::new();
+    for coin in coins.iter() {
+        for (server, connected) in coin.electrum_connections.iter() {
+            electrum.push (Sample::new ("mm2_electrum_connected", vec! [("coin", coin.ticker.clone()), ("server", server.clone())],
+                if *connected {1.} else {0.}))
+        }
+        history.push (Sample::new ("mm2_history_sync_state", vec! [("coin", coin.ticker.clone()), ("state", coin.history_sync.into())], 1.))
+    }
+    write_family (&mut out, "mm2_electrum_connected", "gauge", "1 if the Electrum server of the coin is connected, 0 otherwise.", &electrum);
+    write_family (&mut out, "mm2_history_sync_state", "gauge", "The transaction history synchronization state of the coin.", &history);
+
+    write_family (&mut out, "mm2_rpc_request_duration_seconds", "summary", "The time spent in the RPC method handlers.",
+        &ctx.metrics.samples ("mm2_rpc_request_duration_seconds"));
+    write_family (&mut out, "mm2_p2p_messages_sent_total", "counter", "The number of the P2P messages sent.",
+        &ctx.metrics.samples ("mm2_p2p_messages_sent_total"));
+    write_family (&mut out, "mm2_p2p_messages_received_total", "counter", "The number of the P2P messages received.",
+        &ctx.metrics.samples ("mm2_p2p_messages_received_total"));
+
+    Ok (try_s! (Response::builder()
+        .header (CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body (out.into_bytes())))
+}
@@ -0,0 +1,613 @@
+//! Machine-readable description of the RPC methods served by the `dispatcher`.
+//!
+//! The "rpc_schema" method returns a JSON Schema (draft-07) document with an entry for every method:
+//!
+//!     {"method": "rpc_schema"}
+//!     {"result": {"$schema": "...", "definitions": {...}, "methods": {"buy": {"summary": "...", "public": false,
+//!      "request": {"type": "object", "required": ["method", "userpass", "base", ...], "properties": {...}},
+//!      "response": {...}}, ...}}}
+//!
+//! The "request" schemas list the top-level request fields (including the "method" and the "userpass")
+//! along with their defaults, allowing the clients to be generated instead of maintained by hand.
+//! The "help" method renders the same table as a plain text.
+//!
+//! NB: When adding a method to the `dispatcher`, add its descriptor to `rpc_methods`,
+//! `test_every_dispatcher_method_is_described` will fail otherwise.
+
+use common::{rpc_response, HyRes, MM_VERSION};
+use serde_json::{self as json, Value as Json};
+
+use super::PUBLIC_METHODS;
+
+pub struct RpcMethodSchema {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// JSON Schema of the method-specific request fields.
+    pub params: Json,
+    /// JSON Schema of the successful response.
+    pub response: Json,
+}
+
+impl RpcMethodSchema {
+    /// True if the method can be invoked without the "userpass" (cf. `PUBLIC_METHODS`).
+    pub fn is_public (&self) -> bool {PUBLIC_METHODS.contains (&Some (self.name))}
+
+    /// The complete request schema, with the "method" and "userpass" fields added to the `params`.
+    pub fn request (&self) -> Json {
+        let mut request = self.params.clone();
+        let mut required = vec! [json! ("method")];
+        if !self.is_public() {required.push (json! ("userpass"))}
+        if let Some (params_required) = request["required"].as_array() {required.extend (params_required.iter().cloned())}
+        request["required"] = Json::Array (required);
+        request["properties"]["method"] = json! ({"type": "string", "enum": [self.name]});
+        request["properties"]["userpass"] = json! ({"type": "string",
+            "description": "The `rpc_password` or an API key allowing the method"});
+        request["additionalProperties"] = json! (true);
+        request
+    }
+
+    /// "my_tx_history(coin, from_id, limit=10)": the required fields followed by the optional ones with their defaults.
+    pub fn signature (&self) -> String {
+        let required: Vec<&str> = match self.params["required"].as_array() {
+            Some (required) => required.iter().filter_map (|name| name.as_str()) .collect(),
+            None => Vec::new()
+        };
+        let mut args: Vec<String> = required.iter().map (|name| (*name).to_owned()) .collect();
+        if let Some (properties) = self.params["properties"].as_object() {
+            for (name, property) in properties.iter() {
+                if required.contains (&&name[..]) {continue}
+                match property.get ("default") {
+                    Some (default) => args.push (fomat! ((name) "=" (default))),
+                    None => args.push (name.clone())
+                }
+            }
+        }
+        fomat! ((self.name) "(" (args.join (", ")) ")")
+    }
+}
+
+/// An object schema with the given `properties`.
+fn obj (required: &[&str], properties: Json) -> Json {
+    json! ({"type": "object", "required": required, "properties": properties})
+}
+
+fn no_params() -> Json {obj (&[], json! ({}))}
+
+fn def (name: &str) -> Json {json! ({"$ref": fomat! ("#/definitions/" (name))})}
+
+fn string (description: &str) -> Json {json! ({"type": "string", "description": description})}
+
+/// The swap methods are taking the "uuid" from the "params".
+fn swap_uuid_params() -> Json {obj (&["params"], json! ({"params": obj (&["uuid"], json! ({"uuid": def ("Uuid")}))}))}
+
+fn result (schema: Json) -> Json {obj (&["result"], json! ({"result": schema}))}
+
+fn success() -> Json {result (json! ({"type": "string", "enum": ["success"]}))}
+
+/// The types shared between the methods.
+fn definitions() -> Json {
+    json! ({
+        "Ticker": {"type": "string", "description": "The coin ticker, like \"KMD\""},
+        "Uuid": {"type": "string", "format": "uuid"},
+        "Number": {
+            "description": "A decimal number as a string (\"0.1\") or a JSON number. \
+                            The rational numbers are accepted as well in the `num_rational::BigRational` serialization",
+            "type": ["string", "number", "array"]
+        },
+        "Hex": {"type": "string", "pattern": "^([0-9a-fA-F]{2})*$"},
+        "ElectrumServer": obj (&["url"], json! ({
+            "url": string ("The server address, like \"electrum1.cipig.net:10001\""),
+            "protocol": {"type": "string", "enum": ["TCP", "SSL"], "default": "TCP"},
+            "disable_cert_verification": {"type": "boolean", "default": false}
+        })),
+        "WithdrawFee": {"oneOf": [
+            obj (&["type", "amount"], json! ({"type": {"enum": ["UtxoFixed"]}, "amount": def ("Number")})),
+            obj (&["type", "amount"], json! ({"type": {"enum": ["UtxoPerKbyte"]}, "amount": def ("Number")})),
+            obj (&["type", "gas_price", "gas"], json! ({"type": {"enum": ["EthGas"]},
+                "gas_price": {"allOf": [def ("Number")], "description": "In gwei"}, "gas": {"type": "integer"}}))
+        ]},
+        "TimeInForce": {"oneOf": [
+            obj (&["type"], json! ({"type": {"enum": ["GoodTillCancelled"]}})),
+            obj (&["type", "expires_at"], json! ({"type": {"enum": ["GoodTillTime"]},
+                "expires_at": {"type": "integer", "description": "UNIX timestamp in seconds"}})),
+            obj (&["type"], json! ({"type": {"enum": ["ImmediateOrCancel"]}, "description": "Taker only"})),
+            obj (&["type"], json! ({"type": {"enum": ["FillOrKill"]}, "description": "Taker only, never partially filled"}))
+        ]},
+        "OrderStatus": {"type": "string", "enum": ["Created", "Matched", "PartiallyFilled", "Filled", "Cancelled", "Expired"]},
+        "OrderHistoryRecord": obj (&["uuid", "type", "action", "base", "rel", "price", "volume", "status", "swaps", "created_at", "updated_at"], json! ({
+            "uuid": def ("Uuid"),
+            "type": {"type": "string", "enum": ["Maker", "Taker"]},
+            "action": {"type": "string", "enum": ["Buy", "Sell"]},
+            "base": def ("Ticker"),
+            "rel": def ("Ticker"),
+            "price": def ("Number"),
+            "volume": def ("Number"),
+            "status": def ("OrderStatus"),
+            "cancellation_reason": {"type": ["string", "null"],
+                "enum": ["Cancelled", "Replaced", "Expired", "NotMatched", "InsufficientVolume", null]},
+            "swaps": {"type": "array", "items": def ("Uuid")},
+            "created_at": {"type": "integer", "description": "UNIX timestamp in milliseconds"},
+            "updated_at": {"type": "integer", "description": "UNIX timestamp in milliseconds"}
+        })),
+        "OrderType": {"oneOf": [
+            obj (&["type"], json! ({"type": {"enum": ["Limit"]}})),
+            obj (&["type", "max_slippage"], json! ({"type": {"enum": ["Market"]},
+                "max_slippage": {"allOf": [def ("Number")], "description": "The percentage the price can be worse than the best price in the orderbook. \
+                    The market orders are ImmediateOrCancel unless FillOrKill is requested"}}))
+        ]},
+        "BestOrder": obj (&["uuid", "pubkey", "coin", "price", "max_volume", "min_volume", "age"], json! ({
+            "uuid": def ("Uuid"),
+            "pubkey": {"type": "string"},
+            "coin": {"allOf": [def ("Ticker")], "description": "The counterpart coin"},
+            "price": {"allOf": [def ("Number")], "description": "In the counterpart coin per the requested coin"},
+            "price_rat": def ("Number"),
+            "max_volume": {"allOf": [def ("Number")], "description": "In the requested coin"},
+            "max_volume_rat": def ("Number"),
+            "min_volume": {"allOf": [def ("Number")], "description": "In the requested coin"},
+            "min_volume_rat": def ("Number"),
+            "age": {"type": "integer", "description": "The seconds since the last price ping of the order"}
+        })),
+        "OrderbookEntry": obj (&["coin", "address", "price", "maxvolume", "min_volume", "pubkey", "age"], json! ({
+            "coin": def ("Ticker"),
+            "address": {"type": "string"},
+            "price": def ("Number"),
+            "price_rat": def ("Number"),
+            "maxvolume": {"type": "number", "description": "The volume in the `coin`, which is the rel coin for the bids"},
+            "max_volume_rat": def ("Number"),
+            "base_max_volume": {"allOf": [def ("Number")], "description": "The `maxvolume` in the base coin"},
+            "base_max_volume_rat": def ("Number"),
+            "min_volume": {"allOf": [def ("Number")], "description": "The smaller requests are not matched with the order"},
+            "min_volume_rat": def ("Number"),
+            "depth": {"allOf": [def ("Number")], "description": "The cumulative base coin volume up to and including this order"},
+            "depth_rat": def ("Number"),
+            "pubkey": {"type": "string"},
+            "age": {"type": "integer"}
+        })),
+        "PriceLevel": obj (&["price", "volume", "depth", "orders"], json! ({
+            "price": def ("Number"),
+            "price_rat": def ("Number"),
+            "volume": {"allOf": [def ("Number")], "description": "The base coin volume of the orders at this level"},
+            "volume_rat": def ("Number"),
+            "depth": {"allOf": [def ("Number")], "description": "The cumulative base coin volume up to and including this level"},
+            "depth_rat": def ("Number"),
+            "orders": {"type": "integer"}
+        })),
+        "CancelBy": {"oneOf": [
+            obj (&["type"], json! ({"type": {"enum": ["All"]}})),
+            obj (&["type", "data"], json! ({"type": {"enum": ["Pair"]},
+                "data": obj (&["base", "rel"], json! ({"base": def ("Ticker"), "rel": def ("Ticker")}))})),
+            obj (&["type", "data"], json! ({"type": {"enum": ["Coin"]},
+                "data": obj (&["ticker"], json! ({"ticker": def ("Ticker")}))}))
+        ]},
+        "TransactionDetails": obj (
+            &["tx_hex", "tx_hash", "from", "to", "total_amount", "spent_by_me", "received_by_me", "my_balance_change",
+              "block_height", "timestamp", "coin", "internal_id"],
+            json! ({
+                "tx_hex": def ("Hex"),
+                "tx_hash": string ("The transaction hash in hexadecimal format"),
+                "from": {"type": "array", "items": {"type": "string"}},
+                "to": {"type": "array", "items": {"type": "string"}},
+                "total_amount": def ("Number"),
+                "spent_by_me": def ("Number"),
+                "received_by_me": def ("Number"),
+                "my_balance_change": def ("Number"),
+                "block_height": {"type": "integer"},
+                "timestamp": {"type": "integer"},
+                "fee_details": {"type": "object"},
+                "coin": def ("Ticker"),
+                "internal_id": def ("Hex")
+            })),
+        "Balance": obj (&["coin", "balance", "locked_by_swaps", "address"], json! ({
+            "coin": def ("Ticker"),
+            "balance": def ("Number"),
+            "locked_by_swaps": def ("Number"),
+            "address": {"type": "string"}
+        })),
+        "EnableResult": obj (&["result", "coin", "address", "balance", "locked_by_swaps", "required_confirmations"], json! ({
+            "result": {"type": "string", "enum": ["success"]},
+            "coin": def ("Ticker"),
+            "address": {"type": "string"},
+            "balance": def ("Number"),
+            "locked_by_swaps": def ("Number"),
+            "required_confirmations": {"type": "integer"}
+        })),
+        "Error": obj (&["error"], json! ({
+            "error": string ("The human-readable error"),
+            "error_code": string ("cf. `RpcErrorCode`"),
+            "error_category": string ("cf. `RpcErrorCategory`"),
+            "error_details": {"type": "object"}
+        }))
+    })
+}
+
+/// The descriptors of the methods handled by the `dispatcher`, sorted alphanumerically.
+pub fn rpc_methods() -> Vec<RpcMethodSchema> {
+    let trade_params = || obj (&["base", "rel", "volume"], json! ({
+        "base": def ("Ticker"),
+        "rel": def ("Ticker"),
+        "price": {"allOf": [def ("Number")], "description": "Required for the limit orders, computed from the orderbook for the market orders"},
+        "volume": def ("Number"),
+        "timeout": {"type": "integer"},
+        "duration": {"type": "integer", "description": "Deprecated, not used"},
+        "gui": {"type": "string"},
+        "destpubkey": {"allOf": [def ("Hex")], "description": "Not used"},
+        "order_type": {"allOf": [def ("OrderType")], "default": {"type": "Limit"}},
+        "time_in_force": {"allOf": [def ("TimeInForce")], "default": {"type": "GoodTillCancelled"},
+            "description": "The unmatched (amount of the) ImmediateOrCancel and FillOrKill orders are cancelled instead of becoming maker orders. \
+                The orders other than FillOrKill can be partially filled by several maker orders"},
+        "taker_only": {"type": "boolean", "description": "Makes the GoodTillCancelled order ImmediateOrCancel. Defaults to the \"taker_only\" configuration"}
+    }));
+    macro_rules! method {($name: expr, $summary: expr, $params: expr, $response: expr) => {
+        RpcMethodSchema {name: $name, summary: $summary, params: $params, response: $response}
+    }}
+
+    vec! [
+        method! ("ban_pubkey", "Drops the orders of the pubkey from the orderbook and ignores its price pings until the `unban_pubkey`.",
+            obj (&["pubkey"], json! ({
+                "pubkey": {"type": "string", "description": "The hex of the node pubkey, as shown in the orderbook"},
+                "reason": string ("Remembered along with the ban")
+            })),
+            result (obj (&["pubkey", "removed_orders"], json! ({
+                "pubkey": {"type": "string"},
+                "removed_orders": {"type": "integer"}
+            })))),
+        method! ("best_orders", "The best orders of the other nodes to trade the volume of the coin for any other coin, by the counterpart coin.",
+            obj (&["coin", "action", "volume"], json! ({
+                "coin": def ("Ticker"),
+                "action": {"type": "string", "enum": ["buy", "sell"]},
+                "volume": {"allOf": [def ("Number")], "description": "The orders not able to match this volume of the coin are skipped"}
+            })),
+            result (json! ({"type": "object", "description": "The counterpart coin to its orders, the best price first",
+                "additionalProperties": {"type": "array", "items": def ("BestOrder")}}))),
+        method! ("buy", "Creates a taker order buying the `volume` of the `base` coin for the `rel` coin.",
+            trade_params(), result (json! ({"type": "object", "description": "The taker request"}))),
+        method! ("cancel_all_orders", "Cancels the orders selected by the `cancel_by` condition.",
+            obj (&["cancel_by"], json! ({"cancel_by": def ("CancelBy")})),
+            result (obj (&["cancelled", "currently_matching"], json! ({
+                "cancelled": {"type": "array", "items": def ("Uuid")},
+                "currently_matching": {"type": "array", "items": def ("Uuid")}
+            })))),
+        method! ("cancel_conditional_order", "Cancels the pending stop-loss or take-profit order.",
+            obj (&["uuid"], json! ({"uuid": def ("Uuid")})), success()),
+        method! ("cancel_order", "Cancels the order with the given `uuid`.",
+            obj (&["uuid"], json! ({"uuid": def ("Uuid")})), success()),
+        method! ("coins_needed_for_kick_start", "The coins that should be enabled in order to continue the unfinished swaps.",
+            no_params(), result (json! ({"type": "array", "items": def ("Ticker")}))),
+        method! ("conditional_order", "Creates the stop-loss or take-profit order: the `order` is sent when the `price_source` price crosses the `trigger` price.",
+            obj (&["trigger", "order"], json! ({
+                "trigger": obj (&["type", "price"], json! ({
+                    "type": {"type": "string", "enum": ["StopLoss", "TakeProfit"]},
+                    "price": {"allOf": [def ("Number")], "description": "In the `rel` coin per the `base` coin"},
+                    "min_depth": {"allOf": [def ("Number")],
+                        "description": "The `base` coin volume of the orders needed at the trigger price or better, the order `volume` by default (\"orderbook\" only)"}
+                })),
+                "price_source": {"type": "string", "enum": ["orderbook", "external"], "default": "orderbook",
+                    "description": "The local orderbook, or the BTC prices of the coins from CoinGecko (CoinMarketCap with the \"cmc_key\" configuration)"},
+                "order": {"type": "object", "description": "The parameters of the \"buy\" or \"sell\" request, including the `method`"}
+            })), result (json! ({"type": "object", "description": "The conditional order"}))),
+        method! ("disable_coin", "Disables the coin, cancelling its orders. Fails if the coin is used by the active swaps or the matching orders.",
+            obj (&["coin"], json! ({"coin": def ("Ticker")})),
+            result (obj (&["coin", "cancelled_orders"], json! ({
+                "coin": def ("Ticker"),
+                "cancelled_orders": {"type": "array", "items": def ("Uuid")}
+            })))),
+        method! ("electrum", "Enables the UTXO coin in the Electrum mode.",
+            obj (&["coin", "servers"], json! ({
+                "coin": def ("Ticker"),
+                "servers": {"type": "array", "items": def ("ElectrumServer")},
+                "mm2": {"type": "integer", "enum": [0, 1], "description": "Overrides the \"mm2\" field of the coin config"},
+                "tx_history": {"type": "boolean", "default": false}
+            })), def ("EnableResult")),
+        method! ("enable", "Enables the UTXO coin in the native mode (with the local coin daemon) or the ETH/ERC20 coin.",
+            obj (&["coin"], json! ({
+                "coin": def ("Ticker"),
+                "urls": {"type": "array", "items": {"type": "string"}, "description": "The ETH nodes (ETH/ERC20 only)"},
+                "swap_contract_address": string ("The swap contract address (ETH/ERC20 only)"),
+                "gas_station_url": string ("ETH/ERC20 only"),
+                "mm2": {"type": "integer", "enum": [0, 1], "description": "Overrides the \"mm2\" field of the coin config"},
+                "tx_history": {"type": "boolean", "default": false}
+            })), def ("EnableResult")),
+        method! ("get_enabled_coins", "The list of the enabled coins and their addresses.",
+            no_params(), result (json! ({"type": "array", "items": obj (&["ticker", "address"], json! ({
+                "ticker": def ("Ticker"), "address": {"type": "string"}
+            }))}))),
+        method! ("get_trade_fee", "The approximate fee of the swap transactions in the coin.",
+            obj (&["coin"], json! ({"coin": def ("Ticker")})),
+            result (obj (&["coin", "amount"], json! ({"coin": def ("Ticker"), "amount": def ("Number")})))),
+        method! ("help", "The list of the RPC methods and their parameters, as a plain text.",
+            no_params(), json! ({"type": "string"})),
+        method! ("import_swaps", "Imports the swaps exported from another node (the `my_swap_status` results).",
+            obj (&["swaps"], json! ({"swaps": {"type": "array", "items": {"type": "object"}}})),
+            result (obj (&["imported", "skipped"], json! ({
+                "imported": {"type": "array", "items": def ("Uuid")},
+                "skipped": {"type": "object", "additionalProperties": {"type": "string"}}
+            })))),
+        method! ("list_api_keys", "The API keys (cf. the `rpc_api_keys` configuration).",
+            no_params(), result (json! ({"type": "array", "items": {"type": "object"}}))),
+        method! ("my_balance", "The balance of the enabled coin.",
+            obj (&["coin"], json! ({"coin": def ("Ticker")})), def ("Balance")),
+        method! ("my_conditional_orders", "The stop-loss and take-profit orders of this node with their trigger history, most recent first.",
+            no_params(), result (json! ({"type": "array", "items": {"type": "object"}}))),
+        method! ("my_orders", "The maker and taker orders of this node.",
+            no_params(), result (obj (&["maker_orders", "taker_orders"], json! ({
+                "maker_orders": {"type": "object", "additionalProperties": {"type": "object"}},
+                "taker_orders": {"type": "object", "additionalProperties": {"type": "object"}}
+            })))),
+        method! ("my_recent_swaps", "The recent swaps of this node, most recent first.",
+            obj (&[], json! ({
+                "limit": {"type": "integer", "default": 10},
+                "from_uuid": {"allOf": [def ("Uuid")], "description": "Skip the swaps up to and including this one"}
+            })), result (json! ({"type": "object"}))),
+        method! ("my_swap_status", "The status of the swap of this node.",
+            swap_uuid_params(), result (json! ({"type": "object"}))),
+        method! ("my_tx_history", "The transaction history of the coin enabled with `tx_history`.",
+            obj (&["coin"], json! ({
+                "coin": def ("Ticker"),
+                "limit": {"type": "integer", "default": 10},
+                "from_id": {"allOf": [def ("Hex")], "description": "The `internal_id` to skip the records up to"}
+            })),
+            result (obj (&["transactions"], json! ({
+                "transactions": {"type": "array", "items": def ("TransactionDetails")},
+                "from_id": def ("Hex"),
+                "limit": {"type": "integer"},
+                "skipped": {"type": "integer"},
+                "total": {"type": "integer"},
+                "current_block": {"type": "integer"},
+                "sync_status": {"type": "object"}
+            })))),
+        method! ("notify", "The P2P notifications from the other nodes.",
+            obj (&[], json! ({})), success()),
+        method! ("ohlc", "The OHLC candles of the `base`/`rel` pair built from the successful swaps observed in the network.",
+            obj (&["base", "rel"], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "interval": {"type": "integer", "minimum": 60, "default": 3600, "description": "The candle interval, in seconds"},
+                "from": {"type": "integer", "description": "UNIX timestamp in seconds"},
+                "to": {"type": "integer", "description": "UNIX timestamp in seconds"}
+            })),
+            result (obj (&["base", "rel", "interval", "candles"], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "interval": {"type": "integer"},
+                "candles": {"type": "array", "items": obj (&[], json! ({
+                    "timestamp": {"type": "integer", "description": "The start of the interval"},
+                    "open": def ("Number"),
+                    "high": def ("Number"),
+                    "low": def ("Number"),
+                    "close": def ("Number"),
+                    "volume": {"allOf": [def ("Number")], "description": "In the `base` coin"},
+                    "quote_volume": {"allOf": [def ("Number")], "description": "In the `rel` coin"},
+                    "swaps": {"type": "integer"}
+                }))}
+            })))),
+        method! ("order_status", "The status of the order of this node.",
+            obj (&["uuid"], json! ({"uuid": def ("Uuid")})),
+            obj (&["type", "order"], json! ({"type": {"type": "string", "enum": ["Maker", "Taker"]}, "order": {"type": "object"}}))),
+        method! ("orderbook", "The orderbook of the `base`/`rel` pair.",
+            obj (&["base", "rel"], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "depth": {"type": "integer", "minimum": 1, "description": "The maximal number of the price levels on each side"},
+                "price_step": {"allOf": [def ("Number")],
+                    "description": "Aggregates the orders into the price levels of this step, the orders are grouped by the exact price otherwise"}
+            })),
+            obj (&["asks", "bids", "ask_levels", "bid_levels", "base", "rel"], json! ({
+                "asks": {"type": "array", "items": def ("OrderbookEntry"), "description": "Sorted by the ascending price"},
+                "bids": {"type": "array", "items": def ("OrderbookEntry"), "description": "Sorted by the descending price"},
+                "ask_levels": {"type": "array", "items": def ("PriceLevel")},
+                "bid_levels": {"type": "array", "items": def ("PriceLevel")},
+                "askdepth": {"type": "number", "description": "The total base coin volume of the returned asks. A fractional number, it used to be an integer which was always 0"},
+                "biddepth": {"type": "number", "description": "The total base coin volume of the returned bids. A fractional number, it used to be an integer which was always 0"},
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "numasks": {"type": "integer"},
+                "numbids": {"type": "integer"},
+                "timestamp": {"type": "integer"}
+            }))),
+        method! ("orderbook_limits", "The limits on the orders of the other nodes, the banned pubkeys and the number of the orders per pubkey.",
+            no_params(),
+            result (obj (&["limits", "banned_pubkeys", "pubkeys"], json! ({
+                "limits": obj (&["max_orders_per_pubkey", "max_orders_per_pair", "max_pairs_per_pubkey"], json! ({
+                    "max_orders_per_pubkey": {"type": "integer"},
+                    "max_orders_per_pair": {"type": "integer"},
+                    "max_pairs_per_pubkey": {"type": "integer"}
+                })),
+                "banned_pubkeys": {"type": "array", "items": obj (&["pubkey", "reason"], json! ({
+                    "pubkey": {"type": "string"},
+                    "reason": {"type": "string"}
+                }))},
+                "pubkeys": {"type": "array", "description": "The pubkeys having the most orders first", "items": obj (&["pubkey", "orders", "pairs"], json! ({
+                    "pubkey": {"type": "string"},
+                    "orders": {"type": "integer"},
+                    "pairs": {"type": "integer"}
+                }))}
+            })))),
+        method! ("orders_history", "The history of my maker and taker orders, the most recently created first.",
+            obj (&[], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "type": {"type": "string", "enum": ["Maker", "Taker"]},
+                "status": def ("OrderStatus"),
+                "from_timestamp": {"type": "integer", "description": "The orders created at or after this UNIX timestamp in seconds"},
+                "to_timestamp": {"type": "integer", "description": "The orders created before this UNIX timestamp in seconds"},
+                "limit": {"type": "integer", "default": 10},
+                "from_uuid": {"allOf": [def ("Uuid")], "description": "Skip the orders up to and including this one"}
+            })),
+            result (obj (&["orders", "skipped", "limit", "total"], json! ({
+                "orders": {"type": "array", "items": def ("OrderHistoryRecord")},
+                "from_uuid": def ("Uuid"),
+                "skipped": {"type": "integer"},
+                "limit": {"type": "integer"},
+                "total": {"type": "integer"}
+            })))),
+        method! ("pending_recoveries", "My failed swaps waiting for the funds to be recovered, the earliest unlocked first.",
+            no_params(),
+            result (json! ({"type": "array", "items": obj (&["uuid", "type", "maker_coin", "taker_coin", "unlock_time", "attempts", "next_attempt_at"], json! ({
+                "uuid": def ("Uuid"),
+                "type": {"type": "string", "enum": ["Maker", "Taker"]},
+                "maker_coin": def ("Ticker"),
+                "taker_coin": def ("Ticker"),
+                "unlock_time": {"type": "integer", "description": "The UNIX timestamp in seconds from which the funds can be recovered"},
+                "attempts": {"type": "integer", "description": "The number of the failed recovery attempts"},
+                "last_error": {"type": ["string", "null"]},
+                "next_attempt_at": {"type": "integer", "description": "The UNIX timestamp in seconds of the next recovery attempt"}
+            }))}))),
+        method! ("recover_funds_of_swap", "Refunds or spends the payment of the failed swap.",
+            swap_uuid_params(),
+            result (obj (&["action", "coin", "tx_hash", "tx_hex"], json! ({
+                "action": {"type": "string", "enum": ["RefundedMyPayment", "SpentOtherPayment"]},
+                "coin": def ("Ticker"),
+                "tx_hash": {"type": "string"},
+                "tx_hex": def ("Hex")
+            })))),
+        method! ("revoke_api_key", "Revokes the API key for the lifetime of the process.",
+            obj (&["name"], json! ({"name": string ("The name of the API key")})), success()),
+        method! ("rpc_schema", "This document.",
+            no_params(), result (json! ({"type": "object"}))),
+        method! ("sell", "Creates a taker order selling the `volume` of the `base` coin for the `rel` coin.",
+            trade_params(), result (json! ({"type": "object", "description": "The taker request"}))),
+        method! ("send_raw_transaction", "Broadcasts the signed transaction (cf. `withdraw`).",
+            obj (&["coin", "tx_hex"], json! ({"coin": def ("Ticker"), "tx_hex": def ("Hex")})),
+            obj (&["tx_hash"], json! ({"tx_hash": {"type": "string"}}))),
+        method! ("set_required_confirmations", "Sets the number of the confirmations the swaps should wait for.",
+            obj (&["coin", "confirmations"], json! ({"coin": def ("Ticker"), "confirmations": {"type": "integer", "minimum": 0}})),
+            result (obj (&["coin", "confirmations"], json! ({"coin": def ("Ticker"), "confirmations": {"type": "integer"}})))),
+        method! ("setprice", "Creates a maker order selling the `volume` of the `base` coin for the `rel` coin.",
+            obj (&["base", "rel", "price"], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "price": def ("Number"),
+                "volume": {"allOf": [def ("Number")], "default": "0"},
+                "max": {"type": "boolean", "default": false, "description": "Use the whole balance as the volume"},
+                "min_volume": {"allOf": [def ("Number")], "default": "0",
+                    "description": "The takers requesting less of the base coin are not matched with the order"},
+                "broadcast": {"type": "integer", "default": 1, "description": "Not used"},
+                "cancel_previous": {"type": "boolean", "default": true,
+                    "description": "Cancel the other maker orders of the pair"},
+                "time_in_force": {"allOf": [def ("TimeInForce")], "default": {"type": "GoodTillCancelled"},
+                    "description": "GoodTillCancelled or GoodTillTime"},
+                "allowed_pubkeys": {"type": "array", "items": {"type": "string"}, "default": [],
+                    "description": "Makes the order private: it's only sent to and matched with the nodes having these pubkeys"}
+            })), result (json! ({"type": "object", "description": "The maker order"}))),
+        method! ("stats_swap_status", "The status of any swap observed in the network.",
+            swap_uuid_params(),
+            result (obj (&[], json! ({"maker": {"type": ["object", "null"]}, "taker": {"type": ["object", "null"]}})))),
+        method! ("stop", "Stops the node.",
+            no_params(), success()),
+        method! ("ticker", "The last price and the 24h statistics of the `base`/`rel` pair from the successful swaps observed in the network.",
+            obj (&["base", "rel"], json! ({"base": def ("Ticker"), "rel": def ("Ticker")})),
+            result (obj (&["base", "rel", "volume_24h", "quote_volume_24h", "swaps_24h"], json! ({
+                "base": def ("Ticker"),
+                "rel": def ("Ticker"),
+                "last_price": {"type": ["string", "null"], "description": "In the `rel` coin per the `base` coin"},
+                "last_swap_at": {"type": ["integer", "null"]},
+                "high_24h": {"type": ["string", "null"]},
+                "low_24h": {"type": ["string", "null"]},
+                "volume_24h": {"allOf": [def ("Number")], "description": "In the `base` coin"},
+                "quote_volume_24h": {"allOf": [def ("Number")], "description": "In the `rel` coin"},
+                "swaps_24h": {"type": "integer"}
+            })))),
+        method! ("unban_pubkey", "Accepts the price pings of the pubkey banned with the `ban_pubkey` again.",
+            obj (&["pubkey"], json! ({"pubkey": {"type": "string"}})), success()),
+        method! ("update_maker_order", "Changes the price and the volumes of the maker order, keeping its uuid and matches.",
+            obj (&["uuid"], json! ({
+                "uuid": def ("Uuid"),
+                "price": def ("Number"),
+                "volume": {"allOf": [def ("Number")],
+                    "description": "The new volume available for matching, the amount reserved by the ongoing matches is added on top"},
+                "max": {"type": "boolean", "default": false, "description": "Use the whole balance as the available volume"},
+                "min_volume": def ("Number")
+            })), result (json! ({"type": "object", "description": "The maker order"}))),
+        method! ("version", "The version of the node.",
+            no_params(), result (json! ({"type": "string"}))),
+        method! ("withdraw", "Generates and signs a transaction sending the coins `to` the address, without broadcasting it.",
+            obj (&["coin", "to"], json! ({
+                "coin": def ("Ticker"),
+                "to": string ("The destination address"),
+                "amount": {"allOf": [def ("Number")], "default": "0"},
+                "max": {"type": "boolean", "default": false, "description": "Withdraw the whole balance"},
+                "fee": def ("WithdrawFee")
+            })), def ("TransactionDetails")),
+    ]
+}
+
+/// The JSON Schema document describing the RPC methods.
+pub fn schema_document() -> Json {
+    let mut methods = json::Map::new();
+    for method in rpc_methods() {
+        methods.insert (method.name.into(), json! ({
+            "summary": method.summary,
+            "public": method.is_public(),
+            "request": method.request(),
+            "response": method.response,
+        }));
+    }
+    json! ({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "MarketMaker 2 RPC",
+        "version": MM_VERSION,
+        "description": "POST the request to the RPC endpoint. The failed requests are answered with the non-200 HTTP status \
+                        and the \"Error\" body.",
+        "definitions": definitions(),
+        "methods": methods
+    })
+}
+
+pub fn rpc_schema() -> HyRes {
+    rpc_response (200, json! ({"result": schema_document()}) .to_string())
+}
+
+/// The plain text list of the methods, like "my_balance(coin)".
+pub fn help() -> HyRes {
+    let mut help = String::new();
+    for method in rpc_methods() {
+        if method.name == "notify" {continue}  // Internal.
+        help.push_str (&fomat! ("    " (method.signature()) "\n        " (method.summary) "\n"));
+    }
+    rpc_response (200, help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_dispatcher_method_is_described() {
+        // Parsing the `dispatcher` source in order to catch the methods added without a descriptor.
+        let rpc_rs = include_str! ("../rpc.rs");
+        let start = unwrap! (rpc_rs.find ("DispatcherRes::Match (match &method[..]"));
+        let end = start + unwrap! (rpc_rs[start..].find ("_ => return DispatcherRes::NoMatch"));
+        let dispatched: Vec<&str> = rpc_rs[start..end].lines()
+            .map (|line| line.trim_start())
+            .filter (|line| line.starts_with ('"'))
+            .filter_map (|line| line[1..].split ('"') .next())
+            .collect();
+        assert! (dispatched.len() > 20);
+
+        let described: Vec<&str> = rpc_methods().iter().map (|m| m.name) .collect();
+        for method in dispatched.iter() {assert! (described.contains (method), "No schema for {}", method)}
+        for method in described.iter() {assert! (dispatched.contains (method), "{} is not dispatched", method)}
+        let mut sorted = described.clone();
+        sorted.sort();
+        assert_eq! (described, sorted);
+    }
+
+    #[test]
+    fn test_rpc_schema_request() {
+        let methods = rpc_methods();
+        let setprice = unwrap! (methods.iter().find (|m| m.name == "setprice"));
+        let request = setprice.request();
+        assert_eq! (request["required"], json! (["method", "userpass", "base", "rel", "price"]));
+        assert_eq! (request["properties"]["cancel_previous"]["default"], json! (true));
+        assert_eq! (setprice.signature(),
+            "setprice(base, rel, price, allowed_pubkeys=[], broadcast=1, cancel_previous=true, max=false, min_volume=\"0\", time_in_force={\"type\":\"GoodTillCancelled\"}, volume=\"0\")");
+
+        let orderbook = unwrap! (methods.iter().find (|m| m.name == "orderbook"));
+        assert! (orderbook.is_public());
+        assert_eq! (orderbook.request()["required"], json! (["method", "base", "rel"]));
+
+        let document = schema_document();
+        assert! (document["methods"]["withdraw"]["request"]["properties"]["fee"]["$ref"].is_string());
+        assert! (document["definitions"]["WithdrawFee"]["oneOf"].is_array());
+    }
+}
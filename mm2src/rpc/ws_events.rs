@@ -0,0 +1,129 @@
+//! WebSocket endpoint pushing the swap, order and balance events to the GUI,
+//! allowing it to stop polling `my_swap_status`, `my_orders` and `my_balance`.
+//!
+//! Shares the port with the HTTP RPC. After the upgrade the client sends the JSON requests like
+//!
+//!     {"method": "subscribe", "userpass": "...", "topics": ["swap:<uuid>", "order:<uuid>", "coin:KMD"]}
+//!
+//! and receives the matching `Event`s:
+//!
+//!     {"topic": "coin", "id": "KMD", "event": "BalanceChanged", "data": {...}, "timestamp": 1565000000000}
+//!
+//! Topics are "swap", "order" and "coin" with an optional ":<id>" suffix, "*" subscribes to everything.
+//! "unsubscribe" removes the given topics (or all of them if the "topics" are missing).
+
+use common::event_stream::{Event, Subscription};
+use common::lift_body::LiftBody;
+use common::mm_ctx::MmArc;
+use common::wio::CORE;
+use futures01::{self, Future, Sink, Stream};
+use http::{Request, Response, StatusCode};
+use http::header::{HeaderMap, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use serde_json::{self as json, Value as Json};
+use sha1::{Digest, Sha1};
+use std::sync::{Arc, Mutex};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+use super::{auth, RpcClient};
+
+/// cf. https://tools.ietf.org/html/rfc6455#section-1.3
+const WS_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// True if the client asks to switch the HTTP connection to the WebSocket protocol.
+pub fn is_ws_upgrade (headers: &HeaderMap) -> bool {
+    match headers.get (UPGRADE) .and_then (|v| v.to_str().ok()) {
+        Some (upgrade) => upgrade.eq_ignore_ascii_case ("websocket"),
+        None => false
+}   }
+
+fn ws_accept_key (key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.input (key);
+    hasher.input (WS_GUID);
+    base64::encode (&hasher.result())
+}
+
+/// Accepts the WebSocket handshake and spawns the event session on the `CORE`.
+pub fn ws_upgrade (ctx: MmArc, req: Request<hyper::Body>, client: RpcClient) -> Result<Response<LiftBody<Vec<u8>>>, String> {
+    // Events carry the information about the user's swaps and balances, same as the private RPC methods do.
+    let local_only = ctx.conf["rpc_local_only"].as_bool().unwrap_or (true);
+    if local_only && !client.is_local() {return ERR! ("WebSocket events can be accessed from localhost only!")}
+
+    let key = try_s! (req.headers().get (SEC_WEBSOCKET_KEY) .ok_or ("No Sec-WebSocket-Key"));
+    let accept = ws_accept_key (key.as_bytes());
+
+    let session = req.into_body().on_upgrade()
+        .map_err (|err| ERRL! ("Upgrade error: {}", err))
+        .and_then (move |upgraded| ws_session (ctx, WebSocketStream::from_raw_socket (upgraded, Role::Server, None)))
+        .map_err (move |err| log! ("ws_events] Session with " (client) " ended: " (err)));
+    unwrap! (CORE.lock()) .spawn (session);
+
+    Ok (try_s! (Response::builder()
+        .status (StatusCode::SWITCHING_PROTOCOLS)
+        .header (UPGRADE, "websocket")
+        .header (CONNECTION, "Upgrade")
+        .header (SEC_WEBSOCKET_ACCEPT, accept)
+        .body (LiftBody::from (Vec::new()))))
+}
+
+fn ws_session (ctx: MmArc, ws: WebSocketStream<hyper::upgrade::Upgraded>) -> impl Future<Item=(), Error=String> {
+    let subscriptions: Arc<Mutex<Vec<Subscription>>> = Arc::new (Mutex::new (Vec::new()));
+    let (sink, stream) = ws.split();
+
+    let replies = stream
+        .map_err (|err| ERRL! ("{}", err))
+        .take_while (|msg| Ok (!msg.is_close()))
+        .filter_map ({
+            let ctx = ctx.clone();
+            let subscriptions = subscriptions.clone();
+            move |msg| match msg {
+                Message::Text (text) => Some (Message::Text (ws_request (&ctx, &subscriptions, &text) .to_string())),
+                Message::Ping (payload) => Some (Message::Pong (payload)),
+                _ => None
+        }   })
+        .map (Some)
+        // `None` marks the end of the client stream, allowing us to finish the session.
+        .chain (futures01::stream::once (Ok (None)));
+
+    let events = ctx.event_stream.subscribe()
+        .map_err (|_| ERRL! ("Event stream closed"))
+        .filter_map (move |event: Event| {
+            let subscriptions = unwrap! (subscriptions.lock());
+            if !subscriptions.iter().any (|s| s.matches (&event)) {return None}
+            match json::to_string (&event) {
+                Ok (event) => Some (Some (Message::Text (event))),
+                Err (err) => {log! ("ws_events] !to_string: " (err)); None}
+        }   });
+
+    let outgoing = replies.select (events) .take_while (|msg| Ok (msg.is_some())) .filter_map (|msg| msg);
+    sink.sink_map_err (|err| ERRL! ("{}", err)) .send_all (outgoing) .map (|_| ())
+}
+
+/// Handles a "subscribe" or "unsubscribe" request from the client, returning the reply.
+fn ws_request (ctx: &MmArc, subscriptions: &Mutex<Vec<Subscription>>, text: &str) -> Json {
+    macro_rules! try_j {($e: expr) => {match $e {Ok (ok) => ok, Err (err) => return json! ({"error": ERRL! ("{}", err)})}}}
+
+    let req: Json = try_j! (json::from_str (text));
+    try_j! (auth (&req, ctx));
+    let topics = match req["topics"].as_array() {
+        Some (topics) => try_j! (topics.iter()
+            .map (|t| t.as_str().ok_or (ERRL! ("Topic {} is not a string", t)) .and_then (Subscription::parse))
+            .collect::<Result<Vec<_>, String>>()),
+        None => Vec::new()
+    };
+
+    let mut subscriptions = try_j! (subscriptions.lock());
+    match req["method"].as_str() {
+        Some ("subscribe") => {
+            if topics.is_empty() {return json! ({"error": "No topics to subscribe to"})}
+            for topic in topics {if !subscriptions.contains (&topic) {subscriptions.push (topic)}}
+        },
+        Some ("unsubscribe") => {
+            if req["topics"].is_null() {subscriptions.clear()} else {subscriptions.retain (|s| !topics.contains (s))}
+        },
+        _ => return json! ({"error": ERRL! ("Unknown method {}", req["method"])})
+    }
+    json! ({"result": "success", "subscriptions": subscriptions.len()})
+}